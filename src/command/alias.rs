@@ -0,0 +1,74 @@
+use crate::helper::streams::Streams;
+use std::collections::BTreeMap;
+use std::io;
+
+// `alias` / `alias -p` lists, `alias name=value` defines, `alias name` shows one
+pub fn alias(
+    args: Vec<String>,
+    aliases: &mut BTreeMap<String, String>,
+    streams: &mut Streams,
+) -> io::Result<i32> {
+    if args.is_empty() || args == ["-p"] {
+        for (name, value) in aliases.iter() {
+            writeln!(streams.stdout, "alias {}={}", name, quote(value))?;
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for arg in args {
+        match arg.split_once('=') {
+            Some((name, value)) => {
+                if !is_valid_alias_name(name) {
+                    writeln!(streams.stderr, "alias: `{}': invalid alias name", name)?;
+                    status = 1;
+                    continue;
+                }
+                aliases.insert(name.to_string(), value.to_string());
+            }
+            None => match aliases.get(&arg) {
+                Some(value) => writeln!(streams.stdout, "alias {}={}", arg, quote(value))?,
+                None => {
+                    writeln!(streams.stderr, "alias: {}: not found", arg)?;
+                    status = 1;
+                }
+            },
+        }
+    }
+    Ok(status)
+}
+
+// Single quotes, so the output can be pasted back in as is
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+// Anything the tokenizer would split or treat specially can't be a name
+fn is_valid_alias_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.chars().any(|c| {
+            c.is_whitespace()
+                || matches!(c, '/' | '$' | '`' | '\\' | '\'' | '"' | '=')
+                || matches!(c, '|' | '&' | ';' | '<' | '>' | '(' | ')')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_round_trips_single_quotes() {
+        assert_eq!(quote("ls -l"), "'ls -l'");
+        assert_eq!(quote("echo 'hi'"), "'echo '\\''hi'\\'''");
+    }
+
+    #[test]
+    fn test_alias_names() {
+        assert!(is_valid_alias_name("ll"));
+        assert!(is_valid_alias_name("g++"));
+        assert!(!is_valid_alias_name("a/b"));
+        assert!(!is_valid_alias_name("x y"));
+        assert!(!is_valid_alias_name(""));
+    }
+}
@@ -0,0 +1,28 @@
+use crate::helper::jobs::JobTable;
+use crate::helper::streams::Streams;
+use std::io;
+
+// `bg [jobspec...]`: stopped jobs go on running in the background
+pub fn bg(args: Vec<String>, table: &mut JobTable, streams: &mut Streams) -> io::Result<i32> {
+    if !table.enabled() {
+        writeln!(streams.stderr, "bg: no job control")?;
+        return Ok(1);
+    }
+    let specs = if args.is_empty() {
+        vec!["%+".to_string()]
+    } else {
+        args
+    };
+
+    let mut status = 0;
+    for spec in specs {
+        match table.find(&spec).and_then(|id| table.background(id)) {
+            Ok(line) => writeln!(streams.stdout, "{}", line)?,
+            Err(e) => {
+                writeln!(streams.stderr, "bg: {}", e)?;
+                status = 1;
+            }
+        }
+    }
+    Ok(status)
+}
@@ -1,3 +1,4 @@
+use crate::helper::streams::Streams;
 use crossterm::{
     event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
     terminal::enable_raw_mode,
@@ -8,14 +9,20 @@ use std::{
     path::Path,
 };
 
-pub fn cat(args: Vec<String>) {
-    let stdout = io::stdout();
+pub fn cat(args: Vec<String>, streams: &mut Streams) -> io::Result<i32> {
+    let mut status = 0;
     if args.is_empty() {
+        // Piped input is copied straight through, only a terminal needs the key loop
+        if let Some(input) = streams.stdin.as_mut() {
+            io::copy(input, streams.stdout)?;
+            return Ok(0);
+        }
+
         match enable_raw_mode() {
             Ok(_) => (),
             Err(e) => {
                 eprintln!("Failed to enable raw mode: {}", e);
-                return;
+                return Ok(1);
             }
         }
 
@@ -32,6 +39,7 @@ pub fn cat(args: Vec<String>) {
                             break;
                         } else if c == 'c' {
                             print!("^C\r\n");
+                            status = 130;
                             break;
                         }
                         io::stdout().flush().ok();
@@ -43,17 +51,21 @@ pub fn cat(args: Vec<String>) {
                         io::stdout().flush().ok();
                     }
 
-                    KeyCode::Backspace => {
-                        if !input_buffer.is_empty() {
-                            input_buffer.pop();
-                            print!("\x08 \x08");
-                            io::stdout().flush().ok();
-                        }
+                    KeyCode::Backspace if !input_buffer.is_empty() => {
+                        input_buffer.pop();
+                        print!("\x08 \x08");
+                        io::stdout().flush().ok();
                     }
 
                     KeyCode::Enter => {
                         print!("\r\n");
-                        print!("{}\r\n", input_buffer);
+                        // Raw mode needs \r\n on the terminal, a pipe wants plain lines
+                        if streams.stdout_is_tty {
+                            write!(streams.stdout, "{}\r\n", input_buffer)?;
+                        } else {
+                            writeln!(streams.stdout, "{}", input_buffer)?;
+                        }
+                        streams.stdout.flush()?;
                         io::stdout().flush().ok();
                         input_buffer.clear();
                     }
@@ -67,16 +79,19 @@ pub fn cat(args: Vec<String>) {
             let source_path = Path::new(&file);
             let file_open = File::open(source_path);
             match file_open {
-                Ok(mut f) => match io::copy(&mut f, &mut stdout.lock()) {
+                Ok(mut f) => match io::copy(&mut f, streams.stdout) {
                     Ok(_) => (),
                     Err(e) => {
-                        eprintln!("cat: {}: {}", file, e);
+                        writeln!(streams.stderr, "cat: {}: {}", file, e)?;
+                        status = 1;
                     }
                 },
                 Err(e) => {
-                    eprintln!("cat: {}: {}", file, e);
+                    writeln!(streams.stderr, "cat: {}: {}", file, e)?;
+                    status = 1;
                 }
             }
         }
     }
+    Ok(status)
 }
@@ -1,32 +1,36 @@
 use crate::command::pwd::PwdState;
-use std::{env, io::ErrorKind, path::PathBuf};
+use crate::helper::streams::Streams;
+use crate::helper::variables::Variables;
+use std::{
+    env,
+    io::{self, ErrorKind},
+    path::PathBuf,
+};
 
-pub fn command_cd(error_path: Vec<String>, mut args: Vec<String>, pwd_state: &mut PwdState) {
+pub fn command_cd(
+    error_path: Vec<String>,
+    mut args: Vec<String>,
+    pwd_state: &mut PwdState,
+    vars: &Variables,
+    streams: &mut Streams,
+) -> io::Result<i32> {
     if args.len() > 1 {
-        eprintln!("cd: too many arguments");
-        return;
+        writeln!(streams.stderr, "cd: too many arguments")?;
+        return Ok(1);
     }
     if args.len() == 1 {
         args[0] = args[0].replace("\\n", "\n");
     }
     let target_dir = if args.is_empty() {
-        match env::var("HOME") {
-            Ok(path) => PathBuf::from(path),
-            Err(_) => {
-                eprintln!("cd: HOME environment variable not set");
-                return;
+        match vars.get("HOME") {
+            Some(path) => PathBuf::from(path),
+            None => {
+                writeln!(streams.stderr, "cd: HOME environment variable not set")?;
+                return Ok(1);
             }
         }
     } else if args[0] == "-" {
         PathBuf::from(pwd_state.get_old_dir())
-    } else if args[0] == "~" {
-        match env::var("HOME") {
-            Ok(path) => PathBuf::from(path),
-            Err(_) => {
-                eprintln!("cd: HOME environment variable not set");
-                return;
-            }
-        }
     } else {
         PathBuf::from(&args[0])
     };
@@ -39,7 +43,7 @@ pub fn command_cd(error_path: Vec<String>, mut args: Vec<String>, pwd_state: &mu
                 pwd_state.set_states(new_current.display().to_string(), current_before_move);
 
                 if !args.is_empty() && args[0] == "-" {
-                    println!("{}", pwd_state.get_current_dir());
+                    writeln!(streams.stdout, "{}", pwd_state.get_current_dir())?;
                 }
             } else {
                 pwd_state.set_states(
@@ -48,19 +52,27 @@ pub fn command_cd(error_path: Vec<String>, mut args: Vec<String>, pwd_state: &mu
                 );
             }
         }
-        Err(e) => match e.kind() {
-            ErrorKind::NotFound => {
-                eprintln!("cd:  No such file or directory : {}", error_path[0]);
-            }
-            ErrorKind::PermissionDenied => {
-                eprintln!("cd: Permission denied : {}", error_path[0]);
-            }
-            ErrorKind::NotADirectory => {
-                eprintln!("cd: Not a directory : {}", error_path[0]);
-            }
-            _ => {
-                eprintln!("cd: {}: {}", error_path[0], e);
+        Err(e) => {
+            match e.kind() {
+                ErrorKind::NotFound => {
+                    writeln!(
+                        streams.stderr,
+                        "cd:  No such file or directory : {}",
+                        error_path[0]
+                    )?;
+                }
+                ErrorKind::PermissionDenied => {
+                    writeln!(streams.stderr, "cd: Permission denied : {}", error_path[0])?;
+                }
+                ErrorKind::NotADirectory => {
+                    writeln!(streams.stderr, "cd: Not a directory : {}", error_path[0])?;
+                }
+                _ => {
+                    writeln!(streams.stderr, "cd: {}: {}", error_path[0], e)?;
+                }
             }
-        },
+            return Ok(1);
+        }
     }
+    Ok(0)
 }
@@ -1,28 +1,38 @@
-use std::{collections::HashSet, ffi::OsString, fs, path::Path};
+use crate::helper::streams::Streams;
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    path::Path,
+};
 
-pub fn cp(args: Vec<String>) {
+pub fn cp(args: Vec<String>, streams: &mut Streams) -> io::Result<i32> {
     if args.is_empty() {
-        eprintln!("cp: missing file operand");
-        return;
+        writeln!(streams.stderr, "cp: missing file operand")?;
+        return Ok(1);
     }
     if args.len() < 2 {
-        eprintln!(
+        writeln!(
+            streams.stderr,
             "cp: missing destination file operand after '{}'",
             args[0].replace("\n", "\\n")
-        );
-        return;
+        )?;
+        return Ok(1);
     }
 
+    let mut status = 0;
     let sources = &args[0..args.len() - 1];
     let destination_path: &Path = Path::new(args.last().unwrap());
 
     if args.len() > 2 {
         if !destination_path.is_dir() {
-            eprintln!(
+            writeln!(
+                streams.stderr,
                 "cp: target '{}' is not a directory",
                 destination_path.display().to_string().replace("\n", "\\n")
-            );
-            return;
+            )?;
+            return Ok(1);
         }
 
         let mut dest_seen: HashSet<OsString> = HashSet::new();
@@ -32,7 +42,8 @@ pub fn cp(args: Vec<String>) {
             if let Some(file_name) = source_path.file_name()
                 && !dest_seen.insert(file_name.to_os_string())
             {
-                eprintln!(
+                writeln!(
+                    streams.stderr,
                     "cp: warning: cannot copy '{}' to '{}': destination file already used by another argument",
                     source.replace("\n", "\\n"),
                     destination_path
@@ -40,43 +51,63 @@ pub fn cp(args: Vec<String>) {
                         .display()
                         .to_string()
                         .replace("\n", "\\n")
-                );
+                )?;
+                status = 1;
                 continue;
             }
 
-            copy_file_logic(source_path, destination_path, true);
+            status = status.max(copy_file_logic(
+                source_path,
+                destination_path,
+                true,
+                streams.stderr,
+            )?);
         }
     } else {
         let source_path = Path::new(&args[0]);
-        copy_file_logic(source_path, destination_path, destination_path.is_dir());
+        status = copy_file_logic(
+            source_path,
+            destination_path,
+            destination_path.is_dir(),
+            streams.stderr,
+        )?;
     }
+    Ok(status)
 }
 
-fn copy_file_logic(source: &Path, destination: &Path, dest_is_dir: bool) {
+fn copy_file_logic(
+    source: &Path,
+    destination: &Path,
+    dest_is_dir: bool,
+    err: &mut dyn Write,
+) -> io::Result<i32> {
     if !source.exists() {
-        eprintln!(
+        writeln!(
+            err,
             "cp: cannot stat '{}': No such file or directory",
             source.display().to_string().replace("\n", "\\n")
-        );
-        return;
+        )?;
+        return Ok(1);
     }
     if source.is_dir() {
-        eprintln!(
+        writeln!(
+            err,
             "cp: -r not specified; omitting directory '{}'",
             source.display().to_string().replace("\n", "\\n")
-        );
-        return;
+        )?;
+        return Ok(1);
     }
 
     let final_dest = if dest_is_dir {
         match source.file_name() {
             Some(name) => destination.join(name),
             None => {
-                eprintln!(
+                writeln!(
+                    err,
                     "cp: cannot determine file name for '{}'",
                     source.display().to_string().replace("\n", "\\n")
-                );
-                return;
+                )?;
+                return Ok(1);
             }
         }
     } else {
@@ -87,19 +118,23 @@ fn copy_file_logic(source: &Path, destination: &Path, dest_is_dir: bool) {
         && let (Ok(src_can), Ok(dst_can)) = (source.canonicalize(), final_dest.canonicalize())
         && src_can == dst_can
     {
-        eprintln!(
+        writeln!(
+            err,
             "cp: '{}' and '{}' are the same file",
             source.display().to_string().replace("\n", "\\n"),
             final_dest.display().to_string().replace("\n", "\\n")
-        );
-        return;
+        )?;
+        return Ok(1);
     }
 
     if let Err(e) = fs::copy(source, &final_dest) {
-        eprintln!(
+        writeln!(
+            err,
             "cp: error copying to '{}': {}",
             final_dest.display().to_string().replace("\n", "\\n"),
             e
-        );
+        )?;
+        return Ok(1);
     }
+    Ok(0)
 }
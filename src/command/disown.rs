@@ -0,0 +1,32 @@
+use crate::helper::jobs::JobTable;
+use crate::helper::streams::Streams;
+use std::io;
+
+// `disown [-a] [jobspec...]`: the jobs keep running but are no longer ours
+// to list, wait for or bring back
+pub fn disown(args: Vec<String>, table: &mut JobTable, streams: &mut Streams) -> io::Result<i32> {
+    if args.first().is_some_and(|arg| arg == "-a") {
+        let ids: Vec<usize> = table.jobs().iter().map(|job| job.id).collect();
+        for id in ids {
+            table.disown(id);
+        }
+        return Ok(0);
+    }
+    let specs = if args.is_empty() {
+        vec!["%+".to_string()]
+    } else {
+        args
+    };
+
+    let mut status = 0;
+    for spec in specs {
+        match table.find(&spec) {
+            Ok(id) => table.disown(id),
+            Err(e) => {
+                writeln!(streams.stderr, "disown: {}", e)?;
+                status = 1;
+            }
+        }
+    }
+    Ok(status)
+}
@@ -1,5 +1,9 @@
-pub fn echo(args: Vec<String>) {
+use crate::helper::streams::Streams;
+use std::io;
+
+pub fn echo(args: Vec<String>, streams: &mut Streams) -> io::Result<i32> {
     let buffer = args.join(" ");
 
-    println!("{}", buffer);
+    writeln!(streams.stdout, "{}", buffer)?;
+    Ok(0)
 }
@@ -0,0 +1,39 @@
+use crate::helper::streams::Streams;
+use crate::helper::variables::{Variables, is_valid_name};
+use std::io;
+
+pub fn export(args: Vec<String>, vars: &mut Variables, streams: &mut Streams) -> io::Result<i32> {
+    // `export` / `export -p` list what children will see
+    if args.is_empty() || args == ["-p"] {
+        for (name, value) in vars.exported_names() {
+            match value {
+                Some(value) => writeln!(
+                    streams.stdout,
+                    "export {}=\"{}\"",
+                    name,
+                    value.replace('\\', "\\\\").replace('"', "\\\"")
+                )?,
+                None => writeln!(streams.stdout, "export {}", name)?,
+            }
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for arg in args {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_valid_name(name) {
+            writeln!(streams.stderr, "export: `{}': not a valid identifier", arg)?;
+            status = 1;
+            continue;
+        }
+        if let Some(value) = value {
+            vars.set(name, value.to_string());
+        }
+        vars.export(name);
+    }
+    Ok(status)
+}
@@ -0,0 +1,25 @@
+use crate::helper::jobs::JobTable;
+use crate::helper::streams::Streams;
+use std::io;
+
+// `fg [jobspec]`: the job (the current one by default) gets the terminal
+// and we wait for it like for any command
+pub fn fg(args: Vec<String>, table: &mut JobTable, streams: &mut Streams) -> io::Result<i32> {
+    if !table.enabled() {
+        writeln!(streams.stderr, "fg: no job control")?;
+        return Ok(1);
+    }
+    let spec = args.first().map(String::as_str).unwrap_or("%+");
+    let id = match table.find(spec) {
+        Ok(id) => id,
+        Err(e) => {
+            writeln!(streams.stderr, "fg: {}", e)?;
+            return Ok(1);
+        }
+    };
+    if let Some(job) = table.get(id) {
+        writeln!(streams.stdout, "{}", job.text)?;
+    }
+    streams.stdout.flush()?;
+    Ok(table.foreground(id).unwrap_or(1))
+}
@@ -0,0 +1,55 @@
+use crate::helper::jobs::JobTable;
+use crate::helper::streams::Streams;
+use std::io;
+
+// `jobs [-l|-p] [jobspec...]`: every job (or the ones named) with its state.
+// Jobs shown as done leave the table, like after a prompt notice.
+pub fn jobs(args: Vec<String>, table: &mut JobTable, streams: &mut Streams) -> io::Result<i32> {
+    let (mut long, mut pids) = (false, false);
+    let mut specs = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-l" => long = true,
+            "-p" => pids = true,
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                writeln!(streams.stderr, "jobs: {}: invalid option", flag)?;
+                writeln!(streams.stderr, "jobs: usage: jobs [-lp] [jobspec ...]")?;
+                return Ok(2);
+            }
+            _ => specs.push(arg),
+        }
+    }
+
+    table.reap();
+    let mut status = 0;
+    let ids: Vec<usize> = if specs.is_empty() {
+        table.jobs().iter().map(|job| job.id).collect()
+    } else {
+        let mut ids = Vec::new();
+        for spec in &specs {
+            match table.find(spec) {
+                Ok(id) => ids.push(id),
+                Err(e) => {
+                    writeln!(streams.stderr, "jobs: {}", e)?;
+                    status = 1;
+                }
+            }
+        }
+        ids
+    };
+
+    for id in ids {
+        let Some(job) = table.get(id) else {
+            continue;
+        };
+        if pids {
+            writeln!(streams.stdout, "{}", job.pgid)?;
+        } else if long {
+            writeln!(streams.stdout, "{}", table.long_line(job))?;
+        } else {
+            writeln!(streams.stdout, "{}", table.line(job))?;
+        }
+    }
+    table.forget_done();
+    Ok(status)
+}
@@ -0,0 +1,28 @@
+use crate::helper::streams::Streams;
+use crate::helper::variables::{Variables, is_valid_name};
+use std::io;
+
+// `local NAME[=value]...`: only inside a function, and the names get their
+// old values back when it returns
+pub fn local(args: Vec<String>, vars: &mut Variables, streams: &mut Streams) -> io::Result<i32> {
+    let mut status = 0;
+    for arg in args {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_valid_name(name) {
+            writeln!(streams.stderr, "local: `{}': not a valid identifier", arg)?;
+            status = 1;
+            continue;
+        }
+        if !vars.make_local(name) {
+            writeln!(streams.stderr, "local: can only be used in a function")?;
+            return Ok(1);
+        }
+        if let Some(value) = value {
+            vars.set(name, value.to_string());
+        }
+    }
+    Ok(status)
+}
@@ -1,5 +1,7 @@
+use crate::helper::streams::Streams;
 use chrono::{DateTime, Duration, Local};
 use std::cmp::max;
+use std::io::{self, Write};
 use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
 use std::time::SystemTime;
 use std::{fs, path::Path};
@@ -22,7 +24,7 @@ struct LongEntry {
     blocks: u64,
 }
 
-pub fn ls(args: Vec<String>) {
+pub fn ls(args: Vec<String>, streams: &mut Streams) -> io::Result<i32> {
     let mut flag = Flag {
         a: false,
         l: false,
@@ -43,8 +45,8 @@ pub fn ls(args: Vec<String>) {
 
         if arg.starts_with("-") && !is_dir_marker {
             if !is_flag(&arg, &mut flag) {
-                println!("ls: unrecognized option '{arg}'");
-                return;
+                writeln!(streams.stderr, "ls: unrecognized option '{arg}'")?;
+                return Ok(2);
             }
             continue;
         }
@@ -66,12 +68,25 @@ pub fn ls(args: Vec<String>) {
         dirs.push(".".to_string());
     }
 
-    l(files, dirs, errors.clone(), flag);
+    l(files, dirs, errors.clone(), flag, streams)
 }
 
-fn l(files: Vec<String>, dirs: Vec<String>, errors: Vec<String>, flag: Flag) {
+fn l(
+    files: Vec<String>,
+    dirs: Vec<String>,
+    errors: Vec<String>,
+    flag: Flag,
+    streams: &mut Streams,
+) -> io::Result<i32> {
+    // Like GNU ls: 2 when a command-line argument could not be listed
+    let mut status = if errors.is_empty() { 0 } else { 2 };
+    let out = &mut *streams.stdout;
     for err in &errors {
-        println!("ls: cannot access '{}': No such file or directory", err);
+        writeln!(
+            streams.stderr,
+            "ls: cannot access '{}': No such file or directory",
+            err
+        )?;
     }
 
     if !files.is_empty() {
@@ -88,12 +103,12 @@ fn l(files: Vec<String>, dirs: Vec<String>, errors: Vec<String>, flag: Flag) {
                     if flag.f {
                         display_name = append_indicator(display_name, &m);
                     }
-                    println!("{}", display_name);
+                    writeln!(out, "{}", display_name)?;
                 }
             }
         }
         if flag.l && !file_entries.is_empty() {
-            print!("{}", align_and_format(file_entries, false));
+            write!(out, "{}", align_and_format(file_entries, false))?;
         }
     }
 
@@ -101,45 +116,54 @@ fn l(files: Vec<String>, dirs: Vec<String>, errors: Vec<String>, flag: Flag) {
 
     for (i, path_str) in dirs.iter().enumerate() {
         if i > 0 || !files.is_empty() {
-            println!();
+            writeln!(out)?;
         }
 
         if show_headers {
-            println!("{}:", path_str);
+            writeln!(out, "{}:", path_str)?;
         }
 
         match (flag.a, flag.l, flag.f) {
             (false, false, false) => {
-                if let Ok(r) = get_dir_content(path_str, false) {
+                if let Ok(r) = get_dir_content(path_str, false, streams.stderr) {
                     let r = r.join(" ");
                     if !r.is_empty() {
-                        println!("{r}");
+                        writeln!(out, "{r}")?;
                     }
+                } else {
+                    status = 2;
                 }
             }
             (true, false, false) => {
-                if let Ok(r) = get_dir_content(path_str, true) {
+                if let Ok(r) = get_dir_content(path_str, true, streams.stderr) {
                     let r = r.join(" ");
                     if !r.is_empty() {
-                        println!("{r}");
+                        writeln!(out, "{r}")?;
                     }
+                } else {
+                    status = 2;
                 }
             }
             (false, true, _) | (true, true, _) => {
-                print!("{}", run_ls_l(path_str, flag));
+                write!(out, "{}", run_ls_l(path_str, flag))?;
             }
             (false, false, true) => {
-                if let Ok(r) = get_dir_content(path_str, false) {
-                    println!("{}", add_symbols(r, path_str));
+                if let Ok(r) = get_dir_content(path_str, false, streams.stderr) {
+                    writeln!(out, "{}", add_symbols(r, path_str))?;
+                } else {
+                    status = 2;
                 }
             }
             (true, false, true) => {
-                if let Ok(r) = get_dir_content(path_str, true) {
-                    println!("{}", add_symbols(r, path_str));
+                if let Ok(r) = get_dir_content(path_str, true, streams.stderr) {
+                    writeln!(out, "{}", add_symbols(r, path_str))?;
+                } else {
+                    status = 2;
                 }
             }
         }
     }
+    Ok(status)
 }
 
 fn run_ls_l(path: &str, flag: Flag) -> String {
@@ -203,7 +227,11 @@ fn run_ls_l(path: &str, flag: Flag) -> String {
     align_and_format(entries, true)
 }
 
-fn get_dir_content(path: &str, show_hidden: bool) -> Result<Vec<String>, bool> {
+fn get_dir_content(
+    path: &str,
+    show_hidden: bool,
+    err: &mut dyn Write,
+) -> Result<Vec<String>, bool> {
     let mut filenames = Vec::new();
     if show_hidden {
         if fs::metadata(path).is_ok() {
@@ -228,7 +256,7 @@ fn get_dir_content(path: &str, show_hidden: bool) -> Result<Vec<String>, bool> {
             }
         }
         Err(e) => {
-            eprintln!("ls: cannot access '{}': {}", path, e);
+            let _ = writeln!(err, "ls: cannot access '{}': {}", path, e);
             return Err(false);
         }
     }
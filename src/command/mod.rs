@@ -1,8 +1,20 @@
+pub mod alias;
+pub mod bg;
 pub mod cat;
 pub mod cd;
 pub mod cp;
+pub mod disown;
 pub mod echo;
+pub mod export;
+pub mod fg;
+pub mod jobs;
+pub mod local;
 pub mod ls;
 pub mod mv;
 pub mod pwd;
 pub mod rm;
+pub mod set;
+pub mod source;
+pub mod unalias;
+pub mod unset;
+pub mod wait;
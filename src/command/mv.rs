@@ -1,12 +1,15 @@
+use crate::helper::streams::Streams;
 use std::fs;
+use std::io;
 use std::path::Path;
 
-pub fn mv(args: Vec<String>) {
+pub fn mv(args: Vec<String>, streams: &mut Streams) -> io::Result<i32> {
     if args.len() < 2 {
-        eprintln!("mv: missing operand");
-        return;
+        writeln!(streams.stderr, "mv: missing operand")?;
+        return Ok(1);
     }
 
+    let mut status = 0;
     if args.len() == 2 {
         let src = &args[0];
         let dst = &args[1];
@@ -23,14 +26,21 @@ pub fn mv(args: Vec<String>) {
 
         match fs::rename(src_path, &final_dst) {
             Ok(_) => (),
-            Err(e) => eprintln!("mv: cannot move '{}': {}", src, e),
+            Err(e) => {
+                writeln!(streams.stderr, "mv: cannot move '{}': {}", src, e)?;
+                status = 1;
+            }
         }
     } else if args.len() > 2 {
         let dst_dir = Path::new(args.last().unwrap());
 
         if !dst_dir.is_dir() {
-            eprintln!("mv: target '{}' is not a directory", dst_dir.display());
-            return;
+            writeln!(
+                streams.stderr,
+                "mv: target '{}' is not a directory",
+                dst_dir.display()
+            )?;
+            return Ok(1);
         }
 
         for src in &args[0..args.len() - 1] {
@@ -40,9 +50,13 @@ pub fn mv(args: Vec<String>) {
                 let dst = dst_dir.join(file_name);
                 match fs::rename(src_path, &dst) {
                     Ok(_) => (),
-                    Err(e) => eprintln!("mv: cannot move '{}': {}", src, e),
+                    Err(e) => {
+                        writeln!(streams.stderr, "mv: cannot move '{}': {}", src, e)?;
+                        status = 1;
+                    }
                 }
             }
         }
     }
+    Ok(status)
 }
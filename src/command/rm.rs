@@ -1,6 +1,8 @@
+use crate::helper::streams::Streams;
+use std::io;
 use std::path::Path;
 
-pub fn rm(args: Vec<String>) {
+pub fn rm(args: Vec<String>, streams: &mut Streams) -> io::Result<i32> {
     let mut recursive = false;
 
     for arg in &args {
@@ -8,11 +10,11 @@ pub fn rm(args: Vec<String>) {
             recursive = true;
             continue;
         }
-        if arg.starts_with("-") {
-            for c in arg[1..].chars() {
+        if let Some(opts) = arg.strip_prefix('-') {
+            for c in opts.chars() {
                 if c != 'r' && c != 'R' {
-                    println!("rm: invalid option -- '{}'", c);
-                    return;
+                    writeln!(streams.stderr, "rm: invalid option -- '{}'", c)?;
+                    return Ok(1);
                 }
             }
 
@@ -23,17 +25,22 @@ pub fn rm(args: Vec<String>) {
     let targets: Vec<&String> = args.iter().filter(|a| !a.starts_with('-')).collect();
 
     if targets.is_empty() {
-        println!("rm: missing operand");
-        return;
+        writeln!(streams.stderr, "rm: missing operand")?;
+        return Ok(1);
     }
 
+    let mut status = 0;
     for arg in targets {
         let path = Path::new(arg);
         if matches!(
             path.file_name().and_then(|n| n.to_str()),
             Some(".") | Some("..")
         ) {
-            eprintln!("rm: refusing to remove '.' or '..' directory: skipping '..'");
+            writeln!(
+                streams.stderr,
+                "rm: refusing to remove '.' or '..' directory: skipping '..'"
+            )?;
+            status = 1;
             continue;
         }
 
@@ -41,19 +48,31 @@ pub fn rm(args: Vec<String>) {
             Ok(meta) => {
                 if meta.is_symlink() {
                     if let Err(e) = std::fs::remove_file(path) {
-                        println!("rm: cannot remove symlink '{}': {}", arg, e);
+                        writeln!(streams.stderr, "rm: cannot remove symlink '{}': {}", arg, e)?;
+                        status = 1;
                     }
                 } else if meta.is_dir() {
                     if !recursive {
-                        println!("rm: cannot remove '{}': Is a directory", arg);
+                        writeln!(
+                            streams.stderr,
+                            "rm: cannot remove '{}': Is a directory",
+                            arg
+                        )?;
+                        status = 1;
                     } else if let Err(e) = std::fs::remove_dir_all(path) {
-                        println!("rm: cannot remove '{}': {}", arg, e);
+                        writeln!(streams.stderr, "rm: cannot remove '{}': {}", arg, e)?;
+                        status = 1;
                     }
                 } else if let Err(e) = std::fs::remove_file(path) {
-                    println!("rm: cannot remove '{}': {}", arg, e);
+                    writeln!(streams.stderr, "rm: cannot remove '{}': {}", arg, e)?;
+                    status = 1;
                 }
             }
-            Err(e) => println!("rm: cannot remove '{}': {}", arg, e),
+            Err(e) => {
+                writeln!(streams.stderr, "rm: cannot remove '{}': {}", arg, e)?;
+                status = 1;
+            }
         }
     }
+    Ok(status)
 }
@@ -0,0 +1,47 @@
+use crate::helper::state_manager::ShellOptions;
+use crate::helper::streams::Streams;
+use std::io;
+
+// Only the `-o name` / `+o name` form for now
+// Options: emacs, failglob, vi
+pub fn set(
+    args: Vec<String>,
+    options: &mut ShellOptions,
+    streams: &mut Streams,
+) -> io::Result<i32> {
+    if args.is_empty() || args == ["-o"] {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        writeln!(streams.stdout, "emacs\t{}", on_off(!options.vi))?;
+        writeln!(streams.stdout, "failglob\t{}", on_off(options.failglob))?;
+        writeln!(streams.stdout, "vi\t{}", on_off(options.vi))?;
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let enable = match flag.as_str() {
+            "-o" => true,
+            "+o" => false,
+            _ => {
+                writeln!(streams.stderr, "set: {}: invalid option", flag)?;
+                return Ok(2);
+            }
+        };
+        let Some(name) = args.next() else {
+            writeln!(streams.stderr, "set: {}: option name required", flag)?;
+            return Ok(2);
+        };
+        match name.as_str() {
+            "failglob" => options.failglob = enable,
+            // The two editing modes exclude each other, like in bash
+            "vi" => options.vi = enable,
+            "emacs" => options.vi = !enable,
+            _ => {
+                writeln!(streams.stderr, "set: {}: invalid option name", name)?;
+                status = 2;
+            }
+        }
+    }
+    Ok(status)
+}
@@ -0,0 +1,28 @@
+use crate::helper::script::run_script;
+use crate::helper::state_manager::ShellState;
+use crate::helper::streams::Streams;
+use std::{fs, io, path::Path};
+
+// `source file` / `. file`: runs the file in this shell, so variables,
+// options and `cd` stick. Relative paths start from the shell's cwd.
+// A `return` in the file ends just the file.
+pub fn source(args: Vec<String>, shell: &mut ShellState, streams: &mut Streams) -> io::Result<i32> {
+    let Some(file) = args.first() else {
+        writeln!(streams.stderr, "source: filename argument required")?;
+        return Ok(2);
+    };
+    let path = Path::new(&shell.pwd.get_current_dir()).join(file);
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            shell.call_depth += 1;
+            let status = run_script(&text, file, shell);
+            shell.call_depth -= 1;
+            shell.returning = false;
+            Ok(status)
+        }
+        Err(e) => {
+            writeln!(streams.stderr, "source: {}: {}", file, e)?;
+            Ok(1)
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::helper::streams::Streams;
+use std::collections::BTreeMap;
+use std::io;
+
+// `unalias name...` removes some, `unalias -a` removes them all
+pub fn unalias(
+    args: Vec<String>,
+    aliases: &mut BTreeMap<String, String>,
+    streams: &mut Streams,
+) -> io::Result<i32> {
+    if args.is_empty() {
+        writeln!(
+            streams.stderr,
+            "unalias: usage: unalias [-a] name [name ...]"
+        )?;
+        return Ok(2);
+    }
+    if args[0] == "-a" {
+        aliases.clear();
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for name in args {
+        if aliases.remove(&name).is_none() {
+            writeln!(streams.stderr, "unalias: {}: not found", name)?;
+            status = 1;
+        }
+    }
+    Ok(status)
+}
@@ -0,0 +1,16 @@
+use crate::helper::streams::Streams;
+use crate::helper::variables::{Variables, is_valid_name};
+use std::io;
+
+pub fn unset(args: Vec<String>, vars: &mut Variables, streams: &mut Streams) -> io::Result<i32> {
+    let mut status = 0;
+    for name in args {
+        if !is_valid_name(&name) {
+            writeln!(streams.stderr, "unset: `{}': not a valid identifier", name)?;
+            status = 1;
+            continue;
+        }
+        vars.unset(&name);
+    }
+    Ok(status)
+}
@@ -0,0 +1,59 @@
+use crate::helper::jobs::JobTable;
+use crate::helper::streams::Streams;
+use std::io;
+
+// `wait [jobspec|pid...]`: blocks until the jobs end and returns the status
+// of the last one. With no arguments it waits for all of them (status 0).
+// Ctrl-C stops waiting with 130.
+pub fn wait(args: Vec<String>, table: &mut JobTable, streams: &mut Streams) -> io::Result<i32> {
+    if args.is_empty() {
+        let ids: Vec<usize> = table.jobs().iter().map(|job| job.id).collect();
+        for id in ids {
+            if table.wait_for(id).is_none() && table.get(id).is_some() {
+                return Ok(130);
+            }
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for arg in args {
+        let id = if arg.starts_with('%') {
+            match table.find(&arg) {
+                Ok(id) => id,
+                Err(e) => {
+                    writeln!(streams.stderr, "wait: {}", e)?;
+                    status = 127;
+                    continue;
+                }
+            }
+        } else {
+            let Ok(pid) = arg.parse::<i32>() else {
+                writeln!(
+                    streams.stderr,
+                    "wait: `{}': not a pid or valid job spec",
+                    arg
+                )?;
+                status = 2;
+                continue;
+            };
+            match table.find_pid(pid) {
+                Some(id) => id,
+                None => {
+                    writeln!(
+                        streams.stderr,
+                        "wait: pid {} is not a child of this shell",
+                        pid
+                    )?;
+                    status = 127;
+                    continue;
+                }
+            }
+        };
+        match table.wait_for(id) {
+            Some(code) => status = code,
+            None => return Ok(130),
+        }
+    }
+    Ok(status)
+}
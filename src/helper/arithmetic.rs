@@ -0,0 +1,583 @@
+use crate::helper::variables::Variables;
+use std::fmt;
+
+// --- Arithmetic ---
+// The inside of `$(( ))` and `(( ))`, once `$X` and `$(cmd)` have been
+// expanded: 64-bit integers with C's operators and precedence. A bare name
+// is a variable; unset or empty counts as 0, and a value that is not a
+// number is evaluated as an expression of its own (`a=b+1`).
+// The whole expression is parsed before anything runs, so `0 && x++` and
+// `c ? a=1 : b=2` only touch the variables of the side actually taken.
+// Overflow wraps around like in bash.
+
+// Deep enough for any sane `a=b`, `b=c` chain, small enough for `a=a`
+const MAX_NESTING: usize = 32;
+
+#[derive(Debug, PartialEq)]
+pub struct ArithError {
+    expr: String,
+    message: String,
+    // Char offset in `expr` of the offending token
+    column: usize,
+}
+
+// Two lines, the second points at the bad spot:
+//   1 + * 2: syntax error: operand expected
+//       ^
+impl fmt::Display for ArithError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = self.expr.trim();
+        let lead = self.expr.chars().take_while(|c| c.is_whitespace()).count();
+        let column = self.column.saturating_sub(lead).min(text.chars().count());
+        write!(f, "{}: {}\n{}^", text, self.message, " ".repeat(column))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+// Longest first, so `<<=` is not read as `<<` then `=`
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=",
+    "*=", "/=", "%=", "&=", "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "=", "!", "~", "&", "^",
+    "|", "?", ":", ",", "(", ")",
+];
+
+const ASSIGNMENTS: &[&str] = &[
+    "=", "+=", "-=", "*=", "/=", "%=", "<<=", ">>=", "&=", "^=", "|=",
+];
+
+// Binary operators from the loosest to the tightest (`**` is handled apart,
+// it binds right to left)
+const LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+type Fail = (String, usize);
+
+fn fail<T>(message: &str, column: usize) -> Result<T, Fail> {
+    Err((message.to_string(), column))
+}
+
+// --- Tokens ---
+fn tokenize(expr: &str) -> Result<Vec<(Token, usize)>, Fail> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || "#@_".contains(chars[i]))
+            {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let value = parse_number(&text).map_err(|message| (message, start))?;
+            tokens.push((Token::Num(value), start));
+        } else if c == '_' || c.is_ascii_alphabetic() {
+            let start = i;
+            while i < chars.len() && (chars[i] == '_' || chars[i].is_ascii_alphanumeric()) {
+                i += 1;
+            }
+            tokens.push((Token::Name(chars[start..i].iter().collect()), start));
+        } else {
+            let op = OPERATORS.iter().find(|op| {
+                op.chars()
+                    .enumerate()
+                    .all(|(n, oc)| chars.get(i + n) == Some(&oc))
+            });
+            let Some(op) = op else {
+                return fail("syntax error: invalid arithmetic operator", i);
+            };
+            tokens.push((Token::Op(op), i));
+            i += op.len();
+        }
+    }
+    Ok(tokens)
+}
+
+// `42`, `0x2a`, `052` (octal) and `base#digits` with a base up to 64
+fn parse_number(text: &str) -> Result<i64, String> {
+    let (base, digits) = if let Some((base, digits)) = text.split_once('#') {
+        match base.parse::<u32>() {
+            Ok(base) if (2..=64).contains(&base) => (base, digits),
+            _ => return Err("invalid arithmetic base".to_string()),
+        }
+    } else if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        (16, hex)
+    } else if text.len() > 1 && text.starts_with('0') {
+        (8, &text[1..])
+    } else {
+        (10, text)
+    };
+    if digits.is_empty() {
+        return Err("invalid number".to_string());
+    }
+
+    let mut value: i64 = 0;
+    for c in digits.chars() {
+        // Past 36, lower and upper case letters are different digits
+        let digit = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 10,
+            'A'..='Z' if base <= 36 => c as u32 - 'A' as u32 + 10,
+            'A'..='Z' => c as u32 - 'A' as u32 + 36,
+            '@' => 62,
+            '_' => 63,
+            _ => return Err("invalid number".to_string()),
+        };
+        if digit >= base {
+            return Err("value too great for base".to_string());
+        }
+        value = value.wrapping_mul(base as i64).wrapping_add(digit as i64);
+    }
+    Ok(value)
+}
+
+// --- Syntax Tree ---
+#[derive(Debug)]
+enum Expr {
+    Num(i64),
+    Var(String),
+    // `-x`, `!x`, `~x` (a unary `+` is dropped)
+    Unary(&'static str, Box<Expr>),
+    // The column is where the operator was, for `division by 0`
+    Binary(&'static str, Box<Expr>, Box<Expr>, usize),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    // `x = v`, `x += v`, ...
+    Assign(&'static str, String, Box<Expr>, usize),
+    // `++x` / `x--`: the change and whether the new value is the result
+    Step(String, i64, bool),
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    // Column just past the expression
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.end, |(_, column)| *column)
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn parse(mut self) -> Result<Expr, Fail> {
+        let expr = self.parse_comma()?;
+        if self.pos < self.tokens.len() {
+            return fail("syntax error in expression", self.column());
+        }
+        Ok(expr)
+    }
+
+    fn parse_comma(&mut self) -> Result<Expr, Fail> {
+        let mut lhs = self.parse_assign()?;
+        while self.peek_op() == Some(",") {
+            let column = self.column();
+            self.pos += 1;
+            let rhs = self.parse_assign()?;
+            lhs = Expr::Binary(",", Box::new(lhs), Box::new(rhs), column);
+        }
+        Ok(lhs)
+    }
+
+    fn parse_assign(&mut self) -> Result<Expr, Fail> {
+        let lhs = self.parse_conditional()?;
+        match self.peek_op() {
+            Some(op) if ASSIGNMENTS.contains(&op) => {
+                let column = self.column();
+                let Expr::Var(name) = lhs else {
+                    return fail("attempted assignment to non-variable", column);
+                };
+                self.pos += 1;
+                let rhs = self.parse_assign()?;
+                Ok(Expr::Assign(op, name, Box::new(rhs), column))
+            }
+            _ => Ok(lhs),
+        }
+    }
+
+    fn parse_conditional(&mut self) -> Result<Expr, Fail> {
+        let condition = self.parse_binary(0)?;
+        if self.peek_op() != Some("?") {
+            return Ok(condition);
+        }
+        self.pos += 1;
+        let then = self.parse_comma()?;
+        if self.peek_op() != Some(":") {
+            return fail("`:' expected for conditional expression", self.column());
+        }
+        self.pos += 1;
+        let otherwise = self.parse_assign()?;
+        Ok(Expr::Conditional(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn parse_binary(&mut self, level: usize) -> Result<Expr, Fail> {
+        if level == LEVELS.len() {
+            return self.parse_power();
+        }
+        let mut lhs = self.parse_binary(level + 1)?;
+        loop {
+            let Some(mut op) = self.peek_op() else {
+                return Ok(lhs);
+            };
+            // Between two operands `1--1` is `1 - -1`
+            if LEVELS[level].contains(&"+") && (op == "++" || op == "--") {
+                let column = self.column();
+                op = &op[..1];
+                self.tokens[self.pos] = (Token::Op(op), column);
+                self.tokens
+                    .insert(self.pos + 1, (Token::Op(op), column + 1));
+            }
+            if !LEVELS[level].contains(&op) {
+                return Ok(lhs);
+            }
+            let column = self.column();
+            self.pos += 1;
+            let rhs = self.parse_binary(level + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs), column);
+        }
+    }
+
+    fn parse_power(&mut self) -> Result<Expr, Fail> {
+        let base = self.parse_unary()?;
+        if self.peek_op() != Some("**") {
+            return Ok(base);
+        }
+        let column = self.column();
+        self.pos += 1;
+        let exponent = self.parse_power()?;
+        Ok(Expr::Binary(
+            "**",
+            Box::new(base),
+            Box::new(exponent),
+            column,
+        ))
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, Fail> {
+        match self.peek_op() {
+            // `++x` needs a name right after it; `++5` is just `+(+5)`
+            Some(op @ ("++" | "--")) => {
+                if let Some((Token::Name(name), _)) = self.tokens.get(self.pos + 1) {
+                    let name = name.clone();
+                    self.pos += 2;
+                    let delta = if op == "++" { 1 } else { -1 };
+                    return Ok(Expr::Step(name, delta, true));
+                }
+                let column = self.column();
+                let sign = &op[..1];
+                self.tokens[self.pos] = (Token::Op(sign), column);
+                self.tokens
+                    .insert(self.pos + 1, (Token::Op(sign), column + 1));
+                self.parse_unary()
+            }
+            Some("+") => {
+                self.pos += 1;
+                self.parse_unary()
+            }
+            Some(op @ ("-" | "!" | "~")) => {
+                self.pos += 1;
+                Ok(Expr::Unary(op, Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_postfix(),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, Fail> {
+        let column = self.column();
+        let Some((token, _)) = self.tokens.get(self.pos).cloned() else {
+            return fail("syntax error: operand expected", column);
+        };
+        self.pos += 1;
+        match token {
+            Token::Num(value) => Ok(Expr::Num(value)),
+            Token::Name(name) => match self.peek_op() {
+                Some(op @ ("++" | "--")) => {
+                    self.pos += 1;
+                    let delta = if op == "++" { 1 } else { -1 };
+                    Ok(Expr::Step(name, delta, false))
+                }
+                _ => Ok(Expr::Var(name)),
+            },
+            Token::Op("(") => {
+                let inner = self.parse_comma()?;
+                if self.peek_op() != Some(")") {
+                    return fail("missing `)'", self.column());
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Token::Op(_) => fail("syntax error: operand expected", column),
+        }
+    }
+}
+
+// --- Evaluation ---
+struct Evaluator<'a> {
+    expr: &'a str,
+    vars: &'a mut Variables,
+    depth: usize,
+}
+
+impl Evaluator<'_> {
+    fn error(&self, message: &str, column: usize) -> ArithError {
+        ArithError {
+            expr: self.expr.to_string(),
+            message: message.to_string(),
+            column,
+        }
+    }
+
+    fn variable(&mut self, name: &str) -> Result<i64, ArithError> {
+        let value = self.vars.get(name).unwrap_or("").trim().to_string();
+        if value.is_empty() {
+            return Ok(0);
+        }
+        if let Ok(number) = parse_number(&value) {
+            return Ok(number);
+        }
+        if self.depth >= MAX_NESTING {
+            return Err(self.error("expression recursion level exceeded", 0));
+        }
+        evaluate_nested(&value, self.vars, self.depth + 1)
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<i64, ArithError> {
+        Ok(match expr {
+            Expr::Num(value) => *value,
+            Expr::Var(name) => self.variable(name)?,
+            Expr::Unary(op, operand) => {
+                let value = self.eval(operand)?;
+                match *op {
+                    "-" => value.wrapping_neg(),
+                    "!" => (value == 0) as i64,
+                    _ => !value,
+                }
+            }
+            // Only these three may skip evaluating their right side
+            Expr::Binary("&&", lhs, rhs, _) => {
+                (self.eval(lhs)? != 0 && self.eval(rhs)? != 0) as i64
+            }
+            Expr::Binary("||", lhs, rhs, _) => {
+                (self.eval(lhs)? != 0 || self.eval(rhs)? != 0) as i64
+            }
+            Expr::Conditional(condition, then, otherwise) => {
+                if self.eval(condition)? != 0 {
+                    self.eval(then)?
+                } else {
+                    self.eval(otherwise)?
+                }
+            }
+            Expr::Binary(op, lhs, rhs, column) => {
+                let lhs = self.eval(lhs)?;
+                let rhs = self.eval(rhs)?;
+                self.apply(op, lhs, rhs, *column)?
+            }
+            Expr::Assign(op, name, rhs, column) => {
+                let rhs = self.eval(rhs)?;
+                let value = match op.strip_suffix('=').filter(|op| !op.is_empty()) {
+                    Some(op) => {
+                        let current = self.variable(name)?;
+                        self.apply(op, current, rhs, *column)?
+                    }
+                    None => rhs,
+                };
+                self.vars.set(name, value.to_string());
+                value
+            }
+            Expr::Step(name, delta, prefix) => {
+                let old = self.variable(name)?;
+                let new = old.wrapping_add(*delta);
+                self.vars.set(name, new.to_string());
+                if *prefix { new } else { old }
+            }
+        })
+    }
+
+    fn apply(&self, op: &str, lhs: i64, rhs: i64, column: usize) -> Result<i64, ArithError> {
+        Ok(match op {
+            "," => rhs,
+            "|" => lhs | rhs,
+            "^" => lhs ^ rhs,
+            "&" => lhs & rhs,
+            "==" => (lhs == rhs) as i64,
+            "!=" => (lhs != rhs) as i64,
+            "<" => (lhs < rhs) as i64,
+            "<=" => (lhs <= rhs) as i64,
+            ">" => (lhs > rhs) as i64,
+            ">=" => (lhs >= rhs) as i64,
+            "<<" => lhs.wrapping_shl(rhs as u32),
+            ">>" => lhs.wrapping_shr(rhs as u32),
+            "+" => lhs.wrapping_add(rhs),
+            "-" => lhs.wrapping_sub(rhs),
+            "*" => lhs.wrapping_mul(rhs),
+            "/" | "%" if rhs == 0 => return Err(self.error("division by 0", column)),
+            "/" => lhs.wrapping_div(rhs),
+            "%" => lhs.wrapping_rem(rhs),
+            "**" if rhs < 0 => return Err(self.error("exponent less than 0", column)),
+            _ => {
+                // `**`, by squaring
+                let (mut base, mut exponent, mut result) = (lhs, rhs as u64, 1i64);
+                while exponent > 0 {
+                    if exponent & 1 == 1 {
+                        result = result.wrapping_mul(base);
+                    }
+                    base = base.wrapping_mul(base);
+                    exponent >>= 1;
+                }
+                result
+            }
+        })
+    }
+}
+
+// An empty expression is 0, like in bash
+pub fn evaluate(expr: &str, vars: &mut Variables) -> Result<i64, ArithError> {
+    evaluate_nested(expr, vars, 0)
+}
+
+fn evaluate_nested(expr: &str, vars: &mut Variables, depth: usize) -> Result<i64, ArithError> {
+    let syntax = |(message, column): Fail| ArithError {
+        expr: expr.to_string(),
+        message,
+        column,
+    };
+    let tokens = tokenize(expr).map_err(syntax)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let parser = Parser {
+        tokens,
+        pos: 0,
+        end: expr.chars().count(),
+    };
+    let tree = parser.parse().map_err(syntax)?;
+    Evaluator { expr, vars, depth }.eval(&tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calc(expr: &str) -> i64 {
+        evaluate(expr, &mut Variables::default()).unwrap()
+    }
+
+    fn error(expr: &str) -> String {
+        evaluate(expr, &mut Variables::default())
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn test_precedence_and_operators() {
+        assert_eq!(calc("1 + 2 * 3"), 7);
+        assert_eq!(calc("(1 + 2) * 3"), 9);
+        assert_eq!(calc("2 ** 3 ** 2"), 512);
+        assert_eq!(calc("-2 ** 2"), 4);
+        assert_eq!(calc("7 / 2 + 7 % 2"), 4);
+        assert_eq!(calc("-7 / 2"), -3);
+        assert_eq!(calc("1 << 4 | 1"), 17);
+        assert_eq!(calc("6 & 3 ^ 1"), 3);
+        assert_eq!(calc("!0 + !5 + ~0"), 0);
+        assert_eq!(calc("3 > 2 && 2 >= 2 && 1 != 2 || 0"), 1);
+        assert_eq!(calc("1 ? 2 : 3"), 2);
+        assert_eq!(calc("0 ? 2 : 0 ? 3 : 4"), 4);
+        assert_eq!(calc("1, 2, 3"), 3);
+        assert_eq!(calc("1--1"), 2);
+        assert_eq!(calc("++5"), 5);
+        assert_eq!(calc("  "), 0);
+    }
+
+    #[test]
+    fn test_number_bases() {
+        assert_eq!(calc("0x1F + 010 + 2#101"), 31 + 8 + 5);
+        assert_eq!(calc("36#z + 64#_"), 35 + 63);
+        assert_eq!(calc("9223372036854775807 + 1"), i64::MIN);
+        assert!(error("09").contains("value too great for base"));
+        assert!(error("1#1").contains("invalid arithmetic base"));
+    }
+
+    #[test]
+    fn test_variables_and_assignment() {
+        let mut vars = Variables::default();
+        vars.set("x", "5".to_string());
+        vars.set("expr", "x * 2".to_string());
+        assert_eq!(evaluate("x + 1", &mut vars), Ok(6));
+        assert_eq!(evaluate("unset_name + 1", &mut vars), Ok(1));
+        assert_eq!(evaluate("expr + 1", &mut vars), Ok(11));
+
+        assert_eq!(evaluate("y = x += 2", &mut vars), Ok(7));
+        assert_eq!(vars.get("x"), Some("7"));
+        assert_eq!(vars.get("y"), Some("7"));
+        assert_eq!(evaluate("x++ + x", &mut vars), Ok(15));
+        assert_eq!(evaluate("--x", &mut vars), Ok(7));
+        assert_eq!(evaluate("x <<= 1", &mut vars), Ok(14));
+
+        // The side not taken is never run
+        assert_eq!(
+            evaluate("0 && x++, 1 || x++, 1 ? 0 : x++", &mut vars),
+            Ok(0)
+        );
+        assert_eq!(vars.get("x"), Some("14"));
+
+        vars.set("loop", "loop".to_string());
+        assert!(evaluate("loop", &mut vars).is_err());
+    }
+
+    #[test]
+    fn test_errors_point_at_the_column() {
+        assert_eq!(
+            error("1 + * 2"),
+            "1 + * 2: syntax error: operand expected\n    ^"
+        );
+        assert_eq!(error(" 5 / 0 "), "5 / 0: division by 0\n  ^");
+        assert_eq!(error("1 +"), "1 +: syntax error: operand expected\n   ^");
+        assert_eq!(error("(1 + 2"), "(1 + 2: missing `)'\n      ^");
+        assert_eq!(error("1 2"), "1 2: syntax error in expression\n  ^");
+        assert_eq!(
+            error("3 = 4"),
+            "3 = 4: attempted assignment to non-variable\n  ^"
+        );
+        assert_eq!(
+            error("1 $ 2"),
+            "1 $ 2: syntax error: invalid arithmetic operator\n  ^"
+        );
+        assert!(error("1 ? 2").contains("`:' expected"));
+        assert!(error("2 ** -1").contains("exponent less than 0"));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::helper::parser::{Word, WordPart};
+
+// --- Brace Expansion ---
+// `a{b,c}d` -> `abd acd`, `{1..3}` -> `1 2 3`, `{a..e..2}` -> `a c e`.
+// Runs first, before `~`, `$X` or globbing, and only on braces typed
+// unquoted: `"{a,b}"` and `\{a,b}` stay as they are. Quoted text and
+// parameters inside an alternative just come along (`{"a b",$X}`).
+// A brace with no comma and no valid range (`{}`, `{x}`) is plain text,
+// and so is a word that would expand to too many words, whether from one
+// long range (`{1..99999999999}`) or several braces multiplied together.
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Char(char),
+    // Anything that is not unquoted text, kept whole
+    Part(WordPart),
+}
+
+fn to_pieces(word: &Word) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    for part in &word.0 {
+        match part {
+            WordPart::Literal(text) => pieces.extend(text.chars().map(Piece::Char)),
+            other => pieces.push(Piece::Part(other.clone())),
+        }
+    }
+    pieces
+}
+
+fn to_word(pieces: Vec<Piece>) -> Word {
+    let mut word = Word::default();
+    for piece in pieces {
+        match (piece, word.0.last_mut()) {
+            (Piece::Char(c), Some(WordPart::Literal(text))) => text.push(c),
+            (Piece::Char(c), _) => word.0.push(WordPart::Literal(c.to_string())),
+            (Piece::Part(part), _) => word.0.push(part),
+        }
+    }
+    word
+}
+
+pub fn expand_braces(word: &Word) -> Vec<Word> {
+    let has_brace = word
+        .0
+        .iter()
+        .any(|part| matches!(part, WordPart::Literal(text) if text.contains('{')));
+    if !has_brace {
+        return vec![word.clone()];
+    }
+    match expand(&to_pieces(word)) {
+        Some(words) => words.into_iter().map(to_word).collect(),
+        None => vec![word.clone()],
+    }
+}
+
+// The first brace that expands is split up; the alternatives and whatever
+// follows are expanded again, so nested and repeated braces work too.
+// None once the words would go over MAX_WORDS.
+fn expand(pieces: &[Piece]) -> Option<Vec<Vec<Piece>>> {
+    for open in 0..pieces.len() {
+        if pieces[open] != Piece::Char('{') {
+            continue;
+        }
+        let Some((close, commas)) = find_close(pieces, open) else {
+            continue;
+        };
+        let alternatives: Vec<Vec<Piece>> = if commas.is_empty() {
+            match sequence(&pieces[open + 1..close]) {
+                Some(items) => items
+                    .into_iter()
+                    .map(|item| item.chars().map(Piece::Char).collect())
+                    .collect(),
+                None => continue,
+            }
+        } else {
+            let mut bounds = vec![open];
+            bounds.extend(&commas);
+            bounds.push(close);
+            let mut alternatives = Vec::new();
+            for pair in bounds.windows(2) {
+                alternatives.extend(expand(&pieces[pair[0] + 1..pair[1]])?);
+                if alternatives.len() > MAX_WORDS {
+                    return None;
+                }
+            }
+            alternatives
+        };
+
+        let prefix = &pieces[..open];
+        let suffixes = expand(&pieces[close + 1..])?;
+        if alternatives.len().saturating_mul(suffixes.len()) > MAX_WORDS {
+            return None;
+        }
+        let mut words = Vec::new();
+        for alternative in &alternatives {
+            for suffix in &suffixes {
+                let mut word = prefix.to_vec();
+                word.extend(alternative.iter().cloned());
+                word.extend(suffix.iter().cloned());
+                words.push(word);
+            }
+        }
+        return Some(words);
+    }
+    Some(vec![pieces.to_vec()])
+}
+
+// The matching `}` and the commas that belong to this level
+fn find_close(pieces: &[Piece], open: usize) -> Option<(usize, Vec<usize>)> {
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    for (i, piece) in pieces.iter().enumerate().skip(open + 1) {
+        match piece {
+            Piece::Char('{') => depth += 1,
+            Piece::Char('}') if depth == 0 => return Some((i, commas)),
+            Piece::Char('}') => depth -= 1,
+            Piece::Char(',') if depth == 0 => commas.push(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+// More words than any command line could use
+const MAX_WORDS: usize = 100_000;
+
+// `x..y` or `x..y..step`, with numbers or single letters. A leading zero on
+// either end pads every number to the same width (`{01..10}`).
+fn sequence(inner: &[Piece]) -> Option<Vec<String>> {
+    let text = inner
+        .iter()
+        .map(|piece| match piece {
+            Piece::Char(c) => Some(*c),
+            Piece::Part(_) => None,
+        })
+        .collect::<Option<String>>()?;
+    let (start, end, step) = match text.split("..").collect::<Vec<_>>().as_slice() {
+        [start, end] => (*start, *end, 1),
+        [start, end, step] => (*start, *end, step.parse::<i64>().ok()?),
+        _ => return None,
+    };
+    let step = step.unsigned_abs().max(1) as usize;
+
+    if let (Ok(first), Ok(last)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        let count = first.abs_diff(last) / step as u64 + 1;
+        if count > MAX_WORDS as u64 {
+            return None;
+        }
+        let padded = |s: &str| {
+            let digits = s.trim_start_matches('-');
+            digits.len() > 1 && digits.starts_with('0')
+        };
+        let width = if padded(start) || padded(end) {
+            start.len().max(end.len())
+        } else {
+            0
+        };
+        let values: Vec<i64> = if first <= last {
+            (first..=last).step_by(step).collect()
+        } else {
+            (last..=first).rev().step_by(step).collect()
+        };
+        return Some(
+            values
+                .into_iter()
+                .map(|n| format!("{:0width$}", n, width = width))
+                .collect(),
+        );
+    }
+
+    let letter = |s: &str| match s.as_bytes() {
+        [c] if c.is_ascii_alphabetic() => Some(*c),
+        _ => None,
+    };
+    let (first, last) = (letter(start)?, letter(end)?);
+    let letters: Vec<u8> = if first <= last {
+        (first..=last).step_by(step).collect()
+    } else {
+        (last..=first).rev().step_by(step).collect()
+    };
+    Some(
+        letters
+            .into_iter()
+            .map(|c| (c as char).to_string())
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::parser::{Token, parse_tokens};
+
+    fn braces(input: &str) -> Vec<String> {
+        let Ok(tokens) = parse_tokens(input) else {
+            panic!("Could not tokenize {:?}", input);
+        };
+        let [Token::Word(word)] = tokens.as_slice() else {
+            panic!("Expected one word, got {:?}", tokens);
+        };
+        expand_braces(word)
+            .iter()
+            .map(|word| word.literal_text().expect("word needs expansion"))
+            .collect()
+    }
+
+    #[test]
+    fn test_lists_and_nesting() {
+        assert_eq!(braces("a{b,c}d"), vec!["abd", "acd"]);
+        assert_eq!(braces("{a,b}{1,2}"), vec!["a1", "a2", "b1", "b2"]);
+        assert_eq!(braces("x{a,{b,c}y}"), vec!["xa", "xby", "xcy"]);
+        assert_eq!(braces("{,pre}fix"), vec!["fix", "prefix"]);
+        assert_eq!(braces("{{a,b}}"), vec!["{a}", "{b}"]);
+    }
+
+    #[test]
+    fn test_sequences() {
+        assert_eq!(braces("{1..4}"), vec!["1", "2", "3", "4"]);
+        assert_eq!(braces("{3..1}"), vec!["3", "2", "1"]);
+        assert_eq!(braces("{01..10..3}"), vec!["01", "04", "07", "10"]);
+        assert_eq!(braces("{-2..2..2}"), vec!["-2", "0", "2"]);
+        assert_eq!(braces("{a..e..2}"), vec!["a", "c", "e"]);
+        assert_eq!(braces("f{1..2}.{txt,md}").len(), 4);
+    }
+
+    #[test]
+    fn test_not_expanded() {
+        assert_eq!(braces("{}"), vec!["{}"]);
+        assert_eq!(braces("{x}"), vec!["{x}"]);
+        assert_eq!(braces("{a,b"), vec!["{a,b"]);
+        assert_eq!(braces("{1..b}"), vec!["{1..b}"]);
+        assert_eq!(
+            braces("{1..9223372036854775807}"),
+            vec!["{1..9223372036854775807}"]
+        );
+        assert_eq!(braces("{-9223372036854775808..0..1000}").len(), 1);
+        assert_eq!(braces("{1..100000}").len(), 100_000);
+        // The cap is on the whole word, not each brace
+        assert_eq!(
+            braces("{1..100000}{1..100000}"),
+            vec!["{1..100000}{1..100000}"]
+        );
+        assert_eq!(braces("{{1..100000},{1..100000}}").len(), 1);
+        assert_eq!(braces("{1..1000}{1..100}").len(), 100_000);
+        assert_eq!(braces("'{a,b}'"), vec!["{a,b}"]);
+        assert_eq!(braces("\\{a,b}"), vec!["{a,b}"]);
+        // Quoted text inside an alternative is kept as one piece
+        assert_eq!(braces("{'a,b',c}"), vec!["a,b", "c"]);
+    }
+}
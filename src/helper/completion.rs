@@ -0,0 +1,394 @@
+use crate::helper::external::is_executable;
+use crate::helper::parser::BUILTINS;
+use crate::helper::state_manager::ShellState;
+use crate::helper::unicode::str_width;
+use std::{collections::BTreeSet, env, fs, path::Path};
+use unicode_segmentation::UnicodeSegmentation;
+
+// --- Tab Completion ---
+// `complete` only looks at the text left of the cursor. It finds the word
+// being typed, decides if it is a command name, a flag or a path, and
+// returns what could replace it (already escaped, ready to insert).
+
+#[derive(Debug, PartialEq)]
+pub struct Completion {
+    // Char index in the line where the word being completed starts
+    pub start: usize,
+    pub candidates: Vec<String>,
+}
+
+// Shown under the prompt after a Tab that could not narrow things down
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletionMenu {
+    pub start: usize,
+    pub candidates: Vec<String>,
+    // None until the next Tab starts cycling
+    pub selected: Option<usize>,
+}
+
+// Flags our builtins understand. `ls` letters can be glued together (`-alF`)
+const FLAGS: &[(&str, &[&str])] = &[
+    ("ls", &["-a", "-l", "-F"]),
+    ("rm", &["-r", "-R", "--recursive"]),
+    ("export", &["-p"]),
+    ("set", &["-o", "+o"]),
+];
+const COMBINABLE: &[&str] = &["ls"];
+
+const SPECIAL: &[char] = &[
+    ' ', '\t', '\'', '"', '\\', '$', '*', '?', '[', ']', '&', ';', '|', '<', '>', '(', ')', '`',
+];
+
+fn escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        if SPECIAL.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// Quote removal for the half-typed word, good enough for matching
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    let (mut in_single, mut in_double) = (false, false);
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            '\\' if !in_single => out.extend(chars.next()),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+pub fn complete(line: &str, cursor: usize, cwd: &str, path: Option<&str>) -> Completion {
+    let before: Vec<char> = line.chars().take(cursor).collect();
+
+    // Walk the line like the tokenizer does, remembering where the current
+    // word starts and which words came before it in this command
+    let mut start = 0;
+    let mut words: Vec<String> = Vec::new();
+    let (mut in_single, mut in_double, mut escaped) = (false, false, false);
+    for (i, &c) in before.iter().enumerate() {
+        if escaped {
+            escaped = false;
+        } else if in_single {
+            in_single = c != '\'';
+        } else if in_double {
+            match c {
+                '"' => in_double = false,
+                '\\' => escaped = true,
+                _ => {}
+            }
+        } else {
+            match c {
+                '\\' => escaped = true,
+                '\'' => in_single = true,
+                '"' => in_double = true,
+                ' ' | '\t' | '\n' | '<' | '>' | '|' | ';' | '&' => {
+                    if i > start {
+                        words.push(unescape(&before[start..i].iter().collect::<String>()));
+                    }
+                    if matches!(c, '|' | ';' | '&' | '\n') {
+                        words.clear();
+                    }
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+    }
+    let typed = unescape(&before[start..].iter().collect::<String>());
+
+    let candidates = if words.is_empty() && !typed.contains('/') {
+        complete_command(&typed, path)
+    } else if typed.starts_with(['-', '+']) && !words.is_empty() {
+        complete_flag(&words[0], &typed)
+    } else {
+        complete_path(&typed, cwd)
+    };
+    Completion { start, candidates }
+}
+
+fn complete_command(prefix: &str, path: Option<&str>) -> Vec<String> {
+    let mut names: BTreeSet<String> = BUILTINS
+        .iter()
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| name.to_string())
+        .collect();
+
+    for dir in env::split_paths(path.unwrap_or_default()) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with(prefix) && is_executable(&entry.path()) {
+                names.insert(name);
+            }
+        }
+    }
+    names.into_iter().map(|name| escape(&name)).collect()
+}
+
+fn complete_flag(command: &str, typed: &str) -> Vec<String> {
+    let Some((_, flags)) = FLAGS.iter().find(|(name, _)| *name == command) else {
+        return Vec::new();
+    };
+
+    // `ls -al` -> `-alF`: offer every letter not used yet
+    let letters: Vec<char> = flags
+        .iter()
+        .filter_map(|flag| flag.strip_prefix('-'))
+        .filter(|rest| rest.chars().count() == 1)
+        .filter_map(|rest| rest.chars().next())
+        .collect();
+    let glued = &typed[1..];
+    if COMBINABLE.contains(&command)
+        && typed.starts_with('-')
+        && !glued.is_empty()
+        && glued.chars().all(|c| letters.contains(&c))
+    {
+        return letters
+            .iter()
+            .filter(|c| !glued.contains(**c))
+            .map(|c| format!("{}{}", typed, c))
+            .collect();
+    }
+
+    flags
+        .iter()
+        .filter(|flag| flag.starts_with(typed))
+        .map(|flag| flag.to_string())
+        .collect()
+}
+
+fn complete_path(typed: &str, cwd: &str) -> Vec<String> {
+    let (dir, prefix) = match typed.rfind('/') {
+        Some(i) => (&typed[..=i], &typed[i + 1..]),
+        None => ("", typed),
+    };
+    let base = Path::new(cwd).join(if dir.is_empty() { "." } else { dir });
+    let Ok(entries) = fs::read_dir(base) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Hidden entries only when asked for with a leading dot
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", escape(dir), escape(&name), slash))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+fn common_prefix(candidates: &[String]) -> String {
+    let Some(first) = candidates.first() else {
+        return String::new();
+    };
+    let mut len = first.len();
+    for other in &candidates[1..] {
+        // Whole clusters only, so a prefix never ends inside an accented char
+        len = first
+            .grapheme_indices(true)
+            .zip(other.graphemes(true))
+            .take_while(|((_, a), b)| a == b)
+            .map(|((i, a), _)| i + a.len())
+            .last()
+            .unwrap_or(0)
+            .min(len);
+    }
+    first[..len].to_string()
+}
+
+// --- Tab key ---
+// First Tab inserts what all candidates share, the next one lists them,
+// and every Tab after that cycles through the list.
+pub fn on_tab(shell: &mut ShellState) {
+    if let Some(menu) = &mut shell.completion {
+        let next = menu.selected.map_or(0, |i| (i + 1) % menu.candidates.len());
+        menu.selected = Some(next);
+        let (start, text) = (menu.start, menu.candidates[next].clone());
+        shell.replace_view_range(start, shell.cursor_idx, &text);
+        return;
+    }
+
+    let path = shell.vars.get("PATH").map(str::to_string);
+    let Completion { start, candidates } = complete(
+        &shell.view_buffer,
+        shell.cursor_idx,
+        &shell.pwd.get_current_dir(),
+        path.as_deref(),
+    );
+
+    match candidates.as_slice() {
+        [] => {}
+        [only] => {
+            // A finished word gets its space, a directory stays open
+            let suffix = if only.ends_with('/') { "" } else { " " };
+            shell.replace_view_range(start, shell.cursor_idx, &format!("{}{}", only, suffix));
+        }
+        _ => {
+            let shared = common_prefix(&candidates);
+            let typed: String = shell
+                .view_buffer
+                .chars()
+                .skip(start)
+                .take(shell.cursor_idx - start)
+                .collect();
+            if shared.chars().count() > typed.chars().count() {
+                shell.replace_view_range(start, shell.cursor_idx, &shared);
+            } else {
+                shell.completion = Some(CompletionMenu {
+                    start,
+                    candidates,
+                    selected: None,
+                });
+            }
+        }
+    }
+}
+
+// What the menu shows: the last path component, like bash
+fn label(candidate: &str) -> String {
+    let trimmed = candidate.trim_end_matches('/');
+    let name = trimmed.rsplit('/').next().unwrap_or(trimmed);
+    let slash = if candidate.ends_with('/') { "/" } else { "" };
+    format!("{}{}", unescape(name), slash)
+}
+
+// Lines of the menu, filled column by column to fit `width`
+pub fn format_menu(menu: &CompletionMenu, width: usize) -> Vec<String> {
+    let labels: Vec<String> = menu.candidates.iter().map(|c| label(c)).collect();
+    let col_width = labels.iter().map(|l| str_width(l)).max().unwrap_or(0) + 2;
+    let cols = (width / col_width).max(1);
+    let rows = labels.len().div_ceil(cols);
+
+    (0..rows)
+        .map(|row| {
+            let mut line = String::new();
+            for col in 0..cols {
+                let i = col * rows + row;
+                let Some(text) = labels.get(i) else {
+                    break;
+                };
+                let padding = " ".repeat(col_width - str_width(text));
+                if menu.selected == Some(i) {
+                    line.push_str(&format!("\x1b[7m{}\x1b[0m{}", text, padding));
+                } else {
+                    line.push_str(&format!("{}{}", text, padding));
+                }
+            }
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let dir = env::temp_dir().join(format!("0shell-complete-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        for file in ["main.rs", "my file.txt", ".hidden"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        fs::write(dir.join("src/lib.rs"), "").unwrap();
+        dir
+    }
+
+    fn candidates(line: &str, cwd: &str) -> Vec<String> {
+        complete(line, line.chars().count(), cwd, Some("/usr/bin:/bin")).candidates
+    }
+
+    #[test]
+    fn test_complete_command_names() {
+        let found = candidates("ec", "/");
+        assert!(found.contains(&"echo".to_string()));
+        assert!(candidates("ls | expo", "/").contains(&"export".to_string()));
+        assert!(candidates("true && s", "/").contains(&"sh".to_string()));
+    }
+
+    #[test]
+    fn test_complete_paths_relative_to_cwd() {
+        let dir = scratch_dir("paths");
+        let cwd = dir.display().to_string();
+
+        assert_eq!(candidates("cat m", &cwd), vec!["main.rs", "my\\ file.txt"]);
+        assert_eq!(candidates("cat s", &cwd), vec!["src/"]);
+        assert_eq!(candidates("cat src/", &cwd), vec!["src/lib.rs"]);
+        assert_eq!(candidates("cat .h", &cwd), vec![".hidden"]);
+        assert_eq!(candidates("echo > my\\ f", &cwd), vec!["my\\ file.txt"]);
+        // Only the text left of the cursor matters
+        let line = "cat ma other";
+        assert_eq!(complete(line, 6, &cwd, None).start, 4);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_complete_builtin_flags() {
+        assert_eq!(candidates("ls -", "/"), vec!["-a", "-l", "-F"]);
+        assert_eq!(candidates("ls -al", "/"), vec!["-alF"]);
+        assert_eq!(candidates("rm --r", "/"), vec!["--recursive"]);
+        assert!(candidates("echo -", "/").is_empty());
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        let words = vec!["main.rs".to_string(), "mask".to_string()];
+        assert_eq!(common_prefix(&words), "ma");
+        assert_eq!(common_prefix(&["é1".to_string(), "é2".to_string()]), "é");
+    }
+
+    #[test]
+    fn test_menu_columns() {
+        let menu = CompletionMenu {
+            start: 0,
+            candidates: ["a", "bb", "c", "src/d/"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            selected: None,
+        };
+        // Four columns of width 4 don't fit in 10, two do
+        assert_eq!(format_menu(&menu, 10), vec!["a   c", "bb  d/"]);
+        assert_eq!(format_menu(&menu, 80), vec!["a   bb  c   d/"]);
+    }
+
+    #[test]
+    fn test_tab_cycles_through_menu() {
+        let dir = scratch_dir("cycle");
+        let mut shell = ShellState::new();
+        shell.pwd = crate::command::pwd::PwdState::new(dir.display().to_string(), "/".into());
+        shell.buffer = "cat m".to_string();
+        shell.view_buffer = shell.buffer.clone();
+        shell.cursor_idx = 5;
+
+        on_tab(&mut shell); // nothing shared beyond "m": list
+        assert!(shell.completion.is_some());
+        assert_eq!(shell.view_buffer, "cat m");
+
+        on_tab(&mut shell);
+        assert_eq!(shell.buffer, "cat main.rs");
+        on_tab(&mut shell);
+        assert_eq!(shell.view_buffer, "cat my\\ file.txt");
+        assert_eq!(shell.cursor_idx, shell.view_buffer.chars().count());
+        on_tab(&mut shell);
+        assert_eq!(shell.view_buffer, "cat main.rs");
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -1,49 +1,1038 @@
 use crate::command::{
-    cat::cat, cd::command_cd, cp::cp, echo::echo, ls::ls, mv::mv, pwd::PwdState, rm::rm,
+    alias::alias, bg::bg, cat::cat, cd::command_cd, cp::cp, disown::disown, echo::echo,
+    export::export, fg::fg, jobs::jobs, local::local, ls::ls, mv::mv, rm::rm, set::set,
+    source::source, unalias::unalias, unset::unset, wait::wait,
 };
-use crate::helper::parser::CommandEnum;
+use crate::helper::arithmetic::evaluate;
+use crate::helper::expand::{expand_pattern, expand_word, expand_words};
+use crate::helper::external::{exit_code, interrupted, spawn_external};
+use crate::helper::glob::matches;
+use crate::helper::jobs::{Job, JobTable, ProcessGroup, and_or_text, pipeline_text};
+use crate::helper::parser::{
+    AndOrList, Command, CommandEnum, CommandList, Compound, Connector, FileMode, Function,
+    Pipeline, Redirect, build_command,
+};
+use crate::helper::state_manager::ShellState;
+use crate::helper::streams::Streams;
+use std::cell::RefCell;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Cursor, ErrorKind, IsTerminal, PipeReader, PipeWriter, Read, Write};
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::path::Path;
+use std::process::{Child, Stdio};
+use std::rc::Rc;
+use std::thread;
+
+// What the previous pipeline stage left for the next one
+enum Piped {
+    Reader(PipeReader),
+    Bytes(Vec<u8>),
+}
+
+// Where one of a stage's standard fds (0, 1, 2) points
+enum Slot {
+    Shell(u8), // the shell's own fd, usually the terminal
+    Pipe,      // the pipe to the neighbouring stage
+    File(File),
+}
+
+impl Slot {
+    fn try_clone(&self) -> io::Result<Slot> {
+        Ok(match self {
+            Slot::Shell(fd) => Slot::Shell(*fd),
+            Slot::Pipe => Slot::Pipe,
+            Slot::File(file) => Slot::File(file.try_clone()?),
+        })
+    }
+}
+
+// Builtin output headed for the next stage. Shared so that `2>&1 |`
+// can send both streams into the same buffer.
+#[derive(Clone, Default)]
+struct PipeBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for PipeBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Runs a whole parsed line and returns the status of the last command run
+// (0 = success). `shell.last_status` is kept up to date after every pipeline.
+pub fn execute(list: &CommandList, shell: &mut ShellState) -> i32 {
+    for item in &list.items {
+        if item.background {
+            run_background(item, shell);
+            continue;
+        }
+        execute_and_or(item, shell);
+        if shell.unwinding() {
+            break;
+        }
+    }
+    shell.last_status
+}
+
+fn execute_and_or(item: &AndOrList, shell: &mut ShellState) {
+    execute_pipeline(&item.first, shell);
+    for (connector, pipeline) in &item.rest {
+        if shell.unwinding() {
+            return;
+        }
+        let run = match connector {
+            Connector::And => shell.last_status == 0,
+            Connector::Or => shell.last_status != 0,
+        };
+        if run {
+            execute_pipeline(pipeline, shell);
+        }
+    }
+}
+
+// --- Background Jobs ---
+// `a && b &` runs in a fork of the shell, so builtins, blocks and functions
+// work there too, and nothing it changes reaches us. With job control it
+// gets a process group of its own; without, it ignores Ctrl-C and reads
+// from /dev/null, like in any POSIX shell.
+fn run_background(item: &AndOrList, shell: &mut ShellState) {
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    let job_control = shell.jobs.enabled();
+    let pid = unsafe { libc::fork() };
+    if pid < 0 {
+        eprintln!("fork: {}", io::Error::last_os_error());
+        shell.last_status = 1;
+        return;
+    }
+
+    if pid == 0 {
+        unsafe {
+            if job_control {
+                libc::setpgid(0, 0);
+                for signal in [libc::SIGINT, libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU] {
+                    libc::signal(signal, libc::SIG_DFL);
+                }
+            } else {
+                libc::signal(libc::SIGINT, libc::SIG_IGN);
+                if let Ok(null) = File::open("/dev/null") {
+                    libc::dup2(null.as_raw_fd(), 0);
+                }
+            }
+        }
+        shell.jobs = JobTable::default();
+        execute_and_or(item, shell);
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+        // Straight out: no history saving or other cleanup meant for the shell
+        unsafe { libc::_exit(shell.exit_code.unwrap_or(shell.last_status)) }
+    }
+
+    if job_control {
+        unsafe {
+            libc::setpgid(pid, pid);
+        }
+    }
+    let id = shell.jobs.add(Job::new(pid, vec![pid], and_or_text(item)));
+    shell.jobs.last_pid = Some(pid);
+    // Scripts don't announce their jobs
+    if job_control {
+        eprintln!("[{}] {}", id, pid);
+    }
+    shell.last_status = 0;
+}
+
+// --- Compound Commands ---
+// Conditions are plain command lists: status 0 is true, anything else false
+fn run_compound(compound: &Compound, shell: &mut ShellState) -> i32 {
+    match compound {
+        Compound::Group(body) => execute(body, shell),
+        Compound::If {
+            branches,
+            otherwise,
+        } => {
+            for (condition, body) in branches {
+                let passed = execute(condition, shell) == 0;
+                if shell.unwinding() {
+                    return shell.last_status;
+                }
+                if passed {
+                    return execute(body, shell);
+                }
+            }
+            match otherwise {
+                Some(body) => execute(body, shell),
+                None => 0,
+            }
+        }
+        Compound::While {
+            condition,
+            body,
+            until,
+        } => {
+            let mut status = 0;
+            loop {
+                let passed = execute(condition, shell) == 0;
+                if shell.unwinding() || passed == *until {
+                    break;
+                }
+                status = execute(body, shell);
+                // Ctrl-C stops the whole loop, not just the command it hit
+                if shell.unwinding() || interrupted() {
+                    break;
+                }
+            }
+            status
+        }
+        Compound::For { name, words, body } => {
+            let values = match words {
+                Some(words) => match expand_words(words, shell) {
+                    Ok(values) => values,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return 1;
+                    }
+                },
+                None => shell.positional.clone(),
+            };
+            let mut status = 0;
+            for value in values {
+                shell.vars.set(name, value);
+                status = execute(body, shell);
+                if shell.unwinding() || interrupted() {
+                    break;
+                }
+            }
+            status
+        }
+        Compound::Case { word, arms } => {
+            let subject = match expand_word(word, shell) {
+                Ok(subject) => subject,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 1;
+                }
+            };
+            for arm in arms {
+                let mut hit = false;
+                for pattern in &arm.patterns {
+                    match expand_pattern(pattern, shell) {
+                        Ok(pattern) if matches(&pattern, &subject) => {
+                            hit = true;
+                            break;
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return 1;
+                        }
+                    }
+                }
+                if hit {
+                    // An empty arm still counts as success
+                    return if arm.body.items.is_empty() {
+                        0
+                    } else {
+                        execute(&arm.body, shell)
+                    };
+                }
+            }
+            0
+        }
+        // Status 0 for a non-zero value, so `if (( n > 3 ))` reads naturally
+        Compound::Arith(expr) => {
+            let value = expand_word(expr, shell)
+                .and_then(|text| evaluate(&text, &mut shell.vars).map_err(|e| e.to_string()));
+            match value {
+                Ok(value) => (value == 0) as i32,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    1
+                }
+            }
+        }
+    }
+}
+
+// A block runs in the shell itself, so its redirections (and its place in a
+// pipeline) are put on the shell's own fds 0-2 while it runs and undone after.
+// What it writes into the pipe is collected like builtin output.
+fn run_compound_stage(
+    compound: &Compound,
+    slots: [Slot; 3],
+    piped_in: Option<Piped>,
+    shell: &mut ShellState,
+) -> (i32, Vec<u8>) {
+    let untouched = slots
+        .iter()
+        .enumerate()
+        .all(|(fd, slot)| matches!(slot, Slot::Shell(n) if *n as usize == fd));
+    if untouched && piped_in.is_none() {
+        return (run_compound(compound, shell), Vec::new());
+    }
+    run_redirected(slots, piped_in, shell, |shell| {
+        run_compound(compound, shell)
+    })
+}
+
+// Runs `run` with fds 0-2 pointed at the slots, then puts them back.
+// Returns its status and whatever it wrote into a `Slot::Pipe`.
+fn run_redirected(
+    slots: [Slot; 3],
+    piped_in: Option<Piped>,
+    shell: &mut ShellState,
+    run: impl FnOnce(&mut ShellState) -> i32,
+) -> (i32, Vec<u8>) {
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    let mut saved = [-1; 3];
+    for (fd, copy) in saved.iter_mut().enumerate() {
+        *copy = unsafe { libc::fcntl(fd as RawFd, libc::F_DUPFD_CLOEXEC, 10) };
+    }
+    let result = redirect_fds(slots, piped_in, &saved).map(|redirected| {
+        let status = run(shell);
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+        (status, redirected)
+    });
+    // Put the shell's fds back before anything gets printed
+    for (fd, copy) in saved.into_iter().enumerate() {
+        if copy >= 0 {
+            unsafe {
+                libc::dup2(copy, fd as RawFd);
+                libc::close(copy);
+            }
+        }
+    }
+
+    match result {
+        Ok((status, Redirected { keep, collector })) => {
+            // Our write end must be gone for the collector to see EOF
+            drop(keep);
+            let output = collector
+                .map(|collector| collector.join().unwrap_or_default())
+                .unwrap_or_default();
+            (status, output)
+        }
+        Err(e) => {
+            eprintln!("redirect error: {}", e);
+            (1, Vec::new())
+        }
+    }
+}
+
+// What has to stay open while a redirected block runs, and the thread
+// collecting its piped output, if any
+struct Redirected {
+    keep: Vec<Box<dyn AsRawFd>>,
+    collector: Option<thread::JoinHandle<Vec<u8>>>,
+}
+
+// Points fds 0-2 at the slots
+fn redirect_fds(
+    slots: [Slot; 3],
+    mut piped_in: Option<Piped>,
+    saved: &[RawFd; 3],
+) -> io::Result<Redirected> {
+    if saved.contains(&-1) {
+        return Err(io::Error::last_os_error());
+    }
+    let mut keep: Vec<Box<dyn AsRawFd>> = Vec::new();
+    let mut collector = None;
+    let mut pipe_writer: Option<RawFd> = None;
+
+    for (fd, slot) in slots.into_iter().enumerate() {
+        let source = match slot {
+            Slot::Shell(n) => saved[n as usize],
+            Slot::File(file) => {
+                let raw = file.as_raw_fd();
+                keep.push(Box::new(file));
+                raw
+            }
+            Slot::Pipe if fd == 0 => {
+                let reader = match piped_in.take() {
+                    Some(Piped::Reader(reader)) => reader,
+                    Some(Piped::Bytes(bytes)) => {
+                        let (reader, mut writer) = io::pipe()?;
+                        thread::spawn(move || {
+                            let _ = writer.write_all(&bytes);
+                        });
+                        reader
+                    }
+                    None => io::pipe()?.0,
+                };
+                let raw = reader.as_raw_fd();
+                keep.push(Box::new(reader));
+                raw
+            }
+            // stdout and a `2>&1` stderr share one collector
+            Slot::Pipe => match pipe_writer {
+                Some(writer) => writer,
+                None => {
+                    let (mut reader, writer) = io::pipe()?;
+                    collector = Some(thread::spawn(move || {
+                        let mut output = Vec::new();
+                        let _ = reader.read_to_end(&mut output);
+                        output
+                    }));
+                    let raw = writer.as_raw_fd();
+                    keep.push(Box::new(writer));
+                    pipe_writer = Some(raw);
+                    raw
+                }
+            },
+        };
+        if unsafe { libc::dup2(source, fd as RawFd) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(Redirected { keep, collector })
+}
+
+// --- Command Substitution ---
+// `$(list)` runs in a copy of the shell, so a `cd`, an assignment or an
+// `exit` inside stays inside. Its status becomes `$?`.
+pub fn capture_output(list: &CommandList, shell: &mut ShellState) -> String {
+    let mut subshell = shell.subshell();
+    // `cd` moves the whole process, not just the copy: put it back after
+    let saved_dir = env::current_dir().ok();
+    let slots = [Slot::Shell(0), Slot::Pipe, Slot::Shell(2)];
+    let (status, output) = run_redirected(slots, None, &mut subshell, |subshell| {
+        execute(list, subshell);
+        subshell.exit_code.unwrap_or(subshell.last_status)
+    });
+    if let Some(dir) = saved_dir {
+        let _ = env::set_current_dir(dir);
+    }
+    shell.last_status = status;
+    shell.substitution_status = Some(status);
+
+    let mut output = String::from_utf8_lossy(&output).into_owned();
+    let trimmed = output.trim_end_matches('\n').len();
+    output.truncate(trimmed);
+    output
+}
+
+// --- Functions ---
+// Deep enough for real recursion, shallow enough not to blow our own stack
+const MAX_CALL_DEPTH: usize = 100;
+
+// Runs the body with its own $1..$n and `local` scope; `return` ends it early
+fn call_function(
+    function: &Function,
+    args: Vec<String>,
+    mut slots: [Slot; 3],
+    piped_in: Option<Piped>,
+    cwd: &str,
+    shell: &mut ShellState,
+) -> (i32, Vec<u8>) {
+    if shell.call_depth >= MAX_CALL_DEPTH {
+        eprintln!(
+            "{}: maximum function nesting level exceeded ({})",
+            function.name, MAX_CALL_DEPTH
+        );
+        return (1, Vec::new());
+    }
+    if let Err(e) = apply_redirects(&function.redirects, &mut slots, cwd, shell) {
+        eprintln!("{}", e);
+        return (1, Vec::new());
+    }
+
+    let saved = std::mem::replace(&mut shell.positional, args);
+    shell.vars.push_scope();
+    shell.call_depth += 1;
+    let result = run_compound_stage(&function.body, slots, piped_in, shell);
+    shell.call_depth -= 1;
+    shell.vars.pop_scope();
+    shell.positional = saved;
+    shell.returning = false;
+    result
+}
+
+// Returns the exit status of the last command in the pipeline
+fn execute_pipeline(pipeline: &Pipeline, shell: &mut ShellState) -> i32 {
+    let count = pipeline.commands.len();
+    let mut children: Vec<Child> = Vec::new();
+    // Builtins streaming into the next stage from a thread
+    let mut streaming: Vec<thread::JoinHandle<()>> = Vec::new();
+    let mut input: Option<Piped> = None;
+    let mut status = 0;
+    let mut last_is_child = false;
+    // The job's process group: the first program started leads it
+    let mut pgid = 0;
+
+    for (i, command) in pipeline.commands.iter().enumerate() {
+        let is_last = i + 1 == count;
+        let cwd = shell.pwd.get_current_dir();
+        let piped_in = input.take();
+        let mut slots = [
+            if piped_in.is_some() {
+                Slot::Pipe
+            } else {
+                Slot::Shell(0)
+            },
+            if is_last { Slot::Shell(1) } else { Slot::Pipe },
+            Slot::Shell(2),
+        ];
+        last_is_child = false;
+
+        let redirects = match command {
+            Command::Simple(command) => &command.redirects[..],
+            Command::Compound(_, redirects) => redirects,
+            Command::Function(_) => &[],
+        };
+        // A failed redirection skips the command, like bash does
+        if let Err(e) = apply_redirects(redirects, &mut slots, &cwd, shell) {
+            eprintln!("{}", e);
+            status = 1;
+            if !is_last {
+                input = Some(Piped::Bytes(Vec::new()));
+            }
+            continue;
+        }
+
+        let command = match command {
+            Command::Simple(command) => command,
+            Command::Compound(compound, _) => {
+                let (code, output) = run_compound_stage(compound, slots, piped_in, shell);
+                status = code;
+                if !is_last {
+                    input = Some(Piped::Bytes(output));
+                }
+                continue;
+            }
+            // Defining a function runs nothing yet
+            Command::Function(function) => {
+                let name = function.name.clone();
+                shell.functions.insert(name, function.clone());
+                status = 0;
+                if !is_last {
+                    input = Some(Piped::Bytes(Vec::new()));
+                }
+                continue;
+            }
+        };
+
+        // Leading `NAME=value` words are assignments, not the command name
+        let split = command
+            .words
+            .iter()
+            .position(|word| word.assignment().is_none())
+            .unwrap_or(command.words.len());
+        let args = match expand_words(&command.words[split..], shell) {
+            Ok(args) => args,
+            Err(e) => {
+                eprintln!("{}", e);
+                status = 1;
+                if !is_last {
+                    input = Some(Piped::Bytes(Vec::new()));
+                }
+                continue;
+            }
+        };
+        let assignments = command.words[..split]
+            .iter()
+            .filter_map(|word| word.assignment());
+
+        // Only assignments: they change the shell's own variables, left to right.
+        // `x=$(cmd)` takes the status of cmd, like in bash
+        if args.is_empty() && split > 0 {
+            shell.substitution_status = None;
+            let mut failed = false;
+            for (name, value) in assignments {
+                match expand_word(&value, shell) {
+                    Ok(value) => shell.vars.set(&name, value),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        failed = true;
+                        break;
+                    }
+                }
+            }
+            status = shell.substitution_status.take().unwrap_or(0);
+            if failed {
+                status = 1;
+            }
+            if !is_last {
+                input = Some(Piped::Bytes(Vec::new()));
+            }
+            continue;
+        }
+        let assignments: Result<Vec<(String, String)>, String> = assignments
+            .map(|(name, value)| Ok((name, expand_word(&value, shell)?)))
+            .collect();
+        let assignments = match assignments {
+            Ok(assignments) => assignments,
+            Err(e) => {
+                eprintln!("{}", e);
+                status = 1;
+                if !is_last {
+                    input = Some(Piped::Bytes(Vec::new()));
+                }
+                continue;
+            }
+        };
+
+        // A function comes before a builtin or a program of the same name
+        let function = args.first().and_then(|name| shell.functions.get(name));
+        if let Some(function) = function.cloned() {
+            let args = args[1..].to_vec();
+            let scoped = push_assignments(&assignments, shell);
+            let (code, output) = call_function(&function, args, slots, piped_in, &cwd, shell);
+            if scoped {
+                shell.vars.pop_scope();
+            }
+            status = code;
+            if !is_last {
+                input = Some(Piped::Bytes(output));
+            }
+            continue;
+        }
+
+        match build_command(args) {
+            // Anything that is not a builtin is looked up in PATH.
+            // `NAME=value cmd` only reaches the environment of cmd
+            CommandEnum::External(name, args) => {
+                let mut env = shell.vars.exported();
+                for (name, value) in assignments {
+                    env.retain(|(key, _)| *key != name);
+                    env.push((name, value));
+                }
+                let group = shell.jobs.group(pgid);
+                match spawn_stage(&name, &args, &cwd, &env, slots, piped_in, group) {
+                    Ok((child, reader)) => {
+                        if pgid == 0 {
+                            pgid = child.id() as i32;
+                        }
+                        if !is_last {
+                            input = Some(match reader {
+                                Some(reader) => Piped::Reader(reader),
+                                None => Piped::Bytes(Vec::new()),
+                            });
+                        }
+                        children.push(child);
+                        last_is_child = is_last;
+                    }
+                    Err(code) => {
+                        status = code;
+                        if !is_last {
+                            input = Some(Piped::Bytes(Vec::new()));
+                        }
+                    }
+                }
+            }
+
+            // Built-ins run in the shell itself; in the middle of a pipeline
+            // their output is collected and handed to the next stage
+            // One that needs nothing from the shell gets a thread and a real
+            // pipe, so `yes | cat | head -1` streams and ends like programs do
+            builtin if !is_last && is_standalone(&builtin) => {
+                match stream_builtin(builtin, slots, piped_in) {
+                    Ok((handle, reader)) => {
+                        streaming.push(handle);
+                        input = Some(Piped::Reader(reader));
+                    }
+                    Err(e) => {
+                        eprintln!("pipe error: {}", e);
+                        status = 1;
+                        input = Some(Piped::Bytes(Vec::new()));
+                    }
+                }
+            }
+
+            builtin => {
+                let [stdin_slot, stdout_slot, stderr_slot] = slots;
+                let mut reader = builtin_reader(stdin_slot, piped_in);
+                let stdout_is_tty = match stdout_slot {
+                    Slot::Shell(2) => io::stderr().is_terminal(),
+                    Slot::Shell(_) => io::stdout().is_terminal(),
+                    _ => false,
+                };
+
+                let buffer = PipeBuffer::default();
+                let mut out = slot_writer(stdout_slot, &buffer);
+                let mut err = slot_writer(stderr_slot, &buffer);
+                let mut streams = Streams {
+                    stdin: reader.as_mut().map(|r| r.as_mut() as &mut dyn Read),
+                    stdout: &mut out,
+                    stderr: &mut err,
+                    stdout_is_tty,
+                };
+                let scoped = push_assignments(&assignments, shell);
+                status = run_builtin(builtin, &mut streams, shell);
+                if scoped {
+                    shell.vars.pop_scope();
+                }
+
+                if !is_last {
+                    input = Some(Piped::Bytes(buffer.0.take()));
+                }
+            }
+        }
+    }
+
+    // Close our end of any unread pipe so upstream writers see EOF/EPIPE
+    drop(input);
+
+    // With job control the programs are waited for as a job, which Ctrl-Z
+    // can stop (148) and put in the table
+    if shell.jobs.group(pgid).is_some() && !children.is_empty() {
+        let pids = children.iter().map(|child| child.id() as i32).collect();
+        let job = Job::new(pgid, pids, pipeline_text(pipeline));
+        let code = shell.jobs.wait_foreground(job);
+        let stopped = code == 128 + libc::SIGTSTP;
+        if last_is_child || stopped {
+            status = code;
+        }
+        // A stopped job's builtins stay blocked on its pipes until it goes on
+        if !stopped {
+            for handle in streaming {
+                let _ = handle.join();
+            }
+        }
+        shell.last_status = status;
+        return status;
+    }
+
+    for mut child in children {
+        match child.wait() {
+            Ok(exit) => {
+                if last_is_child {
+                    status = exit_code(exit);
+                }
+            }
+            Err(e) => eprintln!("wait error: {}", e),
+        }
+    }
+    for handle in streaming {
+        let _ = handle.join();
+    }
+
+    shell.last_status = status;
+    status
+}
+
+// `NAME=value f` or `NAME=value cd`: set and exported only while it runs.
+// Returns whether a scope was pushed, for the caller to pop.
+fn push_assignments(assignments: &[(String, String)], shell: &mut ShellState) -> bool {
+    if assignments.is_empty() {
+        return false;
+    }
+    shell.vars.push_scope();
+    for (name, value) in assignments {
+        shell.vars.make_local(name);
+        shell.vars.set(name, value.clone());
+        shell.vars.export(name);
+    }
+    true
+}
+
+fn apply_redirects(
+    redirects: &[Redirect],
+    slots: &mut [Slot; 3],
+    cwd: &str,
+    shell: &mut ShellState,
+) -> Result<(), String> {
+    for redirect in redirects {
+        match redirect {
+            Redirect::File { fd, target, mode } => {
+                let path = expand_word(target, shell)?;
+                let mut options = OpenOptions::new();
+                match mode {
+                    FileMode::Read => options.read(true),
+                    FileMode::Write => options.write(true).create(true).truncate(true),
+                    FileMode::Append => options.append(true).create(true),
+                };
+                let file = options
+                    .open(Path::new(cwd).join(&path))
+                    .map_err(|e| format!("{}: {}", path, e))?;
+                slots[*fd as usize] = Slot::File(file);
+            }
+            Redirect::Dup { fd, target } => {
+                slots[*fd as usize] = slots[*target as usize]
+                    .try_clone()
+                    .map_err(|e| format!("{}>&{}: {}", fd, target, e))?;
+            }
+            // The body goes through a pipe so builtins and externals read it
+            // like any other file; a thread writes it so big bodies can't block
+            Redirect::Here { fd, body } => {
+                let bytes = expand_word(body, shell)?.into_bytes();
+                let (reader, mut writer) = io::pipe().map_err(|e| e.to_string())?;
+                thread::spawn(move || {
+                    let _ = writer.write_all(&bytes);
+                });
+                slots[*fd as usize] = Slot::File(File::from(OwnedFd::from(reader)));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn builtin_reader(slot: Slot, piped_in: Option<Piped>) -> Option<Box<dyn Read + Send>> {
+    match slot {
+        Slot::Pipe => piped_in.map(|piped| match piped {
+            Piped::Reader(reader) => Box::new(reader) as Box<dyn Read + Send>,
+            Piped::Bytes(bytes) => Box::new(Cursor::new(bytes)),
+        }),
+        Slot::File(file) => Some(Box::new(file)),
+        // Only a real terminal gets the interactive fallback
+        Slot::Shell(_) if io::stdin().is_terminal() => None,
+        Slot::Shell(_) => Some(Box::new(io::stdin())),
+    }
+}
+
+// Runs a standalone builtin on a thread writing into a new pipe and returns
+// the read end. When the reader goes away its writes fail with EPIPE and
+// it stops, like a program killed by SIGPIPE.
+fn stream_builtin(
+    builtin: CommandEnum,
+    slots: [Slot; 3],
+    piped_in: Option<Piped>,
+) -> io::Result<(thread::JoinHandle<()>, PipeReader)> {
+    let [stdin_slot, stdout_slot, stderr_slot] = slots;
+    let (reader, writer) = io::pipe()?;
+    let mut out = pipe_writer(stdout_slot, &writer)?;
+    let mut err = pipe_writer(stderr_slot, &writer)?;
+    // Only the copies above may keep the pipe open
+    drop(writer);
+    let mut input = builtin_reader(stdin_slot, piped_in);
+    let handle = thread::spawn(move || {
+        let mut streams = Streams {
+            stdin: input.as_mut().map(|r| r.as_mut() as &mut dyn Read),
+            stdout: &mut out,
+            stderr: &mut err,
+            stdout_is_tty: false,
+        };
+        run_standalone(builtin, &mut streams);
+    });
+    Ok((handle, reader))
+}
+
+fn pipe_writer(slot: Slot, pipe: &PipeWriter) -> io::Result<Box<dyn Write + Send>> {
+    Ok(match slot {
+        Slot::Shell(2) => Box::new(io::stderr()),
+        Slot::Shell(_) => Box::new(io::stdout()),
+        Slot::Pipe => Box::new(pipe.try_clone()?),
+        Slot::File(file) => Box::new(file),
+    })
+}
+
+fn slot_writer(slot: Slot, buffer: &PipeBuffer) -> Box<dyn Write> {
+    match slot {
+        Slot::Shell(2) => Box::new(io::stderr()),
+        Slot::Shell(_) => Box::new(io::stdout()),
+        Slot::Pipe => Box::new(buffer.clone()),
+        Slot::File(file) => Box::new(file),
+    }
+}
+
+fn output_stdio(slot: Slot, fd: u8, pipe: Option<&PipeWriter>) -> io::Result<Stdio> {
+    Ok(match slot {
+        Slot::Shell(n) if n == fd => Stdio::inherit(),
+        Slot::Shell(2) => io::stderr().into(),
+        Slot::Shell(_) => io::stdout().into(),
+        Slot::Pipe => match pipe {
+            Some(pipe) => pipe.try_clone()?.into(),
+            None => Stdio::null(),
+        },
+        Slot::File(file) => file.into(),
+    })
+}
+
+// Spawns one external stage and returns the read end of its output pipe, if any
+fn spawn_stage(
+    name: &str,
+    args: &[String],
+    cwd: &str,
+    env: &[(String, String)],
+    slots: [Slot; 3],
+    piped_in: Option<Piped>,
+    group: Option<ProcessGroup>,
+) -> Result<(Child, Option<PipeReader>), i32> {
+    let [stdin_slot, stdout_slot, stderr_slot] = slots;
+
+    let mut pending = None;
+    let stdin = match stdin_slot {
+        Slot::Pipe => match piped_in {
+            Some(Piped::Reader(reader)) => reader.into(),
+            Some(Piped::Bytes(bytes)) => {
+                pending = Some(bytes);
+                Stdio::piped()
+            }
+            None => Stdio::null(),
+        },
+        Slot::File(file) => file.into(),
+        Slot::Shell(_) => Stdio::inherit(),
+    };
+
+    let wants_pipe = matches!(stdout_slot, Slot::Pipe) || matches!(stderr_slot, Slot::Pipe);
+    let outputs = (|| -> io::Result<_> {
+        let pipe = if wants_pipe { Some(io::pipe()?) } else { None };
+        let writer = pipe.as_ref().map(|(_, writer)| writer);
+        let stdout = output_stdio(stdout_slot, 1, writer)?;
+        let stderr = output_stdio(stderr_slot, 2, writer)?;
+        Ok((pipe.map(|(reader, _)| reader), stdout, stderr))
+    })();
+    let (reader, stdout, stderr) = match outputs {
+        Ok(outputs) => outputs,
+        Err(e) => {
+            eprintln!("pipe error: {}", e);
+            return Err(1);
+        }
+    };
+
+    let mut child = spawn_external(name, args, cwd, env, (stdin, stdout, stderr), group)?;
+    // Feed builtin output from a thread so a full pipe can't deadlock us
+    if let (Some(bytes), Some(mut child_in)) = (pending, child.stdin.take()) {
+        thread::spawn(move || {
+            let _ = child_in.write_all(&bytes);
+        });
+    }
+    Ok((child, reader))
+}
 
-pub fn execute(cmd: CommandEnum, pwd_state: &mut PwdState) {
-    match cmd {
+fn run_builtin(cmd: CommandEnum, streams: &mut Streams, shell: &mut ShellState) -> i32 {
+    let result = match cmd {
         // Built-ins
-        CommandEnum::Cd(args, raw) => command_cd(args, raw, pwd_state),
+        CommandEnum::Cd(args, raw) => command_cd(args, raw, &mut shell.pwd, &shell.vars, streams),
 
-        CommandEnum::Pwd => {
-            println!("{}", pwd_state.get_current_dir());
+        CommandEnum::Pwd => writeln!(streams.stdout, "{}", shell.pwd.get_current_dir()).map(|_| 0),
+        CommandEnum::Clear => write!(streams.stdout, "\x1Bc").map(|_| 0),
+        // main stops the REPL once exit_code is set
+        CommandEnum::Exit(args) => {
+            let code = match args.first() {
+                None => shell.last_status,
+                Some(arg) => match arg.parse::<i32>() {
+                    Ok(n) => n & 0xff,
+                    Err(_) => {
+                        let _ =
+                            writeln!(streams.stderr, "exit: {}: numeric argument required", arg);
+                        2
+                    }
+                },
+            };
+            shell.exit_code = Some(code);
+            Ok(code)
         }
-        CommandEnum::Clear => {
-            print!("\x1Bc");
+        CommandEnum::Export(args) => export(args, &mut shell.vars, streams),
+        CommandEnum::Unset(args) => unset(args, &mut shell.vars, streams),
+        CommandEnum::Set(args) => set(args, &mut shell.options, streams),
+        CommandEnum::Source(args) => source(args, shell, streams),
+        CommandEnum::Local(args) => local(args, &mut shell.vars, streams),
+        CommandEnum::Alias(args) => alias(args, &mut shell.aliases, streams),
+        CommandEnum::Unalias(args) => unalias(args, &mut shell.aliases, streams),
+        CommandEnum::Jobs(args) => jobs(args, &mut shell.jobs, streams),
+        CommandEnum::Fg(args) => fg(args, &mut shell.jobs, streams),
+        CommandEnum::Bg(args) => bg(args, &mut shell.jobs, streams),
+        CommandEnum::Wait(args) => wait(args, &mut shell.jobs, streams),
+        CommandEnum::Disown(args) => disown(args, &mut shell.jobs, streams),
+        // Leaves the function (or sourced file) through `shell.returning`
+        CommandEnum::Return(args) => {
+            if shell.call_depth == 0 {
+                let _ = writeln!(
+                    streams.stderr,
+                    "return: can only `return' from a function or sourced script"
+                );
+                return 1;
+            }
+            let code = match args.first() {
+                None => shell.last_status,
+                Some(arg) => match arg.parse::<i32>() {
+                    Ok(n) => n & 0xff,
+                    Err(_) => {
+                        let _ =
+                            writeln!(streams.stderr, "return: {}: numeric argument required", arg);
+                        2
+                    }
+                },
+            };
+            shell.returning = true;
+            Ok(code)
         }
-        CommandEnum::Exit => { /* Handled in main, but good safety net */ }
         CommandEnum::Unknown(raw_cmd) => {
-            eprintln!("command not found: {}", raw_cmd);
+            if !raw_cmd.is_empty() {
+                let _ = writeln!(streams.stderr, "command not found: {}", raw_cmd);
+                return 127;
+            }
+            Ok(0)
         }
+        CommandEnum::External(..) => unreachable!("externals are spawned by execute"),
 
-        // EXTERNAL COMMANDS (The Fix)
-        // We map all these variants to a helper function
-        CommandEnum::Ls(args) => ls(args),
-        CommandEnum::Cat(args) => cat(args),
-        CommandEnum::Rm(args) => rm(args),
-        CommandEnum::Cp(args) => cp(args),
-        CommandEnum::Mv(args) => mv(args),
-        CommandEnum::Echo(args) => echo(args),
+        cmd @ (CommandEnum::Ls(_)
+        | CommandEnum::Cat(_)
+        | CommandEnum::Rm(_)
+        | CommandEnum::Cp(_)
+        | CommandEnum::Mv(_)
+        | CommandEnum::Echo(_)) => return run_standalone(cmd, streams),
         CommandEnum::Mkdir(dir, error_dir) => {
             if dir.is_empty() {
-                println!("mkdir: missing operand");
-                return;
+                let _ = writeln!(streams.stderr, "mkdir: missing operand");
+                return 1;
             }
             let mut count = 0;
             for d in dir {
                 count += 1;
                 if let Err(e) = std::fs::create_dir(&d) {
-                    eprintln!(
+                    let _ = writeln!(
+                        streams.stderr,
                         "mkdir: cannot create directory '{}': {}",
                         error_dir[count - 1],
                         e
                     );
-                    return;
+                    return 1;
                 }
             }
+            Ok(0)
+        }
+    };
+
+    finish_builtin(result, streams)
+}
+
+// Builtins that only need their arguments and streams
+fn is_standalone(cmd: &CommandEnum) -> bool {
+    matches!(
+        cmd,
+        CommandEnum::Ls(_)
+            | CommandEnum::Cat(_)
+            | CommandEnum::Rm(_)
+            | CommandEnum::Cp(_)
+            | CommandEnum::Mv(_)
+            | CommandEnum::Echo(_)
+    )
+}
+
+fn run_standalone(cmd: CommandEnum, streams: &mut Streams) -> i32 {
+    let result = match cmd {
+        CommandEnum::Ls(args) => ls(args, streams),
+        CommandEnum::Cat(args) => cat(args, streams),
+        CommandEnum::Rm(args) => rm(args, streams),
+        CommandEnum::Cp(args) => cp(args, streams),
+        CommandEnum::Mv(args) => mv(args, streams),
+        CommandEnum::Echo(args) => echo(args, streams),
+        _ => unreachable!("only standalone builtins get here"),
+    };
+    finish_builtin(result, streams)
+}
+
+fn finish_builtin(result: io::Result<i32>, streams: &mut Streams) -> i32 {
+    let result = result.and_then(|status| {
+        streams.stdout.flush()?;
+        streams.stderr.flush()?;
+        Ok(status)
+    });
+    match result {
+        Ok(status) => status,
+        // The reader went away (`ls | head -1`): not worth a message
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => 1,
+        Err(e) => {
+            eprintln!("write error: {}", e);
+            1
         }
     }
 }
@@ -52,10 +1041,35 @@ pub fn execute(cmd: CommandEnum, pwd_state: &mut PwdState) {
 mod tests {
     use super::*;
     use crate::command::pwd::PwdState;
+    use crate::helper::parser::{ParseResult, parse_input_with_aliases};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    // `cd` moves the whole test process: tests that run it, or check where
+    // the process is, take turns
+    static PROCESS_CWD: Mutex<()> = Mutex::new(());
+
+    // Helper to create a shell sitting in /tmp
+    fn mock_shell() -> ShellState {
+        let mut shell = ShellState::new();
+        shell.pwd = PwdState::new("/tmp".to_string(), "/".to_string());
+        shell
+    }
+
+    fn run(input: &str, shell: &mut ShellState) -> i32 {
+        match parse_input_with_aliases(input, &shell.aliases) {
+            ParseResult::Ok(list) => execute(&list, shell),
+            other => panic!("Expected a command list, got {:?}", other),
+        }
+    }
 
-    // Helper to create a dummy PwdState
-    fn mock_pwd() -> PwdState {
-        PwdState::new("/tmp".to_string(), "/".to_string())
+    // Each test gets its own scratch dir so they can run in parallel
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("0shell-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
     }
 
     #[test]
@@ -63,25 +1077,590 @@ mod tests {
         // This test verifies that the built-in Pwd logic runs.
         // Capturing stdout in Rust tests requires a specific setup,
         // so for now we ensure it simply doesn't panic.
-        let mut pwd = mock_pwd();
-        execute(CommandEnum::Pwd, &mut pwd);
+        let mut shell = mock_shell();
+        assert_eq!(run("pwd", &mut shell), 0);
     }
 
     #[test]
     fn test_execute_unknown_runs_without_crash() {
-        let mut pwd = mock_pwd();
-        let cmd = CommandEnum::Unknown("blarg".to_string());
-        execute(cmd, &mut pwd); // Should print error to stderr, but not crash
+        let mut shell = mock_shell();
+        // Should print error to stderr, but not crash
+        assert_eq!(run("blarg-0shell", &mut shell), 127);
     }
 
     // Note: Testing 'Ls', 'Echo', etc. requires the actual 'ls' binary to exist
     // on your computer. This is an "Integration Test".
     #[test]
     fn test_execute_echo_integration() {
-        let mut pwd = mock_pwd();
-        // This tries to actually spawn "echo hello"
-        let cmd = CommandEnum::Echo(vec!["hello".to_string()]);
-        execute(cmd, &mut pwd);
-        // Pass if no panic occurs
+        let mut shell = mock_shell();
+        assert_eq!(run("echo hello", &mut shell), 0);
+    }
+
+    #[test]
+    fn test_execute_external_returns_exit_status() {
+        let mut shell = mock_shell();
+        assert_eq!(run("true", &mut shell), 0);
+        assert_eq!(run("false", &mut shell), 1);
+        assert_eq!(shell.last_status, 1);
+    }
+
+    // `grep -q` turns "did the text arrive through the pipe?" into an exit status
+    #[test]
+    fn test_pipeline_builtin_into_external() {
+        let mut shell = mock_shell();
+        assert_eq!(run("echo hello | grep -q hello", &mut shell), 0);
+        assert_eq!(run("echo hello | grep -q bye", &mut shell), 1);
+    }
+
+    #[test]
+    fn test_pipeline_external_through_cat_builtin() {
+        let mut shell = mock_shell();
+        let status = run("sh -c 'echo one; echo two' | cat | grep -q two", &mut shell);
+        assert_eq!(status, 0);
+    }
+
+    // `cat` in the middle streams: the endless `yes` is stopped by `head`
+    #[test]
+    fn test_pipeline_builtin_in_the_middle_streams() {
+        let dir = scratch_dir("pipeline-stream");
+        let mut shell = mock_shell();
+        shell.pwd = PwdState::new(dir.display().to_string(), "/".to_string());
+
+        assert_eq!(run("yes | cat | head -1 > out", &mut shell), 0);
+        assert_eq!(fs::read_to_string(dir.join("out")).unwrap(), "y\n");
+        run(
+            "seq 3 | cat | echo x 2>&1 | cat | wc -l > count",
+            &mut shell,
+        );
+        assert_eq!(fs::read_to_string(dir.join("count")).unwrap().trim(), "1");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pipeline_status_is_last_stage() {
+        let mut shell = mock_shell();
+        assert_eq!(run("false | true", &mut shell), 0);
+    }
+
+    // --- Redirections ---
+    #[test]
+    fn test_builtin_stdout_to_file_and_append() {
+        let dir = scratch_dir("redirect-out");
+        let out = dir.join("out.txt").display().to_string();
+        let mut shell = mock_shell();
+
+        assert_eq!(run(&format!("echo one > {}", out), &mut shell), 0);
+        assert_eq!(run(&format!("echo two >> {}", out), &mut shell), 0);
+
+        assert_eq!(fs::read_to_string(&out).unwrap(), "one\ntwo\n");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_builtin_stderr_to_file() {
+        let dir = scratch_dir("redirect-err");
+        let err = dir.join("err.txt").display().to_string();
+        let mut shell = mock_shell();
+
+        run(
+            &format!("ls /definitely/missing/0shell 2> {}", err),
+            &mut shell,
+        );
+
+        assert!(
+            fs::read_to_string(&err)
+                .unwrap()
+                .contains("ls: cannot access")
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_external_stderr_follows_stdout_with_dup() {
+        let dir = scratch_dir("redirect-dup");
+        let out = dir.join("all.txt").display().to_string();
+        let mut shell = mock_shell();
+
+        let line = format!("sh -c 'echo out; echo err >&2' > {} 2>&1", out);
+        assert_eq!(run(&line, &mut shell), 0);
+
+        assert_eq!(fs::read_to_string(&out).unwrap(), "out\nerr\n");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_stdin_from_file_into_pipeline() {
+        let dir = scratch_dir("redirect-in");
+        let input = dir.join("in.txt");
+        fs::write(&input, "needle\n").unwrap();
+        let mut shell = mock_shell();
+
+        let line = format!("cat < {} | grep -q needle", input.display());
+        assert_eq!(run(&line, &mut shell), 0);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_missing_input_file_fails_the_command() {
+        let mut shell = mock_shell();
+        assert_eq!(run("true < /definitely/missing/0shell", &mut shell), 1);
+    }
+
+    // --- Lists and exit statuses ---
+    #[test]
+    fn test_and_or_short_circuit() {
+        let dir = scratch_dir("and-or");
+        let marker = dir.join("marker").display().to_string();
+        let mut shell = mock_shell();
+
+        run(&format!("false && echo no > {}", marker), &mut shell);
+        assert!(!Path::new(&marker).exists());
+
+        run(&format!("false || echo yes > {}", marker), &mut shell);
+        assert!(Path::new(&marker).exists());
+
+        assert_eq!(run("true && false || true", &mut shell), 0);
+        assert_eq!(run("true || false && false", &mut shell), 1);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_semicolon_runs_everything_and_keeps_last_status() {
+        let mut shell = mock_shell();
+        assert_eq!(run("false; true", &mut shell), 0);
+        assert_eq!(run("true; false", &mut shell), 1);
+    }
+
+    #[test]
+    fn test_status_param_sees_previous_command() {
+        let mut shell = mock_shell();
+        assert_eq!(run("false; test $? = 1", &mut shell), 0);
+        assert_eq!(run("sh -c 'exit 4'; test \"$?\" = 4", &mut shell), 0);
+    }
+
+    #[test]
+    fn test_builtin_failures_report_status() {
+        let mut shell = mock_shell();
+        assert_eq!(
+            run("cd /definitely/missing/0shell 2> /dev/null", &mut shell),
+            1
+        );
+        assert_eq!(
+            run("cat /definitely/missing/0shell 2> /dev/null", &mut shell),
+            1
+        );
+        assert_eq!(
+            run("ls /definitely/missing/0shell 2> /dev/null", &mut shell),
+            2
+        );
+    }
+
+    #[test]
+    fn test_exit_stops_the_list() {
+        let mut shell = mock_shell();
+        assert_eq!(run("exit 3; exit 4", &mut shell), 3);
+        assert_eq!(shell.exit_code, Some(3));
+
+        let mut shell = mock_shell();
+        run("false; exit", &mut shell);
+        assert_eq!(shell.exit_code, Some(1));
+    }
+
+    // --- Variables ---
+    #[test]
+    fn test_assignment_and_expansion() {
+        let mut shell = mock_shell();
+        assert_eq!(
+            run(
+                "GREETING='hi there'; test \"$GREETING\" = 'hi there'",
+                &mut shell
+            ),
+            0
+        );
+        assert_eq!(shell.vars.get("GREETING"), Some("hi there"));
+        assert_eq!(run("A=1 B=$A; test $B = 1", &mut shell), 0);
+    }
+
+    #[test]
+    fn test_only_exported_variables_reach_children() {
+        let mut shell = mock_shell();
+        run("LOCAL_0SHELL=x", &mut shell);
+        assert_eq!(run("sh -c 'test -z \"$LOCAL_0SHELL\"'", &mut shell), 0);
+
+        run("export LOCAL_0SHELL", &mut shell);
+        assert_eq!(run("sh -c 'test \"$LOCAL_0SHELL\" = x'", &mut shell), 0);
+
+        run("unset LOCAL_0SHELL", &mut shell);
+        assert_eq!(run("sh -c 'test -z \"$LOCAL_0SHELL\"'", &mut shell), 0);
+    }
+
+    #[test]
+    fn test_prefix_assignment_is_temporary() {
+        let mut shell = mock_shell();
+        assert_eq!(
+            run(
+                "ONCE_0SHELL=1 sh -c 'test \"$ONCE_0SHELL\" = 1'",
+                &mut shell
+            ),
+            0
+        );
+        assert_eq!(shell.vars.get("ONCE_0SHELL"), None);
+
+        // A function sees it too, and so do the programs it runs
+        run(
+            "X=old; f() { Y=$X; sh -c 'test \"$X\" = new'; }",
+            &mut shell,
+        );
+        assert_eq!(run("X=new f", &mut shell), 0);
+        assert_eq!(shell.vars.get("Y"), Some("new"));
+        assert_eq!(shell.vars.get("X"), Some("old"));
+        assert_eq!(run("sh -c 'test -z \"$X\"'", &mut shell), 0);
+    }
+
+    #[test]
+    fn test_export_rejects_bad_names() {
+        let mut shell = mock_shell();
+        assert_eq!(run("export 1x=2 2> /dev/null", &mut shell), 1);
+        assert_eq!(run("unset a-b 2> /dev/null", &mut shell), 1);
+    }
+
+    #[test]
+    fn test_cd_uses_home_from_the_store() {
+        let _cwd = PROCESS_CWD.lock().unwrap_or_else(|e| e.into_inner());
+        let before = env::current_dir().unwrap();
+        let dir = scratch_dir("cd-home");
+        let mut shell = mock_shell();
+        run(&format!("HOME={}; cd", dir.display()), &mut shell);
+        assert_eq!(
+            fs::canonicalize(shell.pwd.get_current_dir()).unwrap(),
+            fs::canonicalize(&dir).unwrap()
+        );
+        env::set_current_dir(before).unwrap();
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    // --- Globbing ---
+    #[test]
+    fn test_glob_expands_against_shell_cwd() {
+        let dir = scratch_dir("glob");
+        fs::write(dir.join("one.o"), "").unwrap();
+        fs::write(dir.join("two.o"), "").unwrap();
+        let mut shell = mock_shell();
+        shell.pwd = PwdState::new(dir.display().to_string(), "/".to_string());
+
+        // An external, since it runs in the shell's cwd rather than the test's
+        assert_eq!(run("/bin/rm *.o", &mut shell), 0);
+        assert!(!dir.join("one.o").exists() && !dir.join("two.o").exists());
+
+        // bash style: the pattern itself is passed on
+        assert_eq!(run("test *.o = '*.o'", &mut shell), 0);
+        run("set -o failglob", &mut shell);
+        assert_eq!(run("echo *.o 2> /dev/null", &mut shell), 1);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_source_runs_in_current_shell() {
+        let dir = scratch_dir("source");
+        fs::write(dir.join("env.sh"), "GREETING=hi\ncd /\nfalse\n").unwrap();
+        let mut shell = mock_shell();
+        shell.pwd = PwdState::new(dir.to_string_lossy().into_owned(), "/".to_string());
+
+        assert_eq!(run(". env.sh", &mut shell), 1);
+        assert_eq!(shell.vars.get("GREETING"), Some("hi"));
+        assert_eq!(shell.pwd.get_current_dir(), "/");
+
+        assert_eq!(run("source missing.sh", &mut shell), 1);
+        assert_eq!(run("source", &mut shell), 2);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    // --- Compound commands ---
+    #[test]
+    fn test_if_picks_branch_by_status() {
+        let mut shell = mock_shell();
+        run(
+            "if false; then R=1; elif true; then R=2; else R=3; fi",
+            &mut shell,
+        );
+        assert_eq!(shell.vars.get("R"), Some("2"));
+        run("if false; then R=1; else R=3; fi", &mut shell);
+        assert_eq!(shell.vars.get("R"), Some("3"));
+        // No branch taken: success
+        assert_eq!(run("if false; then true; fi", &mut shell), 0);
+        assert_eq!(run("if true; then false; fi", &mut shell), 1);
+    }
+
+    #[test]
+    fn test_while_and_until_loops() {
+        let mut shell = mock_shell();
+        run("N=; while test \"$N\" != xxx; do N=x$N; done", &mut shell);
+        assert_eq!(shell.vars.get("N"), Some("xxx"));
+        run("until test \"$N\" = x; do N=x; done", &mut shell);
+        assert_eq!(shell.vars.get("N"), Some("x"));
+        assert_eq!(run("while false; do true; done", &mut shell), 0);
+    }
+
+    #[test]
+    fn test_for_loop_splits_words() {
+        let mut shell = mock_shell();
+        run(
+            "L=; for x in a 'b c' $HOME_0SHELL d; do L=$L:$x; done",
+            &mut shell,
+        );
+        assert_eq!(shell.vars.get("L"), Some(":a:b c:d"));
+        assert_eq!(shell.vars.get("x"), Some("d"));
+    }
+
+    #[test]
+    fn test_case_matches_patterns_in_order() {
+        let mut shell = mock_shell();
+        let pick = |subject: &str, shell: &mut ShellState| {
+            run(
+                &format!(
+                    "case {} in *.c|*.h) K=c;; \"*\") K=star;; ?) K=one;; *) K=other;; esac",
+                    subject
+                ),
+                shell,
+            );
+            shell.vars.get("K").unwrap_or_default().to_string()
+        };
+        assert_eq!(pick("main.h", &mut shell), "c");
+        assert_eq!(pick("'*'", &mut shell), "star");
+        assert_eq!(pick("x", &mut shell), "one");
+        assert_eq!(pick("xy", &mut shell), "other");
+        assert_eq!(run("case a in b) false;; esac", &mut shell), 0);
+    }
+
+    #[test]
+    fn test_compound_output_is_piped_and_redirected() {
+        let dir = scratch_dir("compound-io");
+        let out = dir.join("out.txt").display().to_string();
+        let mut shell = mock_shell();
+
+        let line = format!("for x in one two; do echo $x; done > {}", out);
+        assert_eq!(run(&line, &mut shell), 0);
+        assert_eq!(fs::read_to_string(&out).unwrap(), "one\ntwo\n");
+
+        let line = "for x in a b; do echo $x; sh -c 'echo ext'; done | grep -c ext | grep -q 2";
+        assert_eq!(run(line, &mut shell), 0);
+
+        let line = format!("printf 'l1\\nl2\\n' | if true; then cat > {}; fi", out);
+        assert_eq!(run(&line, &mut shell), 0);
+        assert_eq!(fs::read_to_string(&out).unwrap(), "l1\nl2\n");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_exit_inside_a_loop_stops_it() {
+        let mut shell = mock_shell();
+        assert_eq!(run("while true; do exit 7; done; exit 1", &mut shell), 7);
+        assert_eq!(shell.exit_code, Some(7));
+    }
+
+    // --- Functions ---
+    #[test]
+    fn test_function_gets_positional_parameters() {
+        let mut shell = mock_shell();
+        run(
+            "f() { A=$1; N=$#; ALL=\"$*\"; }; f one 'two words' three",
+            &mut shell,
+        );
+        assert_eq!(shell.vars.get("A"), Some("one"));
+        assert_eq!(shell.vars.get("N"), Some("3"));
+        assert_eq!(shell.vars.get("ALL"), Some("one two words three"));
+        // Put back once the call is over
+        assert!(shell.positional.is_empty());
+
+        // "$@" keeps each argument whole
+        run(
+            "function count { C=0; for a in \"$@\"; do C=x$C; done; }; count 'a b' c",
+            &mut shell,
+        );
+        assert_eq!(shell.vars.get("C"), Some("xx0"));
+    }
+
+    #[test]
+    fn test_return_and_local() {
+        let mut shell = mock_shell();
+        assert_eq!(run("f() { return 4; X=no; }; f", &mut shell), 4);
+        assert_eq!(shell.vars.get("X"), None);
+        assert_eq!(
+            run(
+                "g() { for i in 1 2 3; do test $i = 2 && return 9; done; }; g",
+                &mut shell
+            ),
+            9
+        );
+        // The caller keeps going after a return
+        assert_eq!(run("g; true", &mut shell), 0);
+
+        run(
+            "V=outer; h() { local V=inner W; W=set; SEEN=$V; }; h",
+            &mut shell,
+        );
+        assert_eq!(shell.vars.get("SEEN"), Some("inner"));
+        assert_eq!(shell.vars.get("V"), Some("outer"));
+        assert_eq!(shell.vars.get("W"), None);
+
+        assert_eq!(run("return 2> /dev/null", &mut shell), 1);
+        assert_eq!(run("local Y 2> /dev/null", &mut shell), 1);
+    }
+
+    #[test]
+    fn test_functions_shadow_builtins_and_programs() {
+        let dir = scratch_dir("function-io");
+        let out = dir.join("out.txt").display().to_string();
+        let mut shell = mock_shell();
+
+        run("pwd() { P=mine; }; true() { return 3; }", &mut shell);
+        run("pwd", &mut shell);
+        assert_eq!(shell.vars.get("P"), Some("mine"));
+        assert_eq!(run("true", &mut shell), 3);
+
+        // Output goes through pipes and redirects like any command
+        let line = format!(
+            "say() {{ echo \"$1\"; }}; say hello | grep -q hello && say bye > {}",
+            out
+        );
+        assert_eq!(run(&line, &mut shell), 0);
+        assert_eq!(fs::read_to_string(&out).unwrap(), "bye\n");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_return_ends_a_sourced_file() {
+        let dir = scratch_dir("source-return");
+        fs::write(dir.join("lib.sh"), "A=1\nreturn 5\nA=2\n").unwrap();
+        let mut shell = mock_shell();
+        shell.pwd = PwdState::new(dir.to_string_lossy().into_owned(), "/".to_string());
+
+        assert_eq!(run(". lib.sh; B=$?", &mut shell), 0);
+        assert_eq!(shell.vars.get("A"), Some("1"));
+        assert_eq!(shell.vars.get("B"), Some("5"));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_runaway_recursion_is_stopped() {
+        // Debug builds need a bigger stack than the test threads get
+        let handle = std::thread::Builder::new()
+            .stack_size(32 * 1024 * 1024)
+            .spawn(|| {
+                let mut shell = mock_shell();
+                run("f() { N=x$N; f; }; f 2> /dev/null", &mut shell);
+                shell.vars.get("N").map(str::len)
+            })
+            .unwrap();
+        assert_eq!(handle.join().unwrap(), Some(MAX_CALL_DEPTH));
+    }
+
+    // --- Aliases ---
+    #[test]
+    fn test_alias_and_unalias() {
+        let mut shell = mock_shell();
+        assert_eq!(run("alias setx='X=from-alias' noop=true", &mut shell), 0);
+        run("setx", &mut shell);
+        assert_eq!(shell.vars.get("X"), Some("from-alias"));
+        assert_eq!(run("alias noop > /dev/null", &mut shell), 0);
+
+        assert_eq!(run("unalias setx", &mut shell), 0);
+        assert_eq!(run("setx 2> /dev/null", &mut shell), 127);
+        assert_eq!(run("unalias setx 2> /dev/null", &mut shell), 1);
+        assert_eq!(run("alias 'a b=x' 2> /dev/null", &mut shell), 1);
+
+        run("unalias -a", &mut shell);
+        assert!(shell.aliases.is_empty());
+    }
+
+    // --- Command substitution ---
+    #[test]
+    fn test_substitution_captures_builtins_and_externals() {
+        let mut shell = mock_shell();
+        run(
+            "A=$(pwd); B=\"$(echo '  x  y  ')\"; C=$(printf 'one\\n\\n')",
+            &mut shell,
+        );
+        assert_eq!(shell.vars.get("A"), Some("/tmp"));
+        assert_eq!(shell.vars.get("B"), Some("  x  y  "));
+        assert_eq!(shell.vars.get("C"), Some("one"));
+
+        // Unquoted output is split into words
+        run("N=0; for w in $(echo a b c); do N=x$N; done", &mut shell);
+        assert_eq!(shell.vars.get("N"), Some("xxx0"));
+        run("D=`echo back`", &mut shell);
+        assert_eq!(shell.vars.get("D"), Some("back"));
+    }
+
+    #[test]
+    fn test_substitution_runs_in_a_subshell() {
+        let _cwd = PROCESS_CWD.lock().unwrap_or_else(|e| e.into_inner());
+        let before = env::current_dir().unwrap();
+        let mut shell = mock_shell();
+        run("X=out; Y=$(X=in; cd /; echo $X; exit 4)", &mut shell);
+        assert_eq!(shell.vars.get("Y"), Some("in"));
+        assert_eq!(shell.vars.get("X"), Some("out"));
+        assert_eq!(shell.pwd.get_current_dir(), "/tmp");
+        assert_eq!(env::current_dir().unwrap(), before);
+        assert_eq!(shell.exit_code, None);
+
+        // `x=$(cmd)` takes the status of cmd
+        assert_eq!(run("Z=$(false)", &mut shell), 1);
+        assert_eq!(run("Z=$(true)", &mut shell), 0);
+        assert_eq!(run("f() { echo fn; }; Z=$(f)", &mut shell), 0);
+        assert_eq!(shell.vars.get("Z"), Some("fn"));
+    }
+
+    #[test]
+    fn test_heredoc_feeds_stdin() {
+        let dir = scratch_dir("heredoc");
+        let mut shell = mock_shell();
+        shell.pwd = PwdState::new(dir.display().to_string(), "/".to_string());
+
+        run(
+            "X=world; cat <<EOF > out\nhello $X\n$(echo sub)\nEOF",
+            &mut shell,
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("out")).unwrap(),
+            "hello world\nsub\n"
+        );
+        run("cat <<'EOF' > raw\n$X\nEOF", &mut shell);
+        assert_eq!(fs::read_to_string(dir.join("raw")).unwrap(), "$X\n");
+
+        // Externals, functions and blocks read it too
+        run("f() { tr a-z A-Z; }; f <<EOF > up\nshout\nEOF", &mut shell);
+        assert_eq!(fs::read_to_string(dir.join("up")).unwrap(), "SHOUT\n");
+        run("{ echo first; cat; } <<EOF > lines\na\nb\nEOF", &mut shell);
+        assert_eq!(
+            fs::read_to_string(dir.join("lines")).unwrap(),
+            "first\na\nb\n"
+        );
+
+        assert_eq!(run("grep -q world <<< \"$X\"", &mut shell), 0);
+        assert_eq!(run("grep -q nope <<< \"$X\"", &mut shell), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_arithmetic_expansion_and_command() {
+        let mut shell = mock_shell();
+        run("x=5; y=$(( x * 2 + $x )); (( x++ ))", &mut shell);
+        assert_eq!(shell.vars.get("y"), Some("15"));
+        assert_eq!(shell.vars.get("x"), Some("6"));
+
+        // `(( ))` succeeds for a non-zero value
+        assert_eq!(run("(( x > 3 ))", &mut shell), 0);
+        assert_eq!(run("(( x - 6 ))", &mut shell), 1);
+        run("n=0; while (( n < 4 )); do (( n += 1 )); done", &mut shell);
+        assert_eq!(shell.vars.get("n"), Some("4"));
+        run("if (( n == 4 )); then r=yes; else r=no; fi", &mut shell);
+        assert_eq!(shell.vars.get("r"), Some("yes"));
+
+        // A bad expression fails the command and changes nothing
+        assert_eq!(run("z=$(( 1 / 0 ))", &mut shell), 1);
+        assert_eq!(shell.vars.get("z"), None);
+        assert_eq!(run("(( 1 + ))", &mut shell), 1);
+        assert_eq!(run("echo $(( 2 ** -1 ))", &mut shell), 1);
     }
 }
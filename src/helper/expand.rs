@@ -0,0 +1,389 @@
+use crate::helper::arithmetic::evaluate;
+use crate::helper::brace::expand_braces;
+use crate::helper::executor::capture_output;
+use crate::helper::glob::{glob, has_magic};
+use crate::helper::parser::{Word, WordPart};
+use crate::helper::state_manager::ShellState;
+use std::borrow::Cow;
+use users::os::unix::UserExt;
+
+// --- Word Expansion ---
+// Runs right before a command executes, so `$?` sees the status of the
+// command that just finished rather than the one before the whole line.
+// Unquoted parameters are split on whitespace into several fields;
+// quoted text and quoted parameters never are. Each field is then globbed,
+// with its quoted characters escaped in `pattern` so they match literally.
+#[derive(Default)]
+struct Fields {
+    fields: Vec<String>,
+    current: String,
+    pattern: String,
+    // `""` or `"$EMPTY"` is still an argument, an unquoted empty `$X` is not
+    keep: bool,
+    split: bool,
+    // Directory to glob in; None turns globbing off
+    glob_cwd: Option<String>,
+    failglob: bool,
+    error: Option<String>,
+}
+
+impl Fields {
+    fn push_quoted(&mut self, text: &str) {
+        self.current.push_str(text);
+        for c in text.chars() {
+            if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                self.pattern.push('\\');
+            }
+            self.pattern.push(c);
+        }
+        self.keep = true;
+    }
+
+    fn push_literal(&mut self, text: &str) {
+        self.current.push_str(text);
+        self.pattern.push_str(text);
+    }
+
+    fn push_unquoted(&mut self, text: &str) {
+        if !self.split {
+            return self.push_literal(text);
+        }
+        for c in text.chars() {
+            if matches!(c, ' ' | '\t' | '\n') {
+                self.end_field();
+            } else {
+                self.current.push(c);
+                self.pattern.push(c);
+            }
+        }
+    }
+
+    fn end_field(&mut self) {
+        let pattern = std::mem::take(&mut self.pattern);
+        if !self.keep && self.current.is_empty() {
+            return;
+        }
+        self.keep = false;
+        let field = std::mem::take(&mut self.current);
+
+        if let Some(cwd) = &self.glob_cwd
+            && has_magic(&pattern)
+        {
+            let matches = glob(&pattern, cwd);
+            if !matches.is_empty() {
+                self.fields.extend(matches);
+                return;
+            }
+            // bash keeps the pattern as typed, zsh (and `set -o failglob`) refuses
+            if self.failglob && self.error.is_none() {
+                self.error = Some(format!("no matches found: {}", field));
+            }
+        }
+        self.fields.push(field);
+    }
+
+    fn expand(&mut self, parts: &[WordPart], shell: &mut ShellState) {
+        for part in parts {
+            match part {
+                WordPart::Literal(text) => self.push_literal(text),
+                WordPart::Quoted(text) => self.push_quoted(text),
+                // "$@" keeps every positional parameter a word of its own
+                WordPart::Param {
+                    name, quoted: true, ..
+                } if name == "@" && self.split => {
+                    for (i, arg) in shell.positional.iter().enumerate() {
+                        if i > 0 {
+                            self.end_field();
+                        }
+                        self.push_quoted(arg);
+                    }
+                }
+                WordPart::Param {
+                    name,
+                    quoted,
+                    default,
+                } => match (lookup_param(name, shell), default) {
+                    // `${X:-default}` kicks in for unset and empty alike
+                    (value, Some(default)) if value.is_empty() => self.expand(&default.0, shell),
+                    (value, _) if *quoted => self.push_quoted(&value),
+                    (value, _) => self.push_unquoted(&value),
+                },
+                // Split like an unquoted parameter unless it was in quotes
+                WordPart::Command { list, quoted } => {
+                    let output = capture_output(list, shell);
+                    if *quoted {
+                        self.push_quoted(&output);
+                    } else {
+                        self.push_unquoted(&output);
+                    }
+                }
+                // The expression is expanded first (`$(( $n + 1 ))`), then run
+                WordPart::Arith { expr, quoted } => {
+                    let mut inner = Fields::default();
+                    inner.expand(&expr.0, shell);
+                    let result = match inner.error {
+                        Some(error) => Err(error),
+                        None => {
+                            evaluate(&inner.current, &mut shell.vars).map_err(|e| e.to_string())
+                        }
+                    };
+                    match result {
+                        Ok(value) if *quoted => self.push_quoted(&value.to_string()),
+                        Ok(value) => self.push_unquoted(&value.to_string()),
+                        Err(error) => {
+                            self.error.get_or_insert(error);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// A single string, no field splitting or globbing (redirect targets, assignments).
+// Err when an arithmetic expansion failed
+pub fn expand_word(word: &Word, shell: &mut ShellState) -> Result<String, String> {
+    let mut fields = Fields::default();
+    fields.expand(&expand_tilde(word, shell).0, shell);
+    match fields.error {
+        Some(error) => Err(error),
+        None => Ok(fields.current),
+    }
+}
+
+// The word as a pattern for `case`: quoted characters only match themselves
+pub fn expand_pattern(word: &Word, shell: &mut ShellState) -> Result<String, String> {
+    let mut fields = Fields::default();
+    fields.expand(&expand_tilde(word, shell).0, shell);
+    match fields.error {
+        Some(error) => Err(error),
+        None => Ok(fields.pattern),
+    }
+}
+
+// Braces first, then `~`, then the rest word by word.
+// Err when a glob matched nothing and failglob is on, or arithmetic failed
+pub fn expand_words(words: &[Word], shell: &mut ShellState) -> Result<Vec<String>, String> {
+    let mut fields = Fields {
+        split: true,
+        glob_cwd: Some(shell.pwd.get_current_dir()),
+        failglob: shell.options.failglob,
+        ..Fields::default()
+    };
+    for word in words.iter().flat_map(expand_braces) {
+        fields.expand(&expand_tilde(&word, shell).0, shell);
+        fields.end_field();
+    }
+    match fields.error {
+        Some(error) => Err(error),
+        None => Ok(fields.fields),
+    }
+}
+
+// --- Tilde ---
+// An unquoted `~` starting a word, up to the first `/`: `~` is HOME, `~user`
+// that user's home, `~+` the current directory and `~-` the previous one.
+// The directory is never split or globbed. Unknown users and a quoted
+// prefix (`~"x"`) are left alone.
+fn expand_tilde<'a>(word: &'a Word, shell: &ShellState) -> Cow<'a, Word> {
+    let Some((WordPart::Literal(text), rest)) = word.0.split_first() else {
+        return Cow::Borrowed(word);
+    };
+    let Some(prefix) = text.strip_prefix('~') else {
+        return Cow::Borrowed(word);
+    };
+    let (name, tail) = match prefix.find('/') {
+        Some(slash) => prefix.split_at(slash),
+        None if rest.is_empty() => (prefix, ""),
+        None => return Cow::Borrowed(word),
+    };
+    let home_of = |user: users::User| user.home_dir().display().to_string();
+    let dir = match name {
+        "" => shell
+            .vars
+            .get("HOME")
+            .map(str::to_string)
+            .or_else(|| users::get_user_by_uid(users::get_current_uid()).map(home_of)),
+        "+" => Some(shell.pwd.get_current_dir()),
+        "-" => Some(shell.pwd.get_old_dir()),
+        user => users::get_user_by_name(user).map(home_of),
+    };
+    let Some(dir) = dir else {
+        return Cow::Borrowed(word);
+    };
+
+    let mut parts = vec![WordPart::Quoted(dir)];
+    if !tail.is_empty() {
+        parts.push(WordPart::Literal(tail.to_string()));
+    }
+    parts.extend(rest.iter().cloned());
+    Cow::Owned(Word(parts))
+}
+
+fn lookup_param(name: &str, shell: &ShellState) -> String {
+    match name {
+        "?" => shell.last_status.to_string(),
+        "#" => shell.positional.len().to_string(),
+        // Empty until something was started with `&`
+        "!" => shell
+            .jobs
+            .last_pid
+            .map(|pid| pid.to_string())
+            .unwrap_or_default(),
+        "@" | "*" => shell.positional.join(" "),
+        "0" => shell
+            .script_name
+            .clone()
+            .unwrap_or_else(|| "0-shell".to_string()),
+        // `${00}` is `$0` too
+        _ if name.chars().all(|c| c.is_ascii_digit()) => match name.parse::<usize>() {
+            Ok(0) => lookup_param("0", shell),
+            Ok(n) => shell.positional.get(n - 1).cloned().unwrap_or_default(),
+            Err(_) => String::new(),
+        },
+        _ => shell.vars.get(name).unwrap_or_default().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::pwd::PwdState;
+    use crate::helper::parser::{Token, parse_tokens};
+
+    fn words(input: &str) -> Vec<Word> {
+        parse_tokens(input)
+            .unwrap()
+            .into_iter()
+            .filter_map(|t| match t {
+                Token::Word(w) => Some(w),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn expand(input: &str, shell: &mut ShellState) -> Vec<String> {
+        expand_words(&words(input), shell).unwrap()
+    }
+
+    #[test]
+    fn test_expand_status() {
+        let mut shell = ShellState::new();
+        shell.last_status = 3;
+        assert_eq!(
+            expand("echo $? \"[$?]\" '$?'", &mut shell),
+            vec!["echo", "3", "[3]", "$?"]
+        );
+    }
+
+    #[test]
+    fn test_expand_variables_and_defaults() {
+        let mut shell = ShellState::new();
+        shell.vars.set("NAME", "world".to_string());
+        shell.vars.set("EMPTY", String::new());
+        shell.vars.unset("UNSET_0SHELL");
+        assert_eq!(
+            expand(
+                "$NAME ${NAME}s \"${EMPTY:-dflt}\" ${UNSET_0SHELL:-$NAME}",
+                &mut shell
+            ),
+            vec!["world", "worlds", "dflt", "world"]
+        );
+    }
+
+    #[test]
+    fn test_expand_positional_parameters() {
+        let mut shell = ShellState::new();
+        shell.positional = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(
+            expand("$# $1 ${2} ${3:-none} $0 ${0} ${00}", &mut shell),
+            vec!["2", "a", "b", "none", "0-shell", "0-shell", "0-shell"]
+        );
+    }
+
+    #[test]
+    fn test_unquoted_expansion_splits_quoted_does_not() {
+        let mut shell = ShellState::new();
+        shell.vars.set("LIST", "  a b\tc ".to_string());
+        shell.vars.set("EMPTY", String::new());
+        assert_eq!(
+            expand(
+                "x$LIST \"$LIST\" $EMPTY \"$EMPTY\" ${EMPTY:-'p q'}",
+                &mut shell
+            ),
+            vec!["x", "a", "b", "c", "  a b\tc ", "", "p q"]
+        );
+    }
+
+    #[test]
+    fn test_glob_only_unquoted_metacharacters() {
+        let dir = std::env::temp_dir().join(format!("0shell-expand-glob-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["b.rs", "a.rs", "*.rs"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let mut shell = ShellState::new();
+        shell.pwd = PwdState::new(dir.display().to_string(), "/".to_string());
+        shell.vars.set("PAT", "?.rs".to_string());
+
+        assert_eq!(
+            expand("ls *.rs", &mut shell),
+            vec!["ls", "*.rs", "a.rs", "b.rs"]
+        );
+        assert_eq!(
+            expand("ls '*'.rs \\*.rs", &mut shell),
+            vec!["ls", "*.rs", "*.rs"]
+        );
+        assert_eq!(
+            expand("ls $PAT \"$PAT\"", &mut shell),
+            vec!["ls", "*.rs", "a.rs", "b.rs", "?.rs"]
+        );
+        assert_eq!(expand("ls *.none", &mut shell), vec!["ls", "*.none"]);
+
+        shell.options.failglob = true;
+        assert_eq!(
+            expand_words(&words("ls *.none"), &mut shell),
+            Err("no matches found: *.none".to_string())
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_tilde_expansion() {
+        let mut shell = ShellState::new();
+        shell.pwd = PwdState::new("/work".to_string(), "/before".to_string());
+        shell.vars.set("HOME", "/home/me dir".to_string());
+        assert_eq!(
+            expand("~ ~/src ~+ ~-/x a~ \"~\" ~\"x\"", &mut shell),
+            vec![
+                "/home/me dir",
+                "/home/me dir/src",
+                "/work",
+                "/before/x",
+                "a~",
+                "~",
+                "~x"
+            ]
+        );
+        assert_eq!(expand("~root", &mut shell), vec!["/root"]);
+        assert_eq!(
+            expand("~no_such_user_0shell/x", &mut shell),
+            vec!["~no_such_user_0shell/x"]
+        );
+        // Assignments and redirect targets go through it too
+        let value = words("X=~/bin")[0].assignment().unwrap().1;
+        assert_eq!(expand_word(&value, &mut shell).unwrap(), "/home/me dir/bin");
+    }
+
+    #[test]
+    fn test_braces_come_before_other_expansions() {
+        let mut shell = ShellState::new();
+        shell.vars.set("X", "x y".to_string());
+        shell.vars.set("HOME", "/h".to_string());
+        assert_eq!(
+            expand("a{1..3} {$X,\"$X\"} {~,b}/c \"{a,b}\"", &mut shell),
+            vec!["a1", "a2", "a3", "x", "y", "x y", "/h/c", "b/c", "{a,b}"]
+        );
+    }
+}
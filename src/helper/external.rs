@@ -0,0 +1,198 @@
+use crate::helper::jobs::ProcessGroup;
+use std::{
+    env, fs,
+    io::ErrorKind,
+    os::unix::{
+        fs::PermissionsExt,
+        process::{CommandExt, ExitStatusExt},
+    },
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// --- PATH lookup ---
+// `path` is the shell's own PATH variable, not the one we were started with
+pub fn find_in_path(name: &str, cwd: &str, path: Option<&str>) -> Option<PathBuf> {
+    // Anything with a slash is a path, never a PATH lookup (same rule as bash)
+    if name.contains('/') {
+        let path = Path::new(cwd).join(name);
+        return if path.exists() { Some(path) } else { None };
+    }
+
+    env::split_paths(path?)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+// Killed-by-signal children report 128 + signo, like every POSIX shell
+pub fn exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or(0))
+}
+
+// Errors are reported here; the caller only gets the status to record.
+// `env` is the complete environment of the child (the exported variables).
+// With a `group` (job control on) the child joins that process group, or
+// leads a new one, and the group gets the terminal.
+pub fn spawn_external(
+    name: &str,
+    args: &[String],
+    cwd: &str,
+    env: &[(String, String)],
+    (stdin, stdout, stderr): (Stdio, Stdio, Stdio),
+    group: Option<ProcessGroup>,
+) -> Result<Child, i32> {
+    let path = env
+        .iter()
+        .find(|(key, _)| key == "PATH")
+        .map(|(_, value)| value.as_str());
+    let Some(program) = find_in_path(name, cwd, path) else {
+        eprintln!("command not found: {}", name);
+        return Err(127);
+    };
+
+    let mut command = Command::new(&program);
+    command
+        .arg0(name)
+        .args(args)
+        .current_dir(cwd)
+        .env_clear()
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(stderr);
+    if let Some(group) = group {
+        // Done in both processes, so whichever runs first sets things up
+        // before the program could read the terminal. Failing is harmless:
+        // the group is gone when the stages before this one already ended.
+        unsafe {
+            command.pre_exec(move || {
+                libc::setpgid(0, group.pgid);
+                // Still ignoring SIGTTOU here, like the shell
+                libc::tcsetpgrp(group.terminal, libc::getpgrp());
+                for signal in [libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU] {
+                    libc::signal(signal, libc::SIG_DFL);
+                }
+                Ok(())
+            });
+        }
+    }
+
+    match command.spawn() {
+        Ok(child) => {
+            if let Some(group) = group {
+                let pid = child.id() as i32;
+                let pgid = if group.pgid == 0 { pid } else { group.pgid };
+                unsafe {
+                    libc::setpgid(pid, pgid);
+                    libc::tcsetpgrp(group.terminal, pgid);
+                }
+            }
+            Ok(child)
+        }
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            eprintln!("{}: Permission denied", name);
+            Err(126)
+        }
+        Err(e) => {
+            eprintln!("{}: {}", name, e);
+            Err(126)
+        }
+    }
+}
+
+// --- Signals ---
+// The shell itself must survive Ctrl-C while a child owns the terminal.
+// A no-op handler (instead of SIG_IGN) is reset to the default by exec,
+// so the children still get interrupted normally.
+// The handler only notes the Ctrl-C, so a running loop can stop on it.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigint(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+// main clears it before each line
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+// For a Ctrl-C the shell did not get itself: job control gives the
+// foreground job its own process group, so only the job sees it
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+pub fn clear_interrupt() {
+    INTERRUPTED.store(false, Ordering::Relaxed);
+}
+
+pub fn install_signal_handlers() {
+    let handler: extern "C" fn(libc::c_int) = on_sigint;
+    unsafe {
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+    #[test]
+    fn test_find_in_path_resolves_sh() {
+        let found = find_in_path("sh", "/", Some(PATH));
+        assert!(found.is_some());
+        assert!(is_executable(&found.unwrap()));
+    }
+
+    #[test]
+    fn test_find_in_path_missing() {
+        assert_eq!(
+            find_in_path("definitely-not-a-real-binary-0shell", "/", Some(PATH)),
+            None
+        );
+    }
+
+    fn run_with_env(name: &str, args: &[&str], env: &[(&str, &str)]) -> i32 {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let mut env: Vec<(String, String)> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        env.push(("PATH".to_string(), PATH.to_string()));
+        let stdio = (Stdio::null(), Stdio::null(), Stdio::null());
+        match spawn_external(name, &args, "/tmp", &env, stdio, None) {
+            Ok(mut child) => exit_code(child.wait().unwrap()),
+            Err(code) => code,
+        }
+    }
+
+    fn run(name: &str, args: &[&str]) -> i32 {
+        run_with_env(name, args, &[])
+    }
+
+    #[test]
+    fn test_spawn_external_reports_status() {
+        assert_eq!(run("true", &[]), 0);
+        assert_eq!(run("false", &[]), 1);
+        assert_eq!(run("sh", &["-c", "exit 7"]), 7);
+        assert_eq!(run("definitely-not-a-real-binary-0shell", &[]), 127);
+    }
+
+    #[test]
+    fn test_spawn_external_gets_only_given_env() {
+        let check = ["-c", "test \"$X\" = y && test -z \"$HOME\""];
+        assert_eq!(run_with_env("sh", &check, &[("X", "y")]), 0);
+        assert_eq!(run_with_env("sh", &check, &[("X", "n")]), 1);
+    }
+}
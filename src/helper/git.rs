@@ -0,0 +1,775 @@
+use flate2::read::ZlibDecoder;
+use std::{
+    collections::{BinaryHeap, HashMap},
+    fs::{self, File},
+    io::{BufReader, Read, Seek, SeekFrom},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+// --- Git Prompt Segment (`\g` in PS1) ---
+// Everything comes straight out of `.git`, no `git` process and no network:
+//   branch        HEAD, or the short hash when detached
+//   ahead/behind  commit graph walk against the upstream from `config`
+//   dirty         tracked files whose size/mtime no longer match the index,
+//                 or an index that no longer matches HEAD's tree
+// Untracked files do not count. The result is cached for one directory and
+// only recomputed after a command ran (see GitCache).
+
+// Commits looked at before giving up on ahead/behind
+const WALK_LIMIT: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitInfo {
+    pub branch: String,
+    pub ahead: usize,
+    pub behind: usize,
+    pub dirty: bool,
+}
+
+impl GitInfo {
+    // " (main ↑1↓2 *)", with a leading space like bash's __git_ps1
+    pub fn segment(&self) -> String {
+        let mut out = format!(" ({}", self.branch);
+        if self.ahead > 0 || self.behind > 0 {
+            out.push(' ');
+        }
+        if self.ahead > 0 {
+            out.push_str(&format!("↑{}", self.ahead));
+        }
+        if self.behind > 0 {
+            out.push_str(&format!("↓{}", self.behind));
+        }
+        if self.dirty {
+            out.push_str(" *");
+        }
+        out.push(')');
+        out
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GitCache {
+    dir: Option<String>,
+    stale: bool,
+    info: Option<GitInfo>,
+}
+
+impl GitCache {
+    // Any command may have committed, switched branch or edited files
+    pub fn mark_stale(&mut self) {
+        self.stale = true;
+    }
+
+    pub fn refresh(&mut self, dir: &str) {
+        if self.stale || self.dir.as_deref() != Some(dir) {
+            self.info = status(Path::new(dir));
+            self.dir = Some(dir.to_string());
+            self.stale = false;
+        }
+    }
+
+    // Only what was computed for `dir` itself
+    pub fn info_for(&self, dir: &str) -> Option<&GitInfo> {
+        match self.dir.as_deref() {
+            Some(cached) if cached == dir => self.info.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+// --- Repository Layout ---
+struct Repo {
+    // HEAD and index live here (per worktree)
+    git_dir: PathBuf,
+    // Refs, objects and config live here (shared by worktrees)
+    common_dir: PathBuf,
+    work_tree: PathBuf,
+}
+
+fn find_repo(start: &Path) -> Option<Repo> {
+    for dir in start.ancestors() {
+        let dot_git = dir.join(".git");
+        let git_dir = if dot_git.is_dir() {
+            dot_git
+        } else if dot_git.is_file() {
+            // Worktrees and submodules: a file saying "gitdir: <path>"
+            let text = fs::read_to_string(&dot_git).ok()?;
+            dir.join(text.trim().strip_prefix("gitdir:")?.trim())
+        } else {
+            continue;
+        };
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(path) => git_dir.join(path.trim()),
+            Err(_) => git_dir.clone(),
+        };
+        return Some(Repo {
+            git_dir,
+            common_dir,
+            work_tree: dir.to_path_buf(),
+        });
+    }
+    None
+}
+
+fn is_hash(text: &str) -> bool {
+    text.len() == 40 && text.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+// A ref name (or HEAD) to a commit hash, following `ref: ` links
+fn resolve_ref(repo: &Repo, name: &str) -> Option<String> {
+    let mut name = name.to_string();
+    for _ in 0..5 {
+        let loose = [&repo.git_dir, &repo.common_dir]
+            .iter()
+            .find_map(|dir| fs::read_to_string(dir.join(&name)).ok());
+        match loose.as_deref().map(str::trim) {
+            Some(target) if target.starts_with("ref: ") => name = target[5..].to_string(),
+            Some(hash) if is_hash(hash) => return Some(hash.to_string()),
+            _ => return packed_ref(repo, &name),
+        }
+    }
+    None
+}
+
+fn packed_ref(repo: &Repo, name: &str) -> Option<String> {
+    let text = fs::read_to_string(repo.common_dir.join("packed-refs")).ok()?;
+    text.lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+        .filter_map(|line| line.split_once(' '))
+        .find(|(_, ref_name)| *ref_name == name)
+        .map(|(hash, _)| hash.to_string())
+}
+
+// The ref `branch` tracks, from `[branch "name"]` in config
+fn upstream_ref(repo: &Repo, branch: &str) -> Option<String> {
+    let text = fs::read_to_string(repo.common_dir.join("config")).ok()?;
+    let header = format!("[branch \"{}\"]", branch);
+    let mut in_section = false;
+    let (mut remote, mut merge) = (None, None);
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_section = line == header;
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            match key.trim() {
+                "remote" => remote = Some(value.trim().to_string()),
+                "merge" => merge = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+    let merge = merge?;
+    let name = merge.strip_prefix("refs/heads/")?;
+    match remote?.as_str() {
+        "." => Some(merge.clone()),
+        remote => Some(format!("refs/remotes/{}/{}", remote, name)),
+    }
+}
+
+// --- Objects ---
+// Loose objects are zlib files; packed ones sit in packfiles found through
+// their .idx, possibly stored as a delta against another object
+struct Objects {
+    dir: PathBuf,
+    packs: Vec<(Vec<u8>, PathBuf)>,
+}
+
+fn be32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn hex_to_bytes(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn inflate(reader: impl Read) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    ZlibDecoder::new(reader).read_to_end(&mut out).ok()?;
+    Some(out)
+}
+
+fn read_byte(reader: &mut impl Read) -> Option<u8> {
+    let mut byte = [0u8];
+    reader.read_exact(&mut byte).ok()?;
+    Some(byte[0])
+}
+
+// Little-endian base-128 (delta headers)
+fn delta_varint(data: &[u8], i: &mut usize) -> Option<usize> {
+    let (mut value, mut shift) = (0usize, 0);
+    loop {
+        let byte = *data.get(*i)?;
+        *i += 1;
+        // A corrupt delta, not a size we could ever allocate
+        if shift >= usize::BITS {
+            return None;
+        }
+        value |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+// Big-endian with an added one per byte (OFS_DELTA offsets, index v4 paths)
+fn offset_varint(mut next: impl FnMut() -> Option<u8>) -> Option<usize> {
+    let mut byte = next()?;
+    let mut value = (byte & 0x7f) as usize;
+    while byte & 0x80 != 0 {
+        byte = next()?;
+        value = (value.checked_add(1)?.checked_mul(0x80)?) | (byte & 0x7f) as usize;
+    }
+    Some(value)
+}
+
+fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut i = 0;
+    let _base_len = delta_varint(delta, &mut i)?;
+    let target_len = delta_varint(delta, &mut i)?;
+    let mut out = Vec::with_capacity(target_len);
+    while i < delta.len() {
+        let op = delta[i];
+        i += 1;
+        if op & 0x80 != 0 {
+            // Copy from the base: which offset/size bytes follow is in the op
+            let (mut offset, mut size) = (0usize, 0usize);
+            for bit in 0..4 {
+                if op & (1 << bit) != 0 {
+                    offset |= (*delta.get(i)? as usize) << (8 * bit);
+                    i += 1;
+                }
+            }
+            for bit in 0..3 {
+                if op & (0x10 << bit) != 0 {
+                    size |= (*delta.get(i)? as usize) << (8 * bit);
+                    i += 1;
+                }
+            }
+            if size == 0 {
+                size = 0x10000;
+            }
+            out.extend_from_slice(base.get(offset..offset + size)?);
+        } else if op != 0 {
+            // Insert the next `op` bytes as they are
+            out.extend_from_slice(delta.get(i..i + op as usize)?);
+            i += op as usize;
+        } else {
+            return None;
+        }
+    }
+    (out.len() == target_len).then_some(out)
+}
+
+impl Objects {
+    fn open(repo: &Repo) -> Self {
+        let dir = repo.common_dir.join("objects");
+        let mut packs = Vec::new();
+        if let Ok(entries) = fs::read_dir(dir.join("pack")) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|ext| ext == "idx")
+                    && let Ok(idx) = fs::read(&path)
+                {
+                    packs.push((idx, path.with_extension("pack")));
+                }
+            }
+        }
+        Objects { dir, packs }
+    }
+
+    // (type, contents); types as in packfiles: 1 commit, 2 tree, 3 blob, 4 tag
+    fn read(&self, hash: &str, depth: usize) -> Option<(u8, Vec<u8>)> {
+        if depth > 50 {
+            return None;
+        }
+        let loose = self.dir.join(hash.get(..2)?).join(hash.get(2..)?);
+        if let Ok(file) = File::open(loose) {
+            let data = inflate(file)?;
+            let nul = data.iter().position(|&b| b == 0)?;
+            let kind = match data[..nul].split(|&b| b == b' ').next()? {
+                b"commit" => 1,
+                b"tree" => 2,
+                b"blob" => 3,
+                b"tag" => 4,
+                _ => return None,
+            };
+            return Some((kind, data[nul + 1..].to_vec()));
+        }
+        let wanted = hex_to_bytes(hash)?;
+        self.packs.iter().find_map(|(idx, pack)| {
+            let offset = pack_offset(idx, &wanted)?;
+            self.read_packed(pack, offset, depth)
+        })
+    }
+
+    fn read_packed(&self, pack: &Path, offset: u64, depth: usize) -> Option<(u8, Vec<u8>)> {
+        let mut file = BufReader::new(File::open(pack).ok()?);
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        // Type in bits 4-6 of the first byte, then the size (unused here)
+        let mut byte = read_byte(&mut file)?;
+        let kind = (byte >> 4) & 7;
+        while byte & 0x80 != 0 {
+            byte = read_byte(&mut file)?;
+        }
+        match kind {
+            1..=4 => Some((kind, inflate(file)?)),
+            6 => {
+                let back = offset_varint(|| read_byte(&mut file))? as u64;
+                let delta = inflate(&mut file)?;
+                let (kind, base) = self.read_packed(pack, offset.checked_sub(back)?, depth + 1)?;
+                Some((kind, apply_delta(&base, &delta)?))
+            }
+            7 => {
+                let mut base_hash = [0u8; 20];
+                file.read_exact(&mut base_hash).ok()?;
+                let delta = inflate(&mut file)?;
+                let (kind, base) = self.read(&bytes_to_hex(&base_hash), depth + 1)?;
+                Some((kind, apply_delta(&base, &delta)?))
+            }
+            _ => None,
+        }
+    }
+}
+
+// Offset of an object in the pack, from a version 2 .idx
+fn pack_offset(idx: &[u8], wanted: &[u8]) -> Option<u64> {
+    if idx.len() < 8 + 1024 || idx[..4] != [0xff, b't', b'O', b'c'] || be32(&idx[4..8]) != 2 {
+        return None;
+    }
+    let fanout = |byte: usize| be32(&idx[8 + byte * 4..]) as usize;
+    let count = fanout(255);
+    let hashes = 8 + 1024;
+    let hash_at = |i: usize| idx.get(hashes + i * 20..hashes + i * 20 + 20);
+
+    let first = *wanted.first()? as usize;
+    let (mut lo, mut hi) = (
+        if first == 0 { 0 } else { fanout(first - 1) },
+        fanout(first),
+    );
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        match hash_at(mid)?.cmp(wanted) {
+            std::cmp::Ordering::Less => lo = mid + 1,
+            std::cmp::Ordering::Greater => hi = mid,
+            std::cmp::Ordering::Equal => {
+                let offsets = hashes + count * 24;
+                let at = offsets + mid * 4;
+                let small = be32(idx.get(at..at + 4)?);
+                if small & 0x8000_0000 == 0 {
+                    return Some(small as u64);
+                }
+                // Packs over 2GB keep big offsets in a second table
+                let large = offsets + count * 4 + (small & 0x7fff_ffff) as usize * 8;
+                let bytes = idx.get(large..large + 8)?;
+                return Some(u64::from_be_bytes(bytes.try_into().ok()?));
+            }
+        }
+    }
+    None
+}
+
+struct Commit {
+    tree: String,
+    parents: Vec<String>,
+    time: i64,
+}
+
+fn read_commit(objects: &Objects, hash: &str) -> Option<Commit> {
+    let (kind, data) = objects.read(hash, 0)?;
+    if kind != 1 {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&data);
+    let mut commit = Commit {
+        tree: String::new(),
+        parents: Vec::new(),
+        time: 0,
+    };
+    for line in text.lines().take_while(|line| !line.is_empty()) {
+        if let Some(tree) = line.strip_prefix("tree ") {
+            commit.tree = tree.to_string();
+        } else if let Some(parent) = line.strip_prefix("parent ") {
+            commit.parents.push(parent.to_string());
+        } else if let Some(committer) = line.strip_prefix("committer ") {
+            // "Name <mail> 1700000000 +0100"
+            commit.time = committer
+                .rsplit(' ')
+                .nth(1)
+                .and_then(|t| t.parse().ok())
+                .unwrap_or(0);
+        }
+    }
+    Some(commit)
+}
+
+// Commits only `local` has, and only `upstream` has. Walks newest first from
+// both tips, marking which side reaches each commit, and stops once every
+// commit still queued is reachable from both (shared history).
+fn ahead_behind(objects: &Objects, local: &str, upstream: &str) -> Option<(usize, usize)> {
+    const LOCAL: u8 = 1;
+    const UPSTREAM: u8 = 2;
+    const BOTH: u8 = 3;
+
+    let mut commits: HashMap<String, Commit> = HashMap::new();
+    let mut flags: HashMap<String, u8> = HashMap::new();
+    // Flags each commit had when it was counted
+    let mut counted: HashMap<String, u8> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    let (mut ahead, mut behind) = (0usize, 0usize);
+
+    for (hash, flag) in [(local, LOCAL), (upstream, UPSTREAM)] {
+        *flags.entry(hash.to_string()).or_default() |= flag;
+        let commit = read_commit(objects, hash)?;
+        queue.push((commit.time, hash.to_string()));
+        commits.insert(hash.to_string(), commit);
+    }
+
+    let mut steps = 0;
+    while let Some((_, hash)) = queue.pop() {
+        steps += 1;
+        if steps > WALK_LIMIT {
+            return None;
+        }
+        let flag = flags[&hash];
+        let before = counted.insert(hash.clone(), flag).unwrap_or(0);
+        if before == flag {
+            continue;
+        }
+        // A commit first reached from one side may turn out to be shared
+        match before {
+            LOCAL => ahead -= 1,
+            UPSTREAM => behind -= 1,
+            _ => {}
+        }
+        match flag {
+            LOCAL => ahead += 1,
+            UPSTREAM => behind += 1,
+            _ => {}
+        }
+
+        let parents = commits[&hash].parents.clone();
+        for parent in parents {
+            let known = flags.get(&parent).copied().unwrap_or(0);
+            if known | flag == known {
+                continue;
+            }
+            // A shallow clone simply ends where its objects do
+            if !commits.contains_key(&parent) {
+                let Some(commit) = read_commit(objects, &parent) else {
+                    continue;
+                };
+                commits.insert(parent.clone(), commit);
+            }
+            flags.insert(parent.clone(), known | flag);
+            queue.push((commits[&parent].time, parent));
+        }
+
+        // ...unless one of them still has to be un-counted from one side
+        let settled =
+            |hash: &String| flags[hash] == BOTH && counted.get(hash).is_none_or(|&c| c == BOTH);
+        if queue.iter().all(|(_, hash)| settled(hash)) {
+            break;
+        }
+    }
+    Some((ahead, behind))
+}
+
+// --- Index ---
+// Entries: 62 fixed bytes (stat data, hash, flags), maybe 2 more flag bytes,
+// then the path. Versions 2/3 pad each entry to 8 bytes with NULs; version 4
+// stores each path as "drop N bytes of the previous path, then add this".
+fn is_dirty(repo: &Repo, head_tree: Option<&str>) -> bool {
+    let Ok(data) = fs::read(repo.git_dir.join("index")) else {
+        return false;
+    };
+    if data.len() < 32 || &data[..4] != b"DIRC" {
+        return false;
+    }
+    let version = be32(&data[4..8]);
+    let count = be32(&data[8..12]);
+    let mut i = 12;
+    let mut path: Vec<u8> = Vec::new();
+
+    for _ in 0..count {
+        let Some(entry) = data.get(i..i + 62) else {
+            return false;
+        };
+        let mtime = be32(&entry[8..12]);
+        let mode = be32(&entry[24..28]);
+        let size = be32(&entry[36..40]);
+        let flags = u16::from_be_bytes([entry[60], entry[61]]);
+        let mut j = i + 62;
+        // assume-unchanged and skip-worktree entries are not checked
+        let mut skip = flags & 0x8000 != 0;
+        if version >= 3 && flags & 0x4000 != 0 {
+            let Some(extended) = data.get(j..j + 2) else {
+                return false;
+            };
+            skip |= u16::from_be_bytes([extended[0], extended[1]]) & 0x4000 != 0;
+            j += 2;
+        }
+
+        if version == 4 {
+            let Some(strip) = offset_varint(|| {
+                let byte = data.get(j).copied();
+                j += 1;
+                byte
+            }) else {
+                return false;
+            };
+            let Some(end) = data[j..].iter().position(|&b| b == 0) else {
+                return false;
+            };
+            path.truncate(path.len().saturating_sub(strip));
+            path.extend_from_slice(&data[j..j + end]);
+            j += end + 1;
+        } else {
+            let Some(end) = data[j..].iter().position(|&b| b == 0) else {
+                return false;
+            };
+            path = data[j..j + end].to_vec();
+            j = i + ((j - i + end + 8) & !7);
+        }
+        i = j;
+
+        // Submodules (gitlinks) are directories, not files
+        if skip || mode & 0o170000 == 0o160000 {
+            continue;
+        }
+        let file = repo.work_tree.join(String::from_utf8_lossy(&path).as_ref());
+        match fs::symlink_metadata(file) {
+            Ok(meta) if meta.size() as u32 == size && meta.mtime() as u32 == mtime => {}
+            _ => return true,
+        }
+    }
+
+    // Staged changes: the cached root tree (TREE extension) is invalidated
+    // by `git add` and only matches HEAD again after a commit
+    let Some(head_tree) = head_tree else {
+        return count > 0;
+    };
+    let end = data.len() - 20;
+    while i + 8 <= end {
+        let signature = &data[i..i + 4];
+        let len = be32(&data[i + 4..i + 8]) as usize;
+        let body = &data[(i + 8).min(end)..(i + 8 + len).min(end)];
+        if signature == b"TREE" {
+            return root_tree(body).is_none_or(|root| root != head_tree);
+        }
+        i += 8 + len;
+    }
+    false
+}
+
+// Hash of the root in a TREE extension; None when it was invalidated
+fn root_tree(body: &[u8]) -> Option<String> {
+    // "" NUL "<entries> <subtrees>\n" <20 byte hash>
+    let nul = body.iter().position(|&b| b == 0)?;
+    let newline = body.iter().position(|&b| b == b'\n')?;
+    let counts = std::str::from_utf8(&body[nul + 1..newline]).ok()?;
+    let entries: i64 = counts.split(' ').next()?.parse().ok()?;
+    if nul != 0 || entries < 0 {
+        return None;
+    }
+    Some(bytes_to_hex(body.get(newline + 1..newline + 21)?))
+}
+
+pub fn status(dir: &Path) -> Option<GitInfo> {
+    let repo = find_repo(dir)?;
+    let head = fs::read_to_string(repo.git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    let objects = Objects::open(&repo);
+
+    let (branch, hash) = match head.strip_prefix("ref: ") {
+        Some(target) => (
+            target
+                .strip_prefix("refs/heads/")
+                .unwrap_or(target)
+                .to_string(),
+            resolve_ref(&repo, target),
+        ),
+        None if is_hash(head) => (head[..7].to_string(), Some(head.to_string())),
+        None => return None,
+    };
+    let head_commit = hash.as_deref().and_then(|hash| read_commit(&objects, hash));
+
+    let upstream = upstream_ref(&repo, &branch).and_then(|name| resolve_ref(&repo, &name));
+    let (ahead, behind) = match (&hash, &upstream) {
+        (Some(local), Some(upstream)) => ahead_behind(&objects, local, upstream).unwrap_or((0, 0)),
+        _ => (0, 0),
+    };
+
+    Some(GitInfo {
+        dirty: is_dirty(&repo, head_commit.as_ref().map(|c| c.tree.as_str())),
+        branch,
+        ahead,
+        behind,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    // Real repositories made with the git CLI; skipped where it is missing
+    fn git(dir: &Path, args: &[&str]) -> bool {
+        Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("HOME", dir)
+            .env("GIT_AUTHOR_NAME", "t")
+            .env("GIT_AUTHOR_EMAIL", "t@t")
+            .env("GIT_COMMITTER_NAME", "t")
+            .env("GIT_COMMITTER_EMAIL", "t@t")
+            .output()
+            .is_ok_and(|out| out.status.success())
+    }
+
+    fn scratch_repo(name: &str) -> Option<PathBuf> {
+        let dir = std::env::temp_dir().join(format!("0shell-git-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        if !git(&dir, &["init", "-q", "-b", "main"]) {
+            return None;
+        }
+        Some(dir)
+    }
+
+    fn commit(dir: &Path, file: &str, text: &str) {
+        fs::write(dir.join(file), text).unwrap();
+        assert!(git(dir, &["add", file]));
+        assert!(git(dir, &["commit", "-q", "-m", text]));
+    }
+
+    #[test]
+    fn test_segment() {
+        let mut info = GitInfo {
+            branch: "main".to_string(),
+            ahead: 0,
+            behind: 0,
+            dirty: false,
+        };
+        assert_eq!(info.segment(), " (main)");
+        info.ahead = 1;
+        info.behind = 2;
+        info.dirty = true;
+        assert_eq!(info.segment(), " (main ↑1↓2 *)");
+    }
+
+    #[test]
+    fn test_branch_and_dirty_marker() {
+        let Some(dir) = scratch_repo("dirty") else {
+            return;
+        };
+        commit(&dir, "a.txt", "one");
+        let info = status(&dir.join(".")).unwrap();
+        assert_eq!((info.branch.as_str(), info.dirty), ("main", false));
+
+        // Found from a subdirectory too
+        fs::create_dir(dir.join("sub")).unwrap();
+        assert!(status(&dir.join("sub")).is_some());
+
+        fs::write(dir.join("a.txt"), "changed").unwrap();
+        assert!(status(&dir).unwrap().dirty);
+        // Staged but not committed is still dirty
+        assert!(git(&dir, &["add", "a.txt"]));
+        assert!(status(&dir).unwrap().dirty);
+        assert!(git(&dir, &["commit", "-q", "-m", "two"]));
+        assert!(!status(&dir).unwrap().dirty);
+
+        // Untracked files do not count
+        fs::write(dir.join("new.txt"), "x").unwrap();
+        assert!(!status(&dir).unwrap().dirty);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_ahead_behind_loose_and_packed() {
+        let Some(dir) = scratch_repo("ahead") else {
+            return;
+        };
+        commit(&dir, "a.txt", "base");
+        assert!(git(&dir, &["branch", "other"]));
+        commit(&dir, "a.txt", "local");
+        assert!(git(&dir, &["checkout", "-q", "other"]));
+        commit(&dir, "b.txt", "up 1");
+        commit(&dir, "b.txt", "up 2");
+        assert!(git(&dir, &["checkout", "-q", "main"]));
+        assert!(git(&dir, &["config", "branch.main.remote", "."]));
+        assert!(git(
+            &dir,
+            &["config", "branch.main.merge", "refs/heads/other"]
+        ));
+
+        let info = status(&dir).unwrap();
+        assert_eq!((info.ahead, info.behind), (1, 2));
+
+        // Same answer once everything is in a packfile with packed refs
+        assert!(git(&dir, &["gc", "-q", "--aggressive"]));
+        assert!(!dir.join(".git/refs/heads/other").exists());
+        let info = status(&dir).unwrap();
+        assert_eq!((info.ahead, info.behind, info.dirty), (1, 2, false));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_detached_head_and_no_repo() {
+        let Some(dir) = scratch_repo("detached") else {
+            return;
+        };
+        commit(&dir, "a.txt", "one");
+        let head = resolve_ref(&find_repo(&dir).unwrap(), "HEAD").unwrap();
+        assert!(git(&dir, &["checkout", "-q", "--detach"]));
+        assert_eq!(status(&dir).unwrap().branch, head[..7]);
+
+        fs::remove_dir_all(dir.join(".git")).unwrap();
+        assert_eq!(status(&dir), None);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_apply_delta() {
+        let base = b"hello world";
+        // sizes 11 -> 11, copy "hello " (offset 0, size 6), insert "there"
+        let delta = [11, 11, 0x90, 6, 5, b't', b'h', b'e', b'r', b'e'];
+        assert_eq!(apply_delta(base, &delta).unwrap(), b"hello there");
+
+        // Sizes that never end are a bad delta, not a crash
+        assert_eq!(apply_delta(base, &[0xff; 20]), None);
+        let mut i = 0;
+        assert_eq!(offset_varint(|| Some(0xff)), None);
+        assert_eq!(delta_varint(&[0x80; 12], &mut i), None);
+    }
+
+    #[test]
+    fn test_truncated_pack_index() {
+        // A v2 index claiming one object whose offset table was cut off
+        let hash = [0xab; 20];
+        let mut idx = vec![0xff, b't', b'O', b'c', 0, 0, 0, 2];
+        for byte in 0..256 {
+            idx.extend_from_slice(&u32::to_be_bytes(u32::from(byte >= 0xab)));
+        }
+        idx.extend_from_slice(&hash);
+        idx.extend_from_slice(&[0; 4]);
+        idx.extend_from_slice(&[0, 0]);
+        assert_eq!(pack_offset(&idx, &hash), None);
+
+        idx.extend_from_slice(&[0, 42]);
+        assert_eq!(pack_offset(&idx, &hash), Some(42));
+    }
+}
@@ -0,0 +1,228 @@
+use std::fs;
+use std::path::Path;
+
+// --- Filename Globbing ---
+// Patterns come from expansion with quoted characters backslash-escaped,
+// so `"*".rs` reaches us as `\*.rs` and only matches a file literally named so.
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+fn compile(pattern: &str) -> Vec<Token> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                tokens.push(Token::Char(chars[i + 1]));
+                i += 2;
+                continue;
+            }
+            '*' => tokens.push(Token::Star),
+            '?' => tokens.push(Token::Any),
+            '[' => match compile_class(&chars[i + 1..]) {
+                Some((class, used)) => {
+                    tokens.push(class);
+                    i += used + 1;
+                    continue;
+                }
+                // No closing bracket: just a character
+                None => tokens.push(Token::Char('[')),
+            },
+            c => tokens.push(Token::Char(c)),
+        }
+        i += 1;
+    }
+    tokens
+}
+
+// `chars` starts right after the `[`; returns the class and how much it used
+fn compile_class(chars: &[char]) -> Option<(Token, usize)> {
+    let mut i = 0;
+    let negated = matches!(chars.first(), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let mut c = *chars.get(i)?;
+        // A `]` right after `[` or `[!` is part of the set
+        if c == ']' && !first {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+        if c == '\\' {
+            i += 1;
+            c = *chars.get(i)?;
+        }
+        first = false;
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|end| *end != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+}
+
+fn matches_tokens(tokens: &[Token], name: &[char]) -> bool {
+    match tokens.split_first() {
+        None => name.is_empty(),
+        Some((Token::Star, rest)) => {
+            (0..=name.len()).any(|skip| matches_tokens(rest, &name[skip..]))
+        }
+        Some((token, rest)) => {
+            let Some((c, name_rest)) = name.split_first() else {
+                return false;
+            };
+            let ok = match token {
+                Token::Char(expected) => expected == c,
+                Token::Any => true,
+                Token::Class { negated, ranges } => {
+                    ranges.iter().any(|(lo, hi)| lo <= c && c <= hi) != *negated
+                }
+                Token::Star => unreachable!(),
+            };
+            ok && matches_tokens(rest, name_rest)
+        }
+    }
+}
+
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let name: Vec<char> = name.chars().collect();
+    matches_tokens(&compile(pattern), &name)
+}
+
+// True if the pattern has an unescaped `*`, `?` or `[`
+pub fn has_magic(pattern: &str) -> bool {
+    compile(pattern)
+        .iter()
+        .any(|token| !matches!(token, Token::Char(_)))
+}
+
+fn unescape(pattern: &str) -> String {
+    compile(pattern)
+        .into_iter()
+        .map(|token| match token {
+            Token::Char(c) => c,
+            _ => unreachable!("only called on patterns without magic"),
+        })
+        .collect()
+}
+
+// Paths matching `pattern`, relative to `cwd` unless the pattern is absolute.
+// Sorted; empty when nothing matches.
+pub fn glob(pattern: &str, cwd: &str) -> Vec<String> {
+    let absolute = pattern.starts_with('/');
+    let mut found = vec![if absolute {
+        "/".to_string()
+    } else {
+        String::new()
+    }];
+
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+    let dirs_only = pattern.ends_with('/');
+
+    for (i, component) in components.iter().enumerate() {
+        let is_last = i + 1 == components.len();
+        let mut next = Vec::new();
+        for prefix in &found {
+            let base = Path::new(cwd).join(if prefix.is_empty() { "." } else { prefix });
+            if !has_magic(component) {
+                next.push(format!("{}{}", prefix, unescape(component)));
+                continue;
+            }
+            let Ok(entries) = fs::read_dir(&base) else {
+                continue;
+            };
+            // Hidden files only match a pattern that starts with a dot
+            let show_hidden = component.starts_with('.') || component.starts_with("\\.");
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name.starts_with('.') && !show_hidden {
+                    continue;
+                }
+                if matches(component, &name) {
+                    next.push(format!("{}{}", prefix, name));
+                }
+            }
+        }
+        if !is_last {
+            next.retain(|path| Path::new(cwd).join(path).is_dir());
+            next.iter_mut().for_each(|path| path.push('/'));
+        }
+        found = next;
+    }
+
+    found.retain(|path| {
+        let full = Path::new(cwd).join(path);
+        full.symlink_metadata().is_ok() && (!dirs_only || full.is_dir())
+    });
+    if dirs_only {
+        found.iter_mut().for_each(|path| path.push('/'));
+    }
+    found.sort();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("0shell-glob-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_matches_wildcards() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "main.rc"));
+        assert!(matches("?a*", "bar"));
+        assert!(matches("f[a-c]o", "fbo"));
+        assert!(!matches("f[!a-c]o", "fbo"));
+        assert!(matches("[]x]", "]"));
+        assert!(matches("a[", "a["));
+    }
+
+    #[test]
+    fn test_escaped_characters_are_literal() {
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "x"));
+        assert!(!has_magic("\\*.rs"));
+        assert!(has_magic("src/*.rs"));
+    }
+
+    #[test]
+    fn test_glob_sorted_relative_to_cwd() {
+        let dir = scratch_dir("sorted");
+        for name in ["b.rs", "a.rs", "c.txt", ".hidden.rs"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/x.o"), "").unwrap();
+        let cwd = dir.display().to_string();
+
+        assert_eq!(glob("*.rs", &cwd), vec!["a.rs", "b.rs"]);
+        assert_eq!(glob(".*.rs", &cwd), vec![".hidden.rs"]);
+        assert_eq!(glob("*/*.o", &cwd), vec!["sub/x.o"]);
+        assert_eq!(glob("*/", &cwd), vec!["sub/"]);
+        assert!(glob("*.nothing", &cwd).is_empty());
+
+        let absolute = format!("{}/*.txt", cwd);
+        assert_eq!(glob(&absolute, "/"), vec![format!("{}/c.txt", cwd)]);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
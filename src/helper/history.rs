@@ -0,0 +1,302 @@
+use crate::helper::variables::Variables;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::unix::{
+        fs::{OpenOptionsExt, PermissionsExt},
+        io::AsRawFd,
+    },
+    path::{Path, PathBuf},
+};
+
+// --- History File ---
+// One entry per line. Multi-line commands keep their newlines as `\n`
+// (and real backslashes as `\\`) so an entry never spans two lines.
+// Settings come from the same variables bash uses:
+//   HISTFILE     where to save (default ~/.0shell_history)
+//   HISTSIZE     entries kept in memory and in the file (default 1000)
+//   HISTCONTROL  ignorespace, ignoredups, erasedups or ignoreboth (both
+//                ignorespace and ignoredups), separated by `:`
+// The file is only readable by its owner, like bash's: commands can hold
+// passwords and tokens.
+
+const DEFAULT_SIZE: usize = 1000;
+const FILE_MODE: u32 = 0o600;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryConfig {
+    pub max_size: usize,
+    // Lines starting with a space are never recorded
+    pub ignore_space: bool,
+    // Nor is a line equal to the last one saved, even by another session
+    pub ignore_dups: bool,
+    // A new entry removes every older copy of itself, not just the last one
+    pub erase_dups: bool,
+}
+
+impl HistoryConfig {
+    pub fn from_vars(vars: &Variables) -> Self {
+        let max_size = vars
+            .get("HISTSIZE")
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_SIZE);
+        let control: Vec<&str> = vars.get("HISTCONTROL").unwrap_or("").split(':').collect();
+        Self {
+            max_size,
+            ignore_space: control.contains(&"ignorespace") || control.contains(&"ignoreboth"),
+            ignore_dups: control.contains(&"ignoredups") || control.contains(&"ignoreboth"),
+            erase_dups: control.contains(&"erasedups"),
+        }
+    }
+}
+
+pub fn history_path(vars: &Variables) -> Option<PathBuf> {
+    match vars.get("HISTFILE") {
+        Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+        Some(_) => None,
+        None => vars
+            .get("HOME")
+            .map(|home| Path::new(home).join(".0shell_history")),
+    }
+}
+
+fn encode(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn decode(line: &str) -> String {
+    let mut out = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// Newest `max_size` entries, without older duplicates if asked to
+fn trim(entries: Vec<String>, config: &HistoryConfig) -> Vec<String> {
+    let mut entries = entries;
+    if config.erase_dups {
+        let mut seen = std::collections::HashSet::new();
+        let mut kept: Vec<String> = entries
+            .into_iter()
+            .rev()
+            .filter(|entry| seen.insert(entry.clone()))
+            .collect();
+        kept.reverse();
+        entries = kept;
+    }
+    let excess = entries.len().saturating_sub(config.max_size);
+    entries.drain(..excess);
+    entries
+}
+
+// Every session appends to the same file, so all reads and writes happen
+// under an exclusive flock. The lock goes away when the file is closed.
+fn lock(file: &File) -> io::Result<()> {
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn read_entries(file: &mut File) -> io::Result<Vec<String>> {
+    let mut text = String::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_string(&mut text)?;
+    Ok(text.lines().map(decode).collect())
+}
+
+pub fn load(path: &Path, config: &HistoryConfig) -> io::Result<Vec<String>> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    lock(&file)?;
+    Ok(trim(read_entries(&mut file)?, config))
+}
+
+pub fn append(path: &Path, entry: &str, config: &HistoryConfig) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .mode(FILE_MODE)
+        .open(path)?;
+    lock(&file)?;
+    let mut entries = read_entries(&mut file)?;
+    if config.ignore_dups && entries.last().map(String::as_str) == Some(entry) {
+        return Ok(());
+    }
+    file.write_all(format!("{}\n", encode(entry)).as_bytes())?;
+    entries.push(entry.to_string());
+
+    // Rewrite in place (not via rename) so the other sessions' locks stay valid
+    let kept = trim(entries.clone(), config);
+    if kept.len() != entries.len() {
+        let text: String = kept.iter().map(|e| format!("{}\n", encode(e))).collect();
+        file.set_permissions(fs::Permissions::from_mode(FILE_MODE))?;
+        file.set_len(0)?;
+        file.write_all(text.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("0shell-hist-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn config(max_size: usize, erase_dups: bool) -> HistoryConfig {
+        HistoryConfig {
+            max_size,
+            ignore_space: false,
+            ignore_dups: false,
+            erase_dups,
+        }
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        for entry in ["ls", "for x\ndo echo \\n\ndone", "a\\\\b"] {
+            assert!(!encode(entry).contains('\n'));
+            assert_eq!(decode(&encode(entry)), entry);
+        }
+    }
+
+    #[test]
+    fn test_append_then_load() {
+        let path = temp_file("load");
+        let cfg = config(10, false);
+        append(&path, "echo one", &cfg).unwrap();
+        append(&path, "echo 'two\nlines'", &cfg).unwrap();
+
+        assert_eq!(
+            load(&path, &cfg).unwrap(),
+            vec!["echo one", "echo 'two\nlines'"]
+        );
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_file_is_trimmed_to_max_size() {
+        let path = temp_file("trim");
+        let cfg = config(3, false);
+        for i in 0..5 {
+            append(&path, &format!("cmd {}", i), &cfg).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "cmd 2\ncmd 3\ncmd 4\n");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_erase_dups_keeps_newest_copy() {
+        let path = temp_file("dups");
+        let cfg = config(10, true);
+        for entry in ["a", "b", "a", "c"] {
+            append(&path, entry, &cfg).unwrap();
+        }
+        assert_eq!(load(&path, &cfg).unwrap(), vec!["b", "a", "c"]);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_ignore_dups_skips_the_last_saved_entry() {
+        let path = temp_file("ignoredups");
+        let cfg = HistoryConfig {
+            ignore_dups: true,
+            ..config(10, false)
+        };
+        for entry in ["a", "a", "b", "a", "a"] {
+            append(&path, entry, &cfg).unwrap();
+        }
+        assert_eq!(load(&path, &cfg).unwrap(), vec!["a", "b", "a"]);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_file_is_private() {
+        let path = temp_file("mode");
+        append(&path, "export TOKEN=secret", &config(10, false)).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, FILE_MODE);
+
+        // Also once trimmed, even if it was readable before
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        for i in 0..3 {
+            append(&path, &format!("cmd {}", i), &config(2, false)).unwrap();
+        }
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, FILE_MODE);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_concurrent_appends_lose_nothing() {
+        let path = temp_file("concurrent");
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        append(
+                            &path,
+                            &format!("session {} cmd {}", t, i),
+                            &config(1000, false),
+                        )
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(load(&path, &config(1000, false)).unwrap().len(), 100);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_config_from_vars() {
+        let mut vars = Variables::default();
+        assert_eq!(HistoryConfig::from_vars(&vars), config(DEFAULT_SIZE, false));
+
+        vars.set("HISTSIZE", "5".to_string());
+        vars.set("HISTCONTROL", "ignoreboth:erasedups".to_string());
+        let cfg = HistoryConfig::from_vars(&vars);
+        assert_eq!(cfg.max_size, 5);
+        assert!(cfg.ignore_space && cfg.ignore_dups && cfg.erase_dups);
+
+        vars.set("HISTCONTROL", "ignoredups".to_string());
+        let cfg = HistoryConfig::from_vars(&vars);
+        assert!(cfg.ignore_dups && !cfg.ignore_space && !cfg.erase_dups);
+
+        vars.set("HOME", "/home/me".to_string());
+        assert_eq!(
+            history_path(&vars),
+            Some(PathBuf::from("/home/me/.0shell_history"))
+        );
+        vars.set("HISTFILE", String::new());
+        assert_eq!(history_path(&vars), None);
+    }
+}
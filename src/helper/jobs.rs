@@ -0,0 +1,680 @@
+use crate::helper::external::{interrupt, interrupted};
+use crate::helper::parser::{
+    AndOrList, Command, CommandList, Compound, Connector, FileMode, Pipeline, Redirect, Word,
+    WordPart,
+};
+use std::ffi::CStr;
+use std::io;
+use std::os::fd::RawFd;
+use std::thread;
+use std::time::Duration;
+
+// --- Job Control ---
+// Each foreground pipeline of programs gets a process group of its own and
+// the terminal while it runs, so Ctrl-C and Ctrl-Z reach it and not us.
+// Ctrl-Z stops it and it lands in the table; `cmd &` jobs start there.
+// The shell itself ignores SIGTSTP, SIGTTIN and SIGTTOU, and takes the
+// terminal (and its own terminal settings) back whenever a job gives it up.
+// Without a terminal (scripts, tests) there is no job control: background
+// jobs are still tracked for `wait`, `jobs` and `$!`, but `fg`/`bg` refuse.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done,
+}
+
+pub struct Job {
+    // 0 until the table gives it a number
+    pub id: usize,
+    pub pgid: i32,
+    // Each process with its raw wait status once it has ended
+    procs: Vec<(i32, Option<i32>)>,
+    pub text: String,
+    pub state: JobState,
+    // Terminal settings it had when it stopped, put back by `fg`
+    tmodes: Option<libc::termios>,
+    // Stopped or done since the user was last told
+    changed: bool,
+}
+
+impl Job {
+    pub fn new(pgid: i32, pids: Vec<i32>, text: String) -> Job {
+        Job {
+            id: 0,
+            pgid,
+            procs: pids.into_iter().map(|pid| (pid, None)).collect(),
+            text,
+            state: JobState::Running,
+            tmodes: None,
+            changed: false,
+        }
+    }
+
+    // Like a pipeline, the job's status is its last process's
+    pub fn status(&self) -> i32 {
+        match self.procs.last().and_then(|(_, raw)| *raw) {
+            Some(raw) if libc::WIFSIGNALED(raw) => 128 + libc::WTERMSIG(raw),
+            Some(raw) => libc::WEXITSTATUS(raw),
+            None => 0,
+        }
+    }
+
+    fn update(&mut self, pid: i32, raw: i32) {
+        let Some(index) = self.procs.iter().position(|(p, _)| *p == pid) else {
+            return;
+        };
+        if libc::WIFSTOPPED(raw) {
+            self.state = JobState::Stopped;
+            self.changed = true;
+        } else if libc::WIFCONTINUED(raw) {
+            self.state = JobState::Running;
+        } else {
+            self.procs[index].1 = Some(raw);
+            if self.procs.iter().all(|(_, raw)| raw.is_some()) {
+                self.state = JobState::Done;
+                self.changed = true;
+            }
+        }
+    }
+
+    fn killed_by(&self, signal: i32) -> bool {
+        self.procs.iter().any(|(_, raw)| {
+            raw.is_some_and(|raw| libc::WIFSIGNALED(raw) && libc::WTERMSIG(raw) == signal)
+        })
+    }
+
+    // `Running`, `Stopped`, `Done`, `Exit 2`, `Terminated`...
+    fn state_text(&self) -> String {
+        match (self.state, self.procs.last().and_then(|(_, raw)| *raw)) {
+            (JobState::Running, _) => "Running".to_string(),
+            (JobState::Stopped, _) => "Stopped".to_string(),
+            (JobState::Done, Some(raw)) if libc::WIFSIGNALED(raw) => {
+                let name = unsafe { libc::strsignal(libc::WTERMSIG(raw)) };
+                if name.is_null() {
+                    format!("Signal {}", libc::WTERMSIG(raw))
+                } else {
+                    unsafe { CStr::from_ptr(name) }
+                        .to_string_lossy()
+                        .into_owned()
+                }
+            }
+            (JobState::Done, Some(raw)) if libc::WEXITSTATUS(raw) != 0 => {
+                format!("Exit {}", libc::WEXITSTATUS(raw))
+            }
+            (JobState::Done, _) => "Done".to_string(),
+        }
+    }
+}
+
+// How spawn_external puts a program in a job's process group
+#[derive(Clone, Copy)]
+pub struct ProcessGroup {
+    // 0 makes the program the leader of a new group
+    pub pgid: i32,
+    pub terminal: RawFd,
+}
+
+struct Terminal {
+    // A private copy of fd 0, so redirecting a block's stdin doesn't lose it
+    fd: RawFd,
+    shell_pgid: i32,
+    tmodes: libc::termios,
+}
+
+#[derive(Default)]
+pub struct JobTable {
+    // Sorted by id
+    jobs: Vec<Job>,
+    // Ids, most recent last: the end is `%+`, the one before `%-`
+    recent: Vec<usize>,
+    // Some once enable() found a terminal
+    terminal: Option<Terminal>,
+    // `$!`
+    pub last_pid: Option<i32>,
+}
+
+impl JobTable {
+    // Interactive shells only, before anything runs
+    pub fn enable(&mut self) {
+        unsafe {
+            for signal in [libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU] {
+                libc::signal(signal, libc::SIG_IGN);
+            }
+            // Fails harmlessly when we already lead a session or group
+            libc::setpgid(0, 0);
+            let pgid = libc::getpgrp();
+            let fd = libc::fcntl(0, libc::F_DUPFD_CLOEXEC, 10);
+            if fd < 0 {
+                return;
+            }
+            let mut tmodes: libc::termios = std::mem::zeroed();
+            if libc::tcsetpgrp(fd, pgid) != 0 || libc::tcgetattr(fd, &mut tmodes) != 0 {
+                libc::close(fd);
+                return;
+            }
+            self.terminal = Some(Terminal {
+                fd,
+                shell_pgid: pgid,
+                tmodes,
+            });
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.terminal.is_some()
+    }
+
+    // For the programs of a foreground pipeline; None without job control
+    pub fn group(&self, pgid: i32) -> Option<ProcessGroup> {
+        self.terminal.as_ref().map(|terminal| ProcessGroup {
+            pgid,
+            terminal: terminal.fd,
+        })
+    }
+
+    // Gives the job a number (unless it has one) and makes it the current job
+    pub fn add(&mut self, mut job: Job) -> usize {
+        if job.id == 0 {
+            job.id = self.jobs.last().map_or(0, |last| last.id) + 1;
+        }
+        let id = job.id;
+        self.touch(id);
+        let at = self.jobs.partition_point(|other| other.id < id);
+        self.jobs.insert(at, job);
+        id
+    }
+
+    fn touch(&mut self, id: usize) {
+        self.recent.retain(|&other| other != id);
+        self.recent.push(id);
+    }
+
+    fn remove(&mut self, id: usize) -> Option<Job> {
+        let index = self.jobs.iter().position(|job| job.id == id)?;
+        self.recent.retain(|&other| other != id);
+        Some(self.jobs.remove(index))
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    // The job one of our processes belongs to, for `wait PID`
+    pub fn find_pid(&self, pid: i32) -> Option<usize> {
+        self.jobs
+            .iter()
+            .find(|job| job.procs.iter().any(|(p, _)| *p == pid))
+            .map(|job| job.id)
+    }
+
+    // `%1`, `1`, `%%`, `%+`, `%-`, `%name` (start of the command) or
+    // `%?text` (anywhere in it)
+    pub fn find(&self, spec: &str) -> Result<usize, String> {
+        let found = match spec.strip_prefix('%').unwrap_or(spec) {
+            "" | "%" | "+" => self.recent.last().copied(),
+            "-" => self.recent.iter().rev().nth(1).copied(),
+            number if number.parse::<usize>().is_ok() => {
+                number.parse().ok().filter(|id| self.get(*id).is_some())
+            }
+            text => {
+                let matches = |job: &&Job| match text.strip_prefix('?') {
+                    Some(part) => job.text.contains(part),
+                    None => job.text.starts_with(text),
+                };
+                self.jobs.iter().rev().find(matches).map(|job| job.id)
+            }
+        };
+        found.ok_or_else(|| match spec {
+            "" | "%" | "%%" | "%+" => "current: no such job".to_string(),
+            _ => format!("{}: no such job", spec),
+        })
+    }
+
+    // `[1]+  Running                 sleep 10 &`
+    pub fn line(&self, job: &Job) -> String {
+        self.format_line(job, "  ".to_string())
+    }
+
+    // `jobs -l`: the same with the process group in it
+    pub fn long_line(&self, job: &Job) -> String {
+        self.format_line(job, format!(" {:<6}", job.pgid))
+    }
+
+    fn format_line(&self, job: &Job, middle: String) -> String {
+        let mark = match self.recent.iter().rev().position(|&id| id == job.id) {
+            Some(0) => '+',
+            Some(1) => '-',
+            _ => ' ',
+        };
+        let amp = if job.state == JobState::Running {
+            " &"
+        } else {
+            ""
+        };
+        format!(
+            "[{}]{}{}{:<24}{}{}",
+            job.id,
+            mark,
+            middle,
+            job.state_text(),
+            job.text,
+            amp
+        )
+    }
+
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    // Collects whatever ended, stopped or went on again, without blocking
+    pub fn reap(&mut self) {
+        for job in &mut self.jobs {
+            for index in 0..job.procs.len() {
+                let pid = job.procs[index].0;
+                while job.procs[index].1.is_none() {
+                    let mut raw = 0;
+                    let flags = libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED;
+                    match unsafe { libc::waitpid(pid, &mut raw, flags) } {
+                        0 => break,
+                        // Someone else reaped it (it is not ours any more)
+                        -1 => job.update(pid, 0),
+                        _ => job.update(pid, raw),
+                    }
+                }
+            }
+        }
+    }
+
+    // Done jobs leave the table once reported
+    pub fn forget_done(&mut self) {
+        for job in &mut self.jobs {
+            job.changed = false;
+        }
+        let done: Vec<usize> = self
+            .jobs
+            .iter()
+            .filter(|job| job.state == JobState::Done)
+            .map(|job| job.id)
+            .collect();
+        for id in done {
+            self.remove(id);
+        }
+    }
+
+    // One line per job that stopped or ended since last time. main prints
+    // them right before drawing the prompt.
+    pub fn notify(&mut self) -> Vec<String> {
+        self.reap();
+        let lines = self
+            .jobs
+            .iter()
+            .filter(|job| job.changed)
+            .map(|job| self.line(job))
+            .collect();
+        self.forget_done();
+        lines
+    }
+
+    // Waits until every process of `job` ends or it is stopped (Ctrl-Z),
+    // then takes the terminal back. A stopped job goes in the table and
+    // the status is 128 + SIGTSTP, like in bash.
+    pub fn wait_foreground(&mut self, mut job: Job) -> i32 {
+        while job.state == JobState::Running {
+            let Some(&(pid, _)) = job.procs.iter().find(|(_, raw)| raw.is_none()) else {
+                break;
+            };
+            let mut raw = 0;
+            if unsafe { libc::waitpid(pid, &mut raw, libc::WUNTRACED) } == pid {
+                job.update(pid, raw);
+            } else if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                // Not ours to wait for any more
+                job.update(pid, 0);
+            }
+        }
+
+        if let Some(terminal) = &self.terminal {
+            unsafe {
+                if job.state == JobState::Stopped {
+                    let mut tmodes: libc::termios = std::mem::zeroed();
+                    if libc::tcgetattr(terminal.fd, &mut tmodes) == 0 {
+                        job.tmodes = Some(tmodes);
+                    }
+                }
+                libc::tcsetpgrp(terminal.fd, terminal.shell_pgid);
+                libc::tcsetattr(terminal.fd, libc::TCSADRAIN, &terminal.tmodes);
+            }
+        }
+
+        if job.state == JobState::Stopped {
+            job.changed = false;
+            let id = self.add(job);
+            if let Some(job) = self.get(id) {
+                eprintln!("\n{}", self.line(job));
+            }
+            return 128 + libc::SIGTSTP;
+        }
+        // A job killed by Ctrl-C stops the loop it ran in, as if we got it
+        if job.killed_by(libc::SIGINT) {
+            interrupt();
+            eprintln!();
+        }
+        job.status()
+    }
+
+    // `fg`: the job gets the terminal (and its settings) back and continues
+    pub fn foreground(&mut self, id: usize) -> Option<i32> {
+        let fd = self.terminal.as_ref()?.fd;
+        let mut job = self.remove(id)?;
+        unsafe {
+            if let Some(tmodes) = &job.tmodes {
+                libc::tcsetattr(fd, libc::TCSADRAIN, tmodes);
+            }
+            libc::tcsetpgrp(fd, job.pgid);
+            libc::kill(-job.pgid, libc::SIGCONT);
+        }
+        job.state = JobState::Running;
+        Some(self.wait_foreground(job))
+    }
+
+    // `bg`: a stopped job continues where it is
+    pub fn background(&mut self, id: usize) -> Result<String, String> {
+        let Some(index) = self.jobs.iter().position(|job| job.id == id) else {
+            return Err(format!("%{}: no such job", id));
+        };
+        if self.jobs[index].state == JobState::Running {
+            return Err(format!("job {} already in background", id));
+        }
+        unsafe {
+            libc::kill(-self.jobs[index].pgid, libc::SIGCONT);
+        }
+        self.jobs[index].state = JobState::Running;
+        self.touch(id);
+        Ok(format!("[{}]+ {} &", id, self.jobs[index].text))
+    }
+
+    // Blocks until the job has ended (or stopped) and takes it off the
+    // table. None when Ctrl-C cut the wait short.
+    pub fn wait_for(&mut self, id: usize) -> Option<i32> {
+        loop {
+            self.reap();
+            let job = self.get(id)?;
+            match job.state {
+                JobState::Done => return self.remove(id).map(|job| job.status()),
+                JobState::Stopped => return Some(128 + libc::SIGTSTP),
+                JobState::Running => {}
+            }
+            if interrupted() {
+                return None;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    // `disown`: no longer ours to report, wait for or continue
+    pub fn disown(&mut self, id: usize) {
+        self.remove(id);
+    }
+}
+
+// --- Job Text ---
+// What `jobs` and `fg` show. The parser keeps no source text, so the
+// command is written back out from the tree, close to how it was typed.
+pub fn and_or_text(item: &AndOrList) -> String {
+    let mut text = pipeline_text(&item.first);
+    for (connector, pipeline) in &item.rest {
+        text.push_str(match connector {
+            Connector::And => " && ",
+            Connector::Or => " || ",
+        });
+        text.push_str(&pipeline_text(pipeline));
+    }
+    text
+}
+
+pub fn pipeline_text(pipeline: &Pipeline) -> String {
+    let commands: Vec<String> = pipeline.commands.iter().map(command_text).collect();
+    commands.join(" | ")
+}
+
+fn list_text(list: &CommandList) -> String {
+    let mut text = String::new();
+    for item in &list.items {
+        text.push_str(&and_or_text(item));
+        text.push_str(if item.background { " & " } else { "; " });
+    }
+    text
+}
+
+fn command_text(command: &Command) -> String {
+    let (mut text, redirects) = match command {
+        Command::Simple(command) => {
+            let words: Vec<String> = command.words.iter().map(word_text).collect();
+            (words.join(" "), &command.redirects[..])
+        }
+        Command::Compound(compound, redirects) => (compound_text(compound), &redirects[..]),
+        Command::Function(function) => (
+            format!("{}() {}", function.name, compound_text(&function.body)),
+            &function.redirects[..],
+        ),
+    };
+    for redirect in redirects {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(&redirect_text(redirect));
+    }
+    text
+}
+
+fn compound_text(compound: &Compound) -> String {
+    match compound {
+        Compound::Group(body) => format!("{{ {}}}", list_text(body)),
+        Compound::If {
+            branches,
+            otherwise,
+        } => {
+            let mut text = String::new();
+            for (i, (condition, body)) in branches.iter().enumerate() {
+                let keyword = if i == 0 { "if" } else { "elif" };
+                text.push_str(&format!(
+                    "{} {}then {}",
+                    keyword,
+                    list_text(condition),
+                    list_text(body)
+                ));
+            }
+            if let Some(body) = otherwise {
+                text.push_str(&format!("else {}", list_text(body)));
+            }
+            text + "fi"
+        }
+        Compound::While {
+            condition,
+            body,
+            until,
+        } => format!(
+            "{} {}do {}done",
+            if *until { "until" } else { "while" },
+            list_text(condition),
+            list_text(body)
+        ),
+        Compound::For { name, words, body } => match words {
+            Some(words) => {
+                let words: Vec<String> = words.iter().map(word_text).collect();
+                format!(
+                    "for {} in {}; do {}done",
+                    name,
+                    words.join(" "),
+                    list_text(body)
+                )
+            }
+            None => format!("for {}; do {}done", name, list_text(body)),
+        },
+        Compound::Case { word, arms } => {
+            let mut text = format!("case {} in ", word_text(word));
+            for arm in arms {
+                let patterns: Vec<String> = arm.patterns.iter().map(word_text).collect();
+                text.push_str(&format!(
+                    "{}) {};; ",
+                    patterns.join(" | "),
+                    list_text(&arm.body).trim_end_matches("; ")
+                ));
+            }
+            text + "esac"
+        }
+        Compound::Arith(expr) => format!("(({}))", word_text(expr)),
+    }
+}
+
+fn word_text(word: &Word) -> String {
+    let mut text = String::new();
+    for part in &word.0 {
+        match part {
+            WordPart::Literal(literal) => text.push_str(literal),
+            WordPart::Quoted(quoted) => {
+                let plain = !quoted.is_empty()
+                    && quoted
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || "-_./:=,+@%".contains(c));
+                if plain {
+                    text.push_str(quoted);
+                } else {
+                    text.push_str(&format!("'{}'", quoted.replace('\'', "'\\''")));
+                }
+            }
+            WordPart::Param {
+                name,
+                default: Some(default),
+                ..
+            } => text.push_str(&format!("${{{}:-{}}}", name, word_text(default))),
+            WordPart::Param { name, .. } => text.push_str(&format!("${}", name)),
+            WordPart::Command { list, .. } => {
+                text.push_str(&format!("$({})", list_text(list).trim_end_matches("; ")))
+            }
+            WordPart::Arith { expr, .. } => text.push_str(&format!("$(({}))", word_text(expr))),
+        }
+    }
+    text
+}
+
+fn redirect_text(redirect: &Redirect) -> String {
+    match redirect {
+        Redirect::File { fd, target, mode } => {
+            let (default_fd, op) = match mode {
+                FileMode::Read => (0, "<"),
+                FileMode::Write => (1, ">"),
+                FileMode::Append => (1, ">>"),
+            };
+            let fd = if *fd == default_fd {
+                String::new()
+            } else {
+                fd.to_string()
+            };
+            format!("{}{} {}", fd, op, word_text(target))
+        }
+        Redirect::Dup { fd, target } => format!("{}>&{}", fd, target),
+        // The body can be many lines; it is not worth showing
+        Redirect::Here { fd: 0, .. } => "<<EOF".to_string(),
+        Redirect::Here { fd, .. } => format!("{}<<EOF", fd),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::parser::{ParseResult, parse_input};
+
+    fn text(input: &str) -> String {
+        match parse_input(input) {
+            ParseResult::Ok(list) => list_text(&list).trim_end_matches("; ").to_string(),
+            other => panic!("Expected a command list, got {:?}", other),
+        }
+    }
+
+    fn table(texts: &[&str]) -> JobTable {
+        let mut table = JobTable::default();
+        for (n, text) in texts.iter().enumerate() {
+            table.add(Job::new(n as i32 + 1, vec![n as i32 + 1], text.to_string()));
+        }
+        table
+    }
+
+    #[test]
+    fn test_job_text_reads_like_the_input() {
+        assert_eq!(
+            text("sleep 10 | wc -l > out 2>&1"),
+            "sleep 10 | wc -l > out 2>&1"
+        );
+        assert_eq!(
+            text("echo \"a b\" $X $(pwd) && false"),
+            "echo 'a b' $X $(pwd) && false"
+        );
+        assert_eq!(
+            text("while true; do sleep 1; done"),
+            "while true; do sleep 1; done"
+        );
+        assert_eq!(
+            text("for f in *.rs; do wc $f; done"),
+            "for f in *.rs; do wc $f; done"
+        );
+        assert_eq!(text("{ a & b; }"), "{ a & b; }");
+        assert_eq!(text("((x++))"), "((x++))");
+    }
+
+    #[test]
+    fn test_job_specs() {
+        let mut table = table(&["sleep 10", "vim notes", "sleep 20"]);
+        assert_eq!(table.find("%2"), Ok(2));
+        assert_eq!(table.find("2"), Ok(2));
+        assert_eq!(table.find("%%"), Ok(3));
+        assert_eq!(table.find(""), Ok(3));
+        assert_eq!(table.find("%-"), Ok(2));
+        assert_eq!(table.find("%vim"), Ok(2));
+        assert_eq!(table.find("%?10"), Ok(1));
+        assert_eq!(table.find("%sleep"), Ok(3));
+        assert_eq!(table.find("%4"), Err("%4: no such job".to_string()));
+
+        // Numbers go on from the highest one, and the mark follows use
+        table.disown(3);
+        assert_eq!(table.find("%+"), Ok(2));
+        let id = table.add(Job::new(9, vec![9], "make".to_string()));
+        assert_eq!(id, 3);
+        assert!(
+            table
+                .line(table.get(3).unwrap())
+                .starts_with("[3]+  Running ")
+        );
+        assert!(
+            table
+                .line(table.get(2).unwrap())
+                .starts_with("[2]-  Running ")
+        );
+        assert!(table.line(table.get(1).unwrap()).ends_with("sleep 10 &"));
+        table.disown(1);
+        table.disown(2);
+        table.disown(3);
+        assert_eq!(table.find("%%"), Err("current: no such job".to_string()));
+    }
+
+    #[test]
+    fn test_job_state_follows_wait_statuses() {
+        let mut job = Job::new(10, vec![10, 11], "a | b".to_string());
+        job.update(10, 0x7f | (libc::SIGTSTP << 8));
+        assert_eq!(job.state, JobState::Stopped);
+        job.update(10, 0xffff);
+        assert_eq!(job.state, JobState::Running);
+        job.update(10, 0);
+        assert_eq!(job.state, JobState::Running);
+        job.update(11, 3 << 8);
+        assert_eq!(job.state, JobState::Done);
+        assert_eq!(job.status(), 3);
+        assert_eq!(job.state_text(), "Exit 3");
+
+        let mut killed = Job::new(12, vec![12], "sleep 9".to_string());
+        killed.update(12, libc::SIGINT);
+        assert_eq!(killed.status(), 130);
+        assert!(killed.killed_by(libc::SIGINT));
+    }
+}
@@ -0,0 +1,386 @@
+use crate::helper::state_manager::ShellState;
+use crate::helper::unicode::{next_boundary, prev_boundary};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// --- Line Editor ---
+// Emacs-style editing on the line being typed. `view_buffer` is the current
+// line and `buffer` is every line of the command so far, so each edit
+// rewrites the view and swaps it back in at the end of `buffer`.
+
+// Killed text, newest last. Kills in a row grow the same entry like in emacs
+#[derive(Debug, Clone, Default)]
+pub struct KillRing {
+    entries: Vec<String>,
+    chaining: bool,
+}
+
+const KILL_RING_SIZE: usize = 16;
+
+impl KillRing {
+    fn kill(&mut self, text: String, backward: bool) {
+        if text.is_empty() {
+            return;
+        }
+        match self.entries.last_mut() {
+            Some(last) if self.chaining && backward => last.insert_str(0, &text),
+            Some(last) if self.chaining => last.push_str(&text),
+            _ => {
+                self.entries.push(text);
+                if self.entries.len() > KILL_RING_SIZE {
+                    self.entries.remove(0);
+                }
+            }
+        }
+        self.chaining = true;
+    }
+
+    // A copy that is not a kill (vi `y`): always a new entry
+    pub fn store(&mut self, text: String) {
+        self.chaining = false;
+        self.kill(text, false);
+        self.chaining = false;
+    }
+
+    pub fn yank_text(&self) -> Option<&str> {
+        self.entries.last().map(String::as_str)
+    }
+}
+
+fn chars(shell: &ShellState) -> Vec<char> {
+    shell.view_buffer.chars().collect()
+}
+
+pub fn set_line(shell: &mut ShellState, line: Vec<char>, cursor: usize) {
+    let base_len = shell.buffer.len() - shell.view_buffer.len();
+    shell.view_buffer = line.into_iter().collect();
+    shell.buffer.truncate(base_len);
+    shell.buffer.push_str(&shell.view_buffer);
+    shell.cursor_idx = cursor;
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Start of the word left of `from` (Alt-B, Alt-Backspace)
+fn word_start(line: &[char], from: usize) -> usize {
+    let mut i = from;
+    while i > 0 && !is_word_char(line[i - 1]) {
+        i -= 1;
+    }
+    while i > 0 && is_word_char(line[i - 1]) {
+        i -= 1;
+    }
+    i
+}
+
+// End of the word right of `from` (Alt-F, Alt-D)
+fn word_end(line: &[char], from: usize) -> usize {
+    let mut i = from;
+    while i < line.len() && !is_word_char(line[i]) {
+        i += 1;
+    }
+    while i < line.len() && is_word_char(line[i]) {
+        i += 1;
+    }
+    i
+}
+
+// Ctrl-W uses whitespace as the only separator, like bash's unix-word-rubout
+fn big_word_start(line: &[char], from: usize) -> usize {
+    let mut i = from;
+    while i > 0 && line[i - 1].is_whitespace() {
+        i -= 1;
+    }
+    while i > 0 && !line[i - 1].is_whitespace() {
+        i -= 1;
+    }
+    i
+}
+
+pub fn insert_str(shell: &mut ShellState, text: &str) {
+    let mut line = chars(shell);
+    let cursor = shell.cursor_idx.min(line.len());
+    let inserted: Vec<char> = text.chars().collect();
+    let count = inserted.len();
+    line.splice(cursor..cursor, inserted);
+    set_line(shell, line, cursor + count);
+}
+
+fn kill_range(shell: &mut ShellState, start: usize, end: usize, backward: bool) {
+    let mut line = chars(shell);
+    let killed: String = line.drain(start..end).collect();
+    set_line(shell, line, start);
+    shell.kill_ring.kill(killed, backward);
+}
+
+pub fn history_prev(shell: &mut ShellState) {
+    if shell.hist_idx > 0 {
+        shell.hist_idx -= 1;
+        shell.buffer = shell.history[shell.hist_idx].clone();
+        shell.view_buffer = shell.history[shell.hist_idx].clone();
+        shell.cursor_idx = shell.view_buffer.chars().count();
+    }
+}
+
+pub fn history_next(shell: &mut ShellState) {
+    if shell.hist_idx < shell.history.len() {
+        shell.hist_idx += 1;
+        if shell.hist_idx < shell.history.len() {
+            shell.buffer = shell.history[shell.hist_idx].clone();
+            shell.view_buffer = shell.history[shell.hist_idx].clone();
+            shell.cursor_idx = shell.view_buffer.chars().count();
+        } else {
+            shell.reset_buffers();
+        }
+    }
+}
+
+// Applies an editing key. Returns false for keys that are not about
+// editing (Enter, Tab, Ctrl-C...), which main handles itself.
+pub fn handle_key(shell: &mut ShellState, key: KeyEvent) -> bool {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    let line = chars(shell);
+    let len = line.len();
+    let cursor = shell.cursor_idx.min(len);
+    // One grapheme cluster left and right of the cursor
+    let prev = prev_boundary(&shell.view_buffer, cursor);
+    let next = next_boundary(&shell.view_buffer, cursor);
+    let chaining = shell.kill_ring.chaining;
+    shell.kill_ring.chaining = false;
+
+    match key.code {
+        // --- Kills (they keep the chain going) ---
+        KeyCode::Char('k') if ctrl => {
+            shell.kill_ring.chaining = chaining;
+            kill_range(shell, cursor, len, false);
+        }
+        KeyCode::Char('u') if ctrl => {
+            shell.kill_ring.chaining = chaining;
+            kill_range(shell, 0, cursor, true);
+        }
+        KeyCode::Char('w') if ctrl => {
+            shell.kill_ring.chaining = chaining;
+            kill_range(shell, big_word_start(&line, cursor), cursor, true);
+        }
+        KeyCode::Backspace if alt => {
+            shell.kill_ring.chaining = chaining;
+            kill_range(shell, word_start(&line, cursor), cursor, true);
+        }
+        KeyCode::Char('d') if alt => {
+            shell.kill_ring.chaining = chaining;
+            kill_range(shell, cursor, word_end(&line, cursor), false);
+        }
+        KeyCode::Char('y') if ctrl => {
+            if let Some(text) = shell.kill_ring.yank_text().map(str::to_string) {
+                insert_str(shell, &text);
+            }
+        }
+
+        // --- Motion ---
+        KeyCode::Char('a') if ctrl => shell.cursor_idx = 0,
+        KeyCode::Home => shell.cursor_idx = 0,
+        KeyCode::Char('e') if ctrl => shell.cursor_idx = len,
+        KeyCode::End => shell.cursor_idx = len,
+        KeyCode::Char('b') if alt => shell.cursor_idx = word_start(&line, cursor),
+        KeyCode::Char('f') if alt => shell.cursor_idx = word_end(&line, cursor),
+        KeyCode::Char('b') if ctrl => shell.cursor_idx = prev,
+        KeyCode::Left => shell.cursor_idx = prev,
+        KeyCode::Char('f') if ctrl => shell.cursor_idx = next,
+        KeyCode::Right => shell.cursor_idx = next,
+        KeyCode::Char('p') if ctrl => history_prev(shell),
+        KeyCode::Up => history_prev(shell),
+        KeyCode::Char('n') if ctrl => history_next(shell),
+        KeyCode::Down => history_next(shell),
+
+        // --- Simple edits ---
+        KeyCode::Char('t') if ctrl => {
+            // At the end of the line the last two chars swap, like emacs.
+            // "Chars" are whole clusters, which may differ in length.
+            let at = if cursor == len { prev } else { cursor };
+            let start = prev_boundary(&shell.view_buffer, at);
+            let end = next_boundary(&shell.view_buffer, at);
+            if start < at && at < end {
+                let mut line = line;
+                line[start..end].rotate_left(at - start);
+                set_line(shell, line, end);
+            }
+        }
+        KeyCode::Backspace if cursor > 0 => {
+            let mut line = line;
+            line.drain(prev..cursor);
+            set_line(shell, line, prev);
+        }
+        KeyCode::Backspace => {}
+        KeyCode::Delete if cursor < len => {
+            let mut line = line;
+            line.drain(cursor..next);
+            set_line(shell, line, cursor);
+        }
+        KeyCode::Delete => {}
+        KeyCode::Char(c) if !ctrl && !alt => insert_str(shell, &c.to_string()),
+
+        _ => {
+            shell.kill_ring.chaining = chaining;
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell_with(line: &str, cursor: usize) -> ShellState {
+        let mut shell = ShellState::new();
+        shell.buffer = line.to_string();
+        shell.view_buffer = line.to_string();
+        shell.cursor_idx = cursor;
+        shell
+    }
+
+    fn key(shell: &mut ShellState, code: KeyCode, modifiers: KeyModifiers) {
+        assert!(handle_key(shell, KeyEvent::new(code, modifiers)));
+    }
+
+    fn ctrl(shell: &mut ShellState, c: char) {
+        key(shell, KeyCode::Char(c), KeyModifiers::CONTROL);
+    }
+
+    fn alt(shell: &mut ShellState, code: KeyCode) {
+        key(shell, code, KeyModifiers::ALT);
+    }
+
+    #[test]
+    fn test_insert_and_delete_keep_buffers_in_sync() {
+        // Second line of a continued command: buffer holds the first one too
+        let mut shell = ShellState::new();
+        shell.buffer = "echo 'a\nbé".to_string();
+        shell.view_buffer = "bé".to_string();
+        shell.cursor_idx = 1;
+
+        key(&mut shell, KeyCode::Char('x'), KeyModifiers::NONE);
+        assert_eq!(shell.view_buffer, "bxé");
+        key(&mut shell, KeyCode::Delete, KeyModifiers::NONE);
+        assert_eq!(shell.view_buffer, "bx");
+        key(&mut shell, KeyCode::Backspace, KeyModifiers::NONE);
+        assert_eq!(shell.buffer, "echo 'a\nb");
+        assert_eq!(shell.cursor_idx, 1);
+    }
+
+    #[test]
+    fn test_home_end_and_word_motion() {
+        let mut shell = shell_with("git commit -m msg", 17);
+        ctrl(&mut shell, 'a');
+        assert_eq!(shell.cursor_idx, 0);
+        key(&mut shell, KeyCode::End, KeyModifiers::NONE);
+        assert_eq!(shell.cursor_idx, 17);
+
+        alt(&mut shell, KeyCode::Char('b'));
+        assert_eq!(shell.cursor_idx, 14);
+        alt(&mut shell, KeyCode::Char('b'));
+        assert_eq!(shell.cursor_idx, 12);
+        alt(&mut shell, KeyCode::Char('f'));
+        assert_eq!(shell.cursor_idx, 13);
+    }
+
+    #[test]
+    fn test_kill_and_yank() {
+        let mut shell = shell_with("echo hello world", 10);
+        ctrl(&mut shell, 'k');
+        assert_eq!(shell.view_buffer, "echo hello");
+        ctrl(&mut shell, 'a');
+        ctrl(&mut shell, 'y');
+        assert_eq!(shell.view_buffer, " worldecho hello");
+
+        let mut shell = shell_with("echo hello world", 10);
+        ctrl(&mut shell, 'u');
+        assert_eq!(shell.view_buffer, " world");
+        assert_eq!(shell.cursor_idx, 0);
+    }
+
+    #[test]
+    fn test_consecutive_kills_join() {
+        let mut shell = shell_with("cat /tmp/a.txt file", 14);
+        ctrl(&mut shell, 'w');
+        ctrl(&mut shell, 'w');
+        assert_eq!(shell.view_buffer, " file");
+        ctrl(&mut shell, 'y');
+        assert_eq!(shell.view_buffer, "cat /tmp/a.txt file");
+
+        // A motion in between starts a new entry
+        let mut shell = shell_with("one two", 7);
+        alt(&mut shell, KeyCode::Backspace);
+        key(&mut shell, KeyCode::Left, KeyModifiers::NONE);
+        alt(&mut shell, KeyCode::Backspace);
+        assert_eq!(shell.kill_ring.yank_text(), Some("one"));
+    }
+
+    #[test]
+    fn test_alt_backspace_stops_at_punctuation() {
+        let mut shell = shell_with("cd /usr/local", 13);
+        alt(&mut shell, KeyCode::Backspace);
+        assert_eq!(shell.view_buffer, "cd /usr/");
+    }
+
+    #[test]
+    fn test_transpose() {
+        let mut shell = shell_with("sl", 2);
+        ctrl(&mut shell, 't');
+        assert_eq!(shell.view_buffer, "ls");
+
+        let mut shell = shell_with("abc", 1);
+        ctrl(&mut shell, 't');
+        assert_eq!(shell.view_buffer, "bac");
+        assert_eq!(shell.cursor_idx, 2);
+    }
+
+    #[test]
+    fn test_history_keys() {
+        let mut shell = shell_with("", 0);
+        shell.history = vec!["first".to_string(), "second".to_string()];
+        shell.hist_idx = 2;
+        key(&mut shell, KeyCode::Up, KeyModifiers::NONE);
+        ctrl(&mut shell, 'p');
+        assert_eq!(shell.buffer, "first");
+        key(&mut shell, KeyCode::Down, KeyModifiers::NONE);
+        ctrl(&mut shell, 'n');
+        assert_eq!(shell.buffer, "");
+    }
+
+    #[test]
+    fn test_other_keys_are_left_to_main() {
+        let mut shell = shell_with("ls", 2);
+        assert!(!handle_key(
+            &mut shell,
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)
+        ));
+        assert!(!handle_key(
+            &mut shell,
+            KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)
+        ));
+    }
+
+    #[test]
+    fn test_edits_move_by_grapheme_cluster() {
+        // "e" + combining acute is one cluster of two chars
+        let mut shell = shell_with("cafe\u{301}s", 6);
+        key(&mut shell, KeyCode::Left, KeyModifiers::NONE);
+        assert_eq!(shell.cursor_idx, 5);
+        key(&mut shell, KeyCode::Left, KeyModifiers::NONE);
+        assert_eq!(shell.cursor_idx, 3);
+        key(&mut shell, KeyCode::Delete, KeyModifiers::NONE);
+        assert_eq!(shell.view_buffer, "cafs");
+
+        let mut shell = shell_with("ok👍🏽", 4);
+        key(&mut shell, KeyCode::Backspace, KeyModifiers::NONE);
+        assert_eq!(shell.view_buffer, "ok");
+
+        let mut shell = shell_with("ae\u{301}", 3);
+        ctrl(&mut shell, 't');
+        assert_eq!(shell.view_buffer, "e\u{301}a");
+        assert_eq!(shell.cursor_idx, 3);
+    }
+}
@@ -1,5 +1,22 @@
+pub mod arithmetic;
+pub mod brace;
+pub mod completion;
 pub mod executor;
+pub mod expand;
+pub mod external;
+pub mod git;
+pub mod glob;
+pub mod history;
+pub mod jobs;
+pub mod line_editor;
 pub mod parser;
 pub mod print_banner;
+pub mod prompt;
+pub mod script;
+pub mod search;
 pub mod state_manager;
+pub mod streams;
 pub mod ui;
+pub mod unicode;
+pub mod variables;
+pub mod vi_mode;
@@ -1,10 +1,234 @@
+use crate::helper::variables::is_valid_name;
+use std::collections::{BTreeMap, VecDeque};
+use std::rc::Rc;
+
 #[derive(Debug, PartialEq)]
 pub enum ParseResult {
-    Ok(CommandEnum),
+    Ok(CommandList),
     Incomplete,
     Err(String),
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub enum Token {
+    Word(Word),
+    Pipe,
+    And,
+    Or,
+    Semi,
+    DoubleSemi,
+    LParen,
+    RParen,
+    Newline,
+    // A lone `&`: run what comes before it in the background
+    Amp,
+    Redirect(RedirectOp),
+    // `(( expr ))`, the expression still to be expanded
+    Arith(Word),
+}
+
+// A word keeps track of what was quoted so expansion can happen later,
+// at execution time, with the shell state of that moment (`false; echo $?`)
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Word(pub Vec<WordPart>);
+
+// `quoted` params are never split into several words after expansion.
+// `default` is the word after `:-` in `${NAME:-default}`
+#[derive(Debug, PartialEq, Clone)]
+pub enum WordPart {
+    Literal(String),
+    Quoted(String),
+    Param {
+        name: String,
+        quoted: bool,
+        default: Option<Word>,
+    },
+    // `$(list)` or `` `list` ``, replaced by what the list prints
+    Command {
+        list: CommandList,
+        quoted: bool,
+    },
+    // `$(( expr ))`, replaced by its value
+    Arith {
+        expr: Word,
+        quoted: bool,
+    },
+}
+
+impl Word {
+    fn push_literal(&mut self, c: char) {
+        match self.0.last_mut() {
+            Some(WordPart::Literal(text)) => text.push(c),
+            _ => self.0.push(WordPart::Literal(c.to_string())),
+        }
+    }
+
+    fn push_quoted(&mut self, c: char) {
+        match self.0.last_mut() {
+            Some(WordPart::Quoted(text)) => text.push(c),
+            _ => self.0.push(WordPart::Quoted(c.to_string())),
+        }
+    }
+
+    // The plain text of a word that needs no expansion
+    pub fn literal_text(&self) -> Option<String> {
+        let mut text = String::new();
+        for part in &self.0 {
+            match part {
+                WordPart::Literal(s) | WordPart::Quoted(s) => text.push_str(s),
+                _ => return None,
+            }
+        }
+        Some(text)
+    }
+
+    // `NAME=value` -> (NAME, value); the `=` must be unquoted
+    pub fn assignment(&self) -> Option<(String, Word)> {
+        let (first, rest) = self.0.split_first()?;
+        let WordPart::Literal(text) = first else {
+            return None;
+        };
+        let (name, value) = text.split_once('=')?;
+        if !is_valid_name(name) {
+            return None;
+        }
+        let mut parts = Vec::new();
+        if !value.is_empty() {
+            parts.push(WordPart::Literal(value.to_string()));
+        }
+        parts.extend(rest.iter().cloned());
+        Some((name.to_string(), Word(parts)))
+    }
+}
+
+// Operators as written; the number is the fd on the left (`2>` -> Write(2))
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RedirectOp {
+    Read(u8),
+    Write(u8),
+    Append(u8),
+    Dup(u8, u8),
+    WriteBoth,
+    AppendBoth,
+    // `<<EOF` / `<<-EOF`; the tokenizer puts the body right after as a word
+    Heredoc(u8),
+    // `<<<word`
+    HereString(u8),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FileMode {
+    Read,
+    Write,
+    Append,
+}
+
+// Applied left to right, so `> f 2>&1` and `2>&1 > f` differ like in bash
+#[derive(Debug, PartialEq, Clone)]
+pub enum Redirect {
+    File {
+        fd: u8,
+        target: Word,
+        mode: FileMode,
+    },
+    Dup {
+        fd: u8,
+        target: u8,
+    },
+    // A here-document or here-string: `body` is expanded and fed to `fd`
+    Here {
+        fd: u8,
+        body: Word,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SimpleCommand {
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+// One stage of a pipeline. `for ...; done > log` keeps its redirects
+// next to the block they apply to.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command {
+    Simple(SimpleCommand),
+    Compound(Compound, Vec<Redirect>),
+    Function(Rc<Function>),
+}
+
+// `name() { ...; }` or `function name { ...; }`. Running the definition
+// only stores it; the body can be any block, redirects included.
+#[derive(Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub body: Compound,
+    pub redirects: Vec<Redirect>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Compound {
+    // `{ a; b; }`
+    Group(CommandList),
+    // `if a; then b; elif c; then d; else e; fi` -> [(a, b), (c, d)] and e
+    If {
+        branches: Vec<(CommandList, CommandList)>,
+        otherwise: Option<CommandList>,
+    },
+    // `until` is a `while` with the test the other way around
+    While {
+        condition: CommandList,
+        body: CommandList,
+        until: bool,
+    },
+    // `words` is None for a bare `for x; do`
+    For {
+        name: String,
+        words: Option<Vec<Word>>,
+        body: CommandList,
+    },
+    Case {
+        word: Word,
+        arms: Vec<CaseArm>,
+    },
+    // `(( expr ))`: succeeds when the value is not 0
+    Arith(Word),
+}
+
+// `a | b*) body ;;`
+#[derive(Debug, PartialEq, Clone)]
+pub struct CaseArm {
+    pub patterns: Vec<Word>,
+    pub body: CommandList,
+}
+
+// `a | b | c` -> one Command per stage, left to right
+#[derive(Debug, PartialEq, Clone)]
+pub struct Pipeline {
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Connector {
+    And,
+    Or,
+}
+
+// `a && b || c`: each pipeline runs depending on the status of the previous one.
+// `background` is set by a `&` after it: the whole list runs as one job.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AndOrList {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+    pub background: bool,
+}
+
+// Everything between `;`, `&` or newlines
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CommandList {
+    pub items: Vec<AndOrList>,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum CommandEnum {
     Rm(Vec<String>),
@@ -14,20 +238,379 @@ pub enum CommandEnum {
     Cd(Vec<String>, Vec<String>),
     Echo(Vec<String>),
     Mkdir(Vec<String>, Vec<String>),
-    Exit,
+    Exit(Vec<String>),
+    Export(Vec<String>),
+    Unset(Vec<String>),
+    Set(Vec<String>),
+    Source(Vec<String>),
+    Local(Vec<String>),
+    Return(Vec<String>),
+    Alias(Vec<String>),
+    Unalias(Vec<String>),
+    Jobs(Vec<String>),
+    Fg(Vec<String>),
+    Bg(Vec<String>),
+    Wait(Vec<String>),
+    Disown(Vec<String>),
     Unknown(String),
+    External(String, Vec<String>),
     Cat(Vec<String>),
     Ls(Vec<String>),
     Clear,
 }
 
-pub fn parse_tokens(input: &str) -> Result<Vec<String>, String> {
-    let mut args = Vec::new();
-    let mut current_arg = String::new();
+fn flush_word(tokens: &mut Vec<Token>, word: &mut Word, quoted: &mut bool) {
+    // '' is still a (empty) word, an unquoted nothing is not
+    if *quoted || !word.0.is_empty() {
+        tokens.push(Token::Word(std::mem::take(word)));
+    }
+    *quoted = false;
+}
+
+// --- Parameters ---
+// Called right after a `$`. None means the `$` was just a character.
+fn read_param(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    quoted: bool,
+) -> Result<Option<WordPart>, String> {
+    let param = |name: String, default| WordPart::Param {
+        name,
+        quoted,
+        default,
+    };
+
+    // `$?`, `$#`, `$@`, `$*`, `$!` and `$1`..`$9` (`${10}` for more)
+    if let Some(c) =
+        chars.next_if(|c| matches!(c, '?' | '#' | '@' | '*' | '!') || c.is_ascii_digit())
+    {
+        return Ok(Some(param(c.to_string(), None)));
+    }
+    if chars.next_if_eq(&'(').is_some() {
+        if chars.next_if_eq(&'(').is_some() {
+            let text = read_arithmetic(chars)?;
+            let expr = expandable_text(&text)?;
+            return Ok(Some(WordPart::Arith { expr, quoted }));
+        }
+        let body = read_substitution(chars)?;
+        return Ok(Some(substitution(&body, ')', quoted)?));
+    }
+    if chars.next_if_eq(&'{').is_none() {
+        let mut name = String::new();
+        if let Some(c) = chars.next_if(|c| *c == '_' || c.is_ascii_alphabetic()) {
+            name.push(c);
+            while let Some(c) = chars.next_if(|c| *c == '_' || c.is_ascii_alphanumeric()) {
+                name.push(c);
+            }
+        }
+        return Ok(if name.is_empty() {
+            None
+        } else {
+            Some(param(name, None))
+        });
+    }
+
+    // `${...}`: find the matching brace, skipping quoted ones
+    let mut body = String::new();
+    let mut depth = 0;
+    let mut in_single = false;
+    let mut in_double = false;
+    loop {
+        let Some(c) = chars.next() else {
+            return Err("Incomplete".to_string());
+        };
+        match c {
+            '\\' if !in_single => {
+                body.push(c);
+                match chars.next() {
+                    Some(next) => body.push(next),
+                    None => return Err("Incomplete".to_string()),
+                }
+                continue;
+            }
+            '\'' if !in_double && !quoted => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            '{' if !in_single && !in_double && body.ends_with('$') => depth += 1,
+            '}' if !in_single && !in_double => {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+        body.push(c);
+    }
+
+    let (name, default) = match body.split_once(":-") {
+        Some((name, default)) => (name, Some(parse_default(default, quoted)?)),
+        None => (body.as_str(), None),
+    };
+    let special = matches!(name, "?" | "#" | "@" | "*")
+        || (!name.is_empty() && name.chars().all(|c| c.is_ascii_digit()));
+    if !special && !is_valid_name(name) {
+        return Err(format!("${{{}}}: bad substitution", body));
+    }
+    Ok(Some(param(name.to_string(), default)))
+}
+
+// --- Command Substitution ---
+// Called right after `$(`; returns the text up to the matching `)`.
+// Quotes are followed so `$(echo ")")` ends in the right place, and a
+// nested `$(` is read as a whole so its own quotes don't confuse ours.
+fn read_substitution(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, String> {
+    let mut body = String::new();
+    let mut depth = 0;
+    let mut in_single = false;
+    let mut in_double = false;
+    loop {
+        let Some(c) = chars.next() else {
+            return Err("Incomplete".to_string());
+        };
+        match c {
+            '\\' if !in_single => {
+                body.push(c);
+                match chars.next() {
+                    Some(next) => body.push(next),
+                    None => return Err("Incomplete".to_string()),
+                }
+                continue;
+            }
+            '$' if !in_single && chars.next_if_eq(&'(').is_some() => {
+                let inner = read_substitution(chars)?;
+                body.push_str("$(");
+                body.push_str(&inner);
+                body.push(')');
+                continue;
+            }
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            '(' if !in_single && !in_double => depth += 1,
+            ')' if !in_single && !in_double => {
+                if depth == 0 {
+                    return Ok(body);
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+        body.push(c);
+    }
+}
+
+// --- Arithmetic ---
+// Called right after `$((` or `((`; returns the text up to the matching `))`.
+// The expression itself is only parsed when it runs (see arithmetic.rs).
+fn read_arithmetic(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, String> {
+    let mut text = String::new();
+    let mut depth = 0;
+    loop {
+        let Some(c) = chars.next() else {
+            return Err("Incomplete".to_string());
+        };
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            ')' => {
+                if chars.next_if_eq(&')').is_some() {
+                    return Ok(text);
+                }
+                if chars.peek().is_none() {
+                    return Err("Incomplete".to_string());
+                }
+                return Err("syntax error near unexpected token `)'".to_string());
+            }
+            _ => {}
+        }
+        text.push(c);
+    }
+}
+
+// Called right after an opening backquote. Inside, `\$`, `` \` `` and `\\`
+// stand for the plain character.
+fn read_backquoted(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, String> {
+    let mut body = String::new();
+    loop {
+        match chars.next() {
+            None => return Err("Incomplete".to_string()),
+            Some('`') => return Ok(body),
+            Some('\\') => match chars.next_if(|next| matches!(next, '$' | '`' | '\\')) {
+                Some(next) => body.push(next),
+                None => body.push('\\'),
+            },
+            Some(c) => body.push(c),
+        }
+    }
+}
+
+// The body is parsed right away. It is already closed here, so a body
+// that wants more (`$(ls |)`) is a syntax error rather than Incomplete.
+fn substitution(body: &str, close: char, quoted: bool) -> Result<WordPart, String> {
+    match parse_input(body) {
+        ParseResult::Ok(list) => Ok(WordPart::Command { list, quoted }),
+        ParseResult::Incomplete => Err(format!("syntax error near unexpected token `{}'", close)),
+        ParseResult::Err(e) => Err(e),
+    }
+}
+
+// The default of `${NAME:-...}` is a word of its own. Inside double quotes
+// it behaves like the rest of the quoted text.
+fn parse_default(text: &str, quoted: bool) -> Result<Word, String> {
+    let mut word = Word::default();
+    if quoted {
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.next_if(|next| matches!(next, '"' | '\\' | '$' | '}')) {
+                    Some(next) => word.push_quoted(next),
+                    None => word.push_quoted(c),
+                }
+            } else if c == '$' {
+                match read_param(&mut chars, true)? {
+                    Some(param) => word.0.push(param),
+                    None => word.push_quoted('$'),
+                }
+            } else {
+                word.push_quoted(c);
+            }
+        }
+        return Ok(word);
+    }
+
+    // Unquoted: tokenize it, a space between words is split again on expansion
+    for (i, token) in parse_tokens(text)?.into_iter().enumerate() {
+        let Token::Word(part) = token else {
+            return Err(format!("{}: bad substitution", text));
+        };
+        if i > 0 {
+            word.push_literal(' ');
+        }
+        // `${X:-''}` must still count as quoted (an empty word)
+        if part.0.is_empty() {
+            word.0.push(WordPart::Quoted(String::new()));
+        }
+        word.0.extend(part.0);
+    }
+    Ok(word)
+}
+
+// --- Here-documents ---
+// `cat <<EOF` only reads its delimiter on the spot. The body starts on the
+// next line, so it is filled in once the tokenizer reaches that newline.
+struct PendingHeredoc {
+    // Where the (empty for now) body word sits in the tokens
+    index: usize,
+    delimiter: String,
+    strip_tabs: bool,
+    // `<<'EOF'`, `<<"EOF"` and `<<\EOF` leave the body as it is
+    expand: bool,
+}
+
+// Called right after `<<` / `<<-`: the delimiter with its quotes removed,
+// and whether the body should be expanded (no quotes at all)
+fn read_delimiter(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+) -> Result<(String, bool), String> {
+    while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+    let mut delimiter = String::new();
+    let mut quoted = false;
+    while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !";|&<>()".contains(*c)) {
+        match c {
+            '\'' | '"' => {
+                quoted = true;
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(q) => delimiter.push(q),
+                        None => return Err("Incomplete".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                quoted = true;
+                if let Some(next) = chars.next() {
+                    delimiter.push(next);
+                }
+            }
+            _ => delimiter.push(c),
+        }
+    }
+    if delimiter.is_empty() && !quoted {
+        let next = match chars.peek() {
+            None | Some('\n') => "newline".to_string(),
+            Some(c) => c.to_string(),
+        };
+        return Err(format!("syntax error near unexpected token `{}'", next));
+    }
+    Ok((delimiter, !quoted))
+}
+
+// Reads body lines up to the delimiter line (which may end the input)
+fn read_heredoc(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    heredoc: &PendingHeredoc,
+) -> Result<Word, String> {
+    let mut body = String::new();
+    loop {
+        if chars.peek().is_none() {
+            return Err("Incomplete".to_string());
+        }
+        let mut line: String = std::iter::from_fn(|| chars.next_if(|c| *c != '\n')).collect();
+        let ended = chars.next().is_none();
+        if heredoc.strip_tabs {
+            line = line.trim_start_matches('\t').to_string();
+        }
+        if line == heredoc.delimiter {
+            break;
+        }
+        if ended {
+            return Err("Incomplete".to_string());
+        }
+        body.push_str(&line);
+        body.push('\n');
+    }
+    if !heredoc.expand {
+        return Ok(Word(vec![WordPart::Quoted(body)]));
+    }
+    expandable_text(&body)
+}
+
+// A here-document body (unquoted delimiter) or an arithmetic expression:
+// expanded much like double quotes, except that `"` is just a character.
+// Everything in it counts as quoted.
+fn expandable_text(body: &str) -> Result<Word, String> {
+    let mut word = Word(vec![WordPart::Quoted(String::new())]);
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next_if(|next| matches!(next, '$' | '`' | '\\' | '\n')) {
+                Some('\n') => {}
+                Some(next) => word.push_quoted(next),
+                None => word.push_quoted(c),
+            }
+        } else if c == '$' {
+            match read_param(&mut chars, true)? {
+                Some(param) => word.0.push(param),
+                None => word.push_quoted('$'),
+            }
+        } else if c == '`' {
+            let inner = read_backquoted(&mut chars)?;
+            word.0.push(substitution(&inner, '`', true)?);
+        } else {
+            word.push_quoted(c);
+        }
+    }
+    Ok(word)
+}
+
+pub fn parse_tokens(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut word = Word::default();
+    let mut word_quoted = false;
 
     let mut in_single_quote = false;
     let mut in_double_quote = false;
     let mut escaped = false;
+    let mut heredocs: Vec<PendingHeredoc> = Vec::new();
 
     let mut chars = input.chars().peekable();
 
@@ -37,102 +620,797 @@ pub fn parse_tokens(input: &str) -> Result<Vec<String>, String> {
             if c == '\'' {
                 in_single_quote = false;
             } else {
-                current_arg.push(c);
+                word.push_quoted(c);
             }
         } else if in_double_quote {
-            // DOUBLE QUOTE MODE: allow escape characters
+            // DOUBLE QUOTE MODE: allow escape characters and $ expansion
             if escaped {
-                current_arg.push(c);
+                word.push_quoted(c);
                 escaped = false;
             } else if c == '\\' {
                 // If next char is a special one, escape it. Otherwise keep \
                 if let Some(&next) = chars.peek() {
-                    if next == '"' || next == '\\' {
+                    if next == '"' || next == '\\' || next == '$' || next == '`' {
                         escaped = true;
                     } else {
-                        current_arg.push('\\');
+                        word.push_quoted('\\');
                     }
                 } else {
                     escaped = true; // Trailing backslash
                 }
             } else if c == '"' {
                 in_double_quote = false;
+            } else if c == '$' {
+                match read_param(&mut chars, true)? {
+                    Some(param) => word.0.push(param),
+                    None => word.push_quoted('$'),
+                }
+            } else if c == '`' {
+                let body = read_backquoted(&mut chars)?;
+                word.0.push(substitution(&body, '`', true)?);
             } else {
-                current_arg.push(c);
+                word.push_quoted(c);
             }
         } else {
             // NORMAL MODE
             if escaped {
-                current_arg.push(c);
+                // Backslash-newline is a line continuation, not a character
+                if c != '\n' {
+                    word.push_quoted(c);
+                }
                 escaped = false;
             } else if c == '\\' {
                 escaped = true;
+                word_quoted = true;
             } else if c == '\'' {
                 in_single_quote = true;
+                word_quoted = true;
             } else if c == '"' {
                 in_double_quote = true;
+                word_quoted = true;
+            } else if c == '#' && word.0.is_empty() && !word_quoted {
+                // A comment runs to the end of the line (only at a word start)
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            } else if c == '\n' {
+                flush_word(&mut tokens, &mut word, &mut word_quoted);
+                tokens.push(Token::Newline);
+                for heredoc in heredocs.drain(..) {
+                    let body = read_heredoc(&mut chars, &heredoc)?;
+                    tokens[heredoc.index] = Token::Word(body);
+                }
             } else if c.is_whitespace() {
-                if !current_arg.is_empty() {
-                    args.push(current_arg.clone());
-                    current_arg.clear();
+                flush_word(&mut tokens, &mut word, &mut word_quoted);
+            } else if c == ';' {
+                flush_word(&mut tokens, &mut word, &mut word_quoted);
+                if chars.next_if_eq(&';').is_some() {
+                    tokens.push(Token::DoubleSemi);
+                } else {
+                    tokens.push(Token::Semi);
                 }
+            } else if c == '(' && chars.next_if_eq(&'(').is_some() {
+                flush_word(&mut tokens, &mut word, &mut word_quoted);
+                let text = read_arithmetic(&mut chars)?;
+                tokens.push(Token::Arith(expandable_text(&text)?));
+            } else if c == '(' || c == ')' {
+                flush_word(&mut tokens, &mut word, &mut word_quoted);
+                tokens.push(if c == '(' {
+                    Token::LParen
+                } else {
+                    Token::RParen
+                });
+            } else if c == '|' {
+                flush_word(&mut tokens, &mut word, &mut word_quoted);
+                if chars.next_if_eq(&'|').is_some() {
+                    tokens.push(Token::Or);
+                } else {
+                    tokens.push(Token::Pipe);
+                }
+            } else if c == '&' && chars.next_if_eq(&'&').is_some() {
+                flush_word(&mut tokens, &mut word, &mut word_quoted);
+                tokens.push(Token::And);
+            } else if c == '>' || c == '<' || (c == '&' && chars.peek() == Some(&'>')) {
+                // A bare 0/1/2 glued to the operator is its fd, anything else is a word
+                let explicit_fd = match word.0.as_slice() {
+                    [WordPart::Literal(digit)] if !word_quoted => match digit.as_str() {
+                        "0" | "1" | "2" => digit.parse::<u8>().ok(),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if explicit_fd.is_some() {
+                    word.0.clear();
+                } else {
+                    flush_word(&mut tokens, &mut word, &mut word_quoted);
+                }
+
+                let op = if c == '&' {
+                    chars.next(); // the '>'
+                    if chars.next_if_eq(&'>').is_some() {
+                        RedirectOp::AppendBoth
+                    } else {
+                        RedirectOp::WriteBoth
+                    }
+                } else if c == '<' {
+                    let fd = explicit_fd.unwrap_or(0);
+                    if chars.next_if_eq(&'<').is_none() {
+                        RedirectOp::Read(fd)
+                    } else if chars.next_if_eq(&'<').is_some() {
+                        RedirectOp::HereString(fd)
+                    } else {
+                        let strip_tabs = chars.next_if_eq(&'-').is_some();
+                        let (delimiter, expand) = read_delimiter(&mut chars)?;
+                        // The body comes after the end of the line
+                        tokens.push(Token::Redirect(RedirectOp::Heredoc(fd)));
+                        tokens.push(Token::Word(Word::default()));
+                        heredocs.push(PendingHeredoc {
+                            index: tokens.len() - 1,
+                            delimiter,
+                            strip_tabs,
+                            expand,
+                        });
+                        continue;
+                    }
+                } else {
+                    let fd = explicit_fd.unwrap_or(1);
+                    if chars.next_if_eq(&'>').is_some() {
+                        RedirectOp::Append(fd)
+                    } else if chars.next_if_eq(&'&').is_some() {
+                        match chars.next_if(|d| matches!(d, '0'..='2')) {
+                            Some(d) => RedirectOp::Dup(fd, d as u8 - b'0'),
+                            None => {
+                                return Err("syntax error near unexpected token `&'".to_string());
+                            }
+                        }
+                    } else {
+                        RedirectOp::Write(fd)
+                    }
+                };
+                tokens.push(Token::Redirect(op));
+            } else if c == '&' {
+                flush_word(&mut tokens, &mut word, &mut word_quoted);
+                tokens.push(Token::Amp);
+            } else if c == '$' {
+                match read_param(&mut chars, false)? {
+                    Some(param) => word.0.push(param),
+                    None => word.push_literal('$'),
+                }
+            } else if c == '`' {
+                let body = read_backquoted(&mut chars)?;
+                word.0.push(substitution(&body, '`', false)?);
             } else {
-                current_arg.push(c);
+                word.push_literal(c);
             }
         }
     }
 
-    // Check for unclosed quotes
-    if in_single_quote || in_double_quote || escaped {
+    // Check for unclosed quotes (or a here-document still waiting for its body)
+    if in_single_quote || in_double_quote || escaped || !heredocs.is_empty() {
         return Err("Incomplete".to_string());
     }
 
     // Push the last argument if exists
-    if !current_arg.is_empty() {
-        args.push(current_arg);
-    }
+    flush_word(&mut tokens, &mut word, &mut word_quoted);
 
-    Ok(args)
+    Ok(tokens)
 }
 
 pub fn parse_input(input: &str) -> ParseResult {
-    let trimmed = input.trim();
-    if trimmed.is_empty() {
-        return ParseResult::Ok(CommandEnum::Unknown("".to_string()));
-    }
-
-    match parse_tokens(trimmed) {
-        Ok(args) => {
-            if args.is_empty() {
-                return ParseResult::Ok(CommandEnum::Unknown("".to_string()));
-            }
-            let cmd_name = &args[0];
-            let cmd_args = args[1..].to_vec();
-
-            let raw_args = cmd_args.clone();
-            let clean_args: Vec<String> = cmd_args
-                .iter()
-                .map(|ele| ele.replace("\n", "\\n"))
-                .collect();
-
-            let parsed = match cmd_name.as_str() {
-                "ls" => CommandEnum::Ls(clean_args),
-                "cat" => CommandEnum::Cat(clean_args),
-                "cp" => CommandEnum::Cp(clean_args),
-                "pwd" => CommandEnum::Pwd,
-                "cd" => CommandEnum::Cd(clean_args, raw_args),
-                "echo" => CommandEnum::Echo(raw_args),
-                "rm" => CommandEnum::Rm(clean_args),
-                "mkdir" => CommandEnum::Mkdir(raw_args, clean_args),
-                "mv" => CommandEnum::Mv(clean_args),
-                "exit" => CommandEnum::Exit,
-                "clear" => CommandEnum::Clear,
-                _ => CommandEnum::Unknown(cmd_name.clone()),
+    parse_input_with_aliases(input, &BTreeMap::new())
+}
+
+// Aliases are swapped in while parsing, so `alias ll='ls -l'` typed on one
+// line works from the next line on
+pub fn parse_input_with_aliases(input: &str, aliases: &BTreeMap<String, String>) -> ParseResult {
+    let tokens = match parse_tokens(input) {
+        Ok(tokens) => tokens,
+        Err(e) if e == "Incomplete" => return ParseResult::Incomplete,
+        Err(e) => return ParseResult::Err(e),
+    };
+
+    let mut parser = Parser {
+        tokens: TokenStream(tokens.into()),
+        aliases,
+    };
+    match parser.parse_list() {
+        Ok(list) => ParseResult::Ok(list),
+        Err(ParseError::Incomplete) => ParseResult::Incomplete,
+        Err(ParseError::Syntax(e)) => ParseResult::Err(e),
+    }
+}
+
+// --- Grammar ---
+// list     := and_or ((';' | newline) and_or)*
+// and_or   := pipeline (('&&' | '||') newline* pipeline)*
+// pipeline := command ('|' newline* command)*
+// command  := compound redirect* | function | (word | redirect)+
+// function := name '(' ')' newline* compound redirect*
+//           | function name ('(' ')')? newline* compound redirect*
+// compound := '{' list '}'
+//           | if list then list (elif list then list)* (else list)? fi
+//           | (while | until) list do list done
+//           | for name (in word*)? do list done
+//           | case word in ('('? word ('|' word)* ')' list ';;')* esac
+// Reserved words only count unquoted and where a command name can go, so
+// `echo done` is just an echo. A block left open asks for more lines.
+const RESERVED: &[&str] = &[
+    "if", "then", "elif", "else", "fi", "while", "until", "do", "done", "for", "case", "esac", "{",
+    "}", "function",
+];
+
+// The words a function body can start with
+const BLOCK_STARTS: &[&str] = &["{", "if", "while", "until", "for", "case"];
+
+// The reserved word a token spells, if it is one
+fn reserved(token: Option<&Token>) -> Option<&'static str> {
+    let Some(Token::Word(Word(parts))) = token else {
+        return None;
+    };
+    match parts.as_slice() {
+        [WordPart::Literal(text)] => RESERVED.iter().copied().find(|word| word == text),
+        _ => None,
+    }
+}
+
+enum ParseError {
+    // Ran out of tokens where more were required: ask for another line
+    Incomplete,
+    Syntax(String),
+}
+
+fn unexpected(token: Option<&Token>) -> ParseError {
+    let text = match token {
+        None | Some(Token::Newline) => "newline",
+        Some(Token::Word(word)) if reserved(token).is_some() => {
+            return ParseError::Syntax(format!(
+                "syntax error near unexpected token `{}'",
+                word.literal_text().unwrap_or_default()
+            ));
+        }
+        Some(Token::Pipe) => "|",
+        Some(Token::And) => "&&",
+        Some(Token::Or) => "||",
+        Some(Token::Amp) => "&",
+        Some(Token::Semi) => ";",
+        Some(Token::DoubleSemi) => ";;",
+        Some(Token::LParen) => "(",
+        Some(Token::RParen) => ")",
+        Some(Token::Arith(_)) => "((",
+        Some(Token::Redirect(RedirectOp::Read(_))) => "<",
+        Some(Token::Redirect(RedirectOp::Append(_))) => ">>",
+        Some(Token::Redirect(RedirectOp::WriteBoth)) => "&>",
+        Some(Token::Redirect(RedirectOp::AppendBoth)) => "&>>",
+        Some(Token::Redirect(RedirectOp::Heredoc(_))) => "<<",
+        Some(Token::Redirect(RedirectOp::HereString(_))) => "<<<",
+        Some(Token::Redirect(_)) => ">",
+        Some(Token::Word(_)) => "word",
+    };
+    ParseError::Syntax(format!("syntax error near unexpected token `{}'", text))
+}
+
+// Tokens still to parse. Alias expansion puts new ones back at the front.
+struct TokenStream(VecDeque<Token>);
+
+impl TokenStream {
+    fn peek(&self) -> Option<&Token> {
+        self.0.front()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        self.0.pop_front()
+    }
+
+    fn next_if_eq(&mut self, expected: &Token) -> Option<Token> {
+        if self.peek() == Some(expected) {
+            self.next()
+        } else {
+            None
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: TokenStream,
+    aliases: &'a BTreeMap<String, String>,
+}
+
+impl Parser<'_> {
+    // --- Aliases ---
+    // Only an unquoted word where a command name goes is looked up, so
+    // `\ls` or `'ls'` skip the alias. An alias is not expanded again inside
+    // its own expansion (`alias ls='ls -F'`), and one ending in a space
+    // (`alias sudo='sudo '`) gets the word after it looked up as well.
+    fn expand_aliases(&mut self) -> Result<(), ParseError> {
+        let mut seen = Vec::new();
+        let mut index = 0;
+        loop {
+            let (len, check_next) = self.expand_alias_at(index, &mut seen)?;
+            if !check_next {
+                return Ok(());
+            }
+            index += len;
+        }
+    }
+
+    // Replaces the word at `index` if it names an alias, and again while the
+    // result starts with another one. Returns how many tokens stand there now
+    // and whether the word after them needs a look too.
+    fn expand_alias_at(
+        &mut self,
+        index: usize,
+        seen: &mut Vec<String>,
+    ) -> Result<(usize, bool), ParseError> {
+        let Some(Token::Word(Word(parts))) = self.tokens.0.get(index) else {
+            return Ok((1, false));
+        };
+        let name = match parts.as_slice() {
+            [WordPart::Literal(name)] if !seen.contains(name) => name.clone(),
+            _ => return Ok((1, false)),
+        };
+        let Some(value) = self.aliases.get(&name) else {
+            return Ok((1, false));
+        };
+        let expansion = parse_tokens(value)
+            .map_err(|e| ParseError::Syntax(format!("alias {}: {}", name, e.to_lowercase())))?;
+        let trailing_blank = value.ends_with([' ', '\t']);
+        let count = expansion.len();
+        self.tokens.0.remove(index);
+        for (i, token) in expansion.into_iter().enumerate() {
+            self.tokens.0.insert(index + i, token);
+        }
+        seen.push(name);
+        if count == 0 {
+            return Ok((0, trailing_blank));
+        }
+        let (first_len, first_blank) = self.expand_alias_at(index, seen)?;
+        Ok((
+            count - 1 + first_len,
+            trailing_blank || (count == 1 && first_blank),
+        ))
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.tokens.next_if_eq(&Token::Newline).is_some() {}
+    }
+
+    // After `|`, `&&` or `||` the command may continue on the next line
+    fn expect_more(&mut self) -> Result<(), ParseError> {
+        self.skip_newlines();
+        match self.tokens.peek() {
+            None => Err(ParseError::Incomplete),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<CommandList, ParseError> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.tokens.peek().is_none() {
+                break;
+            }
+            let mut item = self.parse_and_or()?;
+            match self.tokens.next() {
+                None => {
+                    items.push(item);
+                    break;
+                }
+                Some(Token::Semi) | Some(Token::Newline) => items.push(item),
+                Some(Token::Amp) => {
+                    item.background = true;
+                    items.push(item);
+                }
+                Some(other) => return Err(unexpected(Some(&other))),
+            }
+        }
+        Ok(CommandList { items })
+    }
+
+    fn parse_and_or(&mut self) -> Result<AndOrList, ParseError> {
+        let first = self.parse_pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let connector = match self.tokens.peek() {
+                Some(Token::And) => Connector::And,
+                Some(Token::Or) => Connector::Or,
+                _ => break,
             };
+            self.tokens.next();
+            self.expect_more()?;
+            rest.push((connector, self.parse_pipeline()?));
+        }
+        Ok(AndOrList {
+            first,
+            rest,
+            background: false,
+        })
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let mut commands = vec![self.parse_command()?];
+        while self.tokens.next_if_eq(&Token::Pipe).is_some() {
+            self.expect_more()?;
+            commands.push(self.parse_command()?);
+        }
+        Ok(Pipeline { commands })
+    }
+
+    fn parse_command(&mut self) -> Result<Command, ParseError> {
+        self.expand_aliases()?;
+        let compound = match reserved(self.tokens.peek()) {
+            None if matches!(self.tokens.peek(), Some(Token::Arith(_))) => {
+                let Some(Token::Arith(expr)) = self.tokens.next() else {
+                    unreachable!("just peeked at it")
+                };
+                Compound::Arith(expr)
+            }
+            None => {
+                let command = self.parse_simple_command()?;
+                // `name()` starts a function definition
+                if self.tokens.peek() == Some(&Token::LParen)
+                    && command.words.len() == 1
+                    && command.redirects.is_empty()
+                {
+                    return self.parse_function(&command.words[0]);
+                }
+                return Ok(Command::Simple(command));
+            }
+            Some("function") => {
+                self.tokens.next();
+                return match self.tokens.next() {
+                    Some(Token::Word(name)) => self.parse_function(&name),
+                    None => Err(ParseError::Incomplete),
+                    other => Err(unexpected(other.as_ref())),
+                };
+            }
+            Some("{") => {
+                self.tokens.next();
+                let body = self.parse_body(&["}"], false)?;
+                self.expect_word("}")?;
+                Compound::Group(body)
+            }
+            Some("if") => self.parse_if()?,
+            Some(keyword @ ("while" | "until")) => self.parse_while(keyword == "until")?,
+            Some("for") => self.parse_for()?,
+            Some("case") => self.parse_case()?,
+            Some(_) => return Err(unexpected(self.tokens.peek())),
+        };
+        let mut redirects = Vec::new();
+        while let Some(Token::Redirect(op)) = self.tokens.peek().cloned() {
+            self.tokens.next();
+            self.parse_redirect(op, &mut redirects)?;
+        }
+        Ok(Command::Compound(compound, redirects))
+    }
 
-            ParseResult::Ok(parsed)
+    // Everything after the name: `()` (optional after `function`) and the body
+    fn parse_function(&mut self, name: &Word) -> Result<Command, ParseError> {
+        let name = match name.literal_text() {
+            Some(name) if !name.is_empty() && !name.contains(['=', '/', '$']) => name,
+            _ => {
+                let text = name.literal_text().unwrap_or_default();
+                return Err(ParseError::Syntax(format!(
+                    "`{}': not a valid identifier",
+                    text
+                )));
+            }
+        };
+        if self.tokens.next_if_eq(&Token::LParen).is_some() {
+            match self.tokens.next() {
+                Some(Token::RParen) => {}
+                None => return Err(ParseError::Incomplete),
+                other => return Err(unexpected(other.as_ref())),
+            }
+        }
+        self.skip_newlines();
+        if self.tokens.peek().is_none() {
+            return Err(ParseError::Incomplete);
+        }
+        if !reserved(self.tokens.peek()).is_some_and(|word| BLOCK_STARTS.contains(&word)) {
+            return Err(unexpected(self.tokens.peek()));
+        }
+        match self.parse_command()? {
+            Command::Compound(body, redirects) => Ok(Command::Function(Rc::new(Function {
+                name,
+                body,
+                redirects,
+            }))),
+            _ => unreachable!("a block start always parses as a block"),
+        }
+    }
+
+    // Takes the reserved word `word`, or says why it isn't there
+    fn expect_word(&mut self, word: &str) -> Result<(), ParseError> {
+        match reserved(self.tokens.peek()) {
+            Some(found) if found == word => {
+                self.tokens.next();
+                Ok(())
+            }
+            _ if self.tokens.peek().is_none() => Err(ParseError::Incomplete),
+            _ => Err(unexpected(self.tokens.peek())),
         }
-        Err(_) => ParseResult::Incomplete,
+    }
+
+    // The commands inside a block, up to one of the reserved words in `ends`
+    // (or a `;;` in a case). Only a case arm may be empty.
+    fn parse_body(&mut self, ends: &[&str], allow_empty: bool) -> Result<CommandList, ParseError> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_newlines();
+            let at_end = match self.tokens.peek() {
+                None => return Err(ParseError::Incomplete),
+                Some(Token::DoubleSemi) => true,
+                token => reserved(token).is_some_and(|word| ends.contains(&word)),
+            };
+            if at_end {
+                if items.is_empty() && !allow_empty {
+                    return Err(unexpected(self.tokens.peek()));
+                }
+                return Ok(CommandList { items });
+            }
+            items.push(self.parse_and_or()?);
+            match self.tokens.peek() {
+                None => return Err(ParseError::Incomplete),
+                Some(Token::Semi) | Some(Token::Newline) => {
+                    self.tokens.next();
+                }
+                Some(Token::Amp) => {
+                    self.tokens.next();
+                    if let Some(item) = items.last_mut() {
+                        item.background = true;
+                    }
+                }
+                // `fi` or `;;` right after a nested block
+                Some(Token::DoubleSemi) => {}
+                token if reserved(token).is_some_and(|word| ends.contains(&word)) => {}
+                other => return Err(unexpected(other)),
+            }
+        }
+    }
+
+    fn parse_if(&mut self) -> Result<Compound, ParseError> {
+        let mut branches = Vec::new();
+        let mut otherwise = None;
+        self.tokens.next(); // `if`
+        loop {
+            let condition = self.parse_body(&["then"], false)?;
+            self.expect_word("then")?;
+            let body = self.parse_body(&["elif", "else", "fi"], false)?;
+            branches.push((condition, body));
+            match reserved(self.tokens.next().as_ref()) {
+                Some("elif") => continue,
+                Some("else") => {
+                    otherwise = Some(self.parse_body(&["fi"], false)?);
+                    self.expect_word("fi")?;
+                }
+                _ => {} // `fi`
+            }
+            return Ok(Compound::If {
+                branches,
+                otherwise,
+            });
+        }
+    }
+
+    fn parse_while(&mut self, until: bool) -> Result<Compound, ParseError> {
+        self.tokens.next(); // `while` / `until`
+        let condition = self.parse_body(&["do"], false)?;
+        self.expect_word("do")?;
+        let body = self.parse_body(&["done"], false)?;
+        self.expect_word("done")?;
+        Ok(Compound::While {
+            condition,
+            body,
+            until,
+        })
+    }
+
+    fn parse_for(&mut self) -> Result<Compound, ParseError> {
+        self.tokens.next(); // `for`
+        let name = match self.tokens.next() {
+            Some(Token::Word(word)) => match word.literal_text() {
+                Some(name) if is_valid_name(&name) => name,
+                _ => {
+                    let text = word.literal_text().unwrap_or_default();
+                    return Err(ParseError::Syntax(format!(
+                        "`{}': not a valid identifier",
+                        text
+                    )));
+                }
+            },
+            None => return Err(ParseError::Incomplete),
+            other => return Err(unexpected(other.as_ref())),
+        };
+
+        // `for x in a b c;` or just `for x;` (then a newline may come first)
+        self.skip_newlines();
+        let mut words = None;
+        let is_in = matches!(self.tokens.peek(), Some(Token::Word(word)) if word.literal_text().as_deref() == Some("in"));
+        if is_in {
+            self.tokens.next();
+            let mut list = Vec::new();
+            while let Some(Token::Word(_)) = self.tokens.peek() {
+                if let Some(Token::Word(word)) = self.tokens.next() {
+                    list.push(word);
+                }
+            }
+            words = Some(list);
+        }
+        match self.tokens.peek() {
+            Some(Token::Semi) | Some(Token::Newline) => {
+                self.tokens.next();
+            }
+            None => return Err(ParseError::Incomplete),
+            _ => {}
+        }
+        self.skip_newlines();
+        self.expect_word("do")?;
+        let body = self.parse_body(&["done"], false)?;
+        self.expect_word("done")?;
+        Ok(Compound::For { name, words, body })
+    }
+
+    fn parse_case(&mut self) -> Result<Compound, ParseError> {
+        self.tokens.next(); // `case`
+        let word = match self.tokens.next() {
+            Some(Token::Word(word)) => word,
+            None => return Err(ParseError::Incomplete),
+            other => return Err(unexpected(other.as_ref())),
+        };
+        self.skip_newlines();
+        match self.tokens.next() {
+            Some(Token::Word(w)) if w.literal_text().as_deref() == Some("in") => {}
+            None => return Err(ParseError::Incomplete),
+            other => return Err(unexpected(other.as_ref())),
+        }
+
+        let mut arms = Vec::new();
+        loop {
+            self.skip_newlines();
+            match self.tokens.peek() {
+                None => return Err(ParseError::Incomplete),
+                token if reserved(token) == Some("esac") => {
+                    self.tokens.next();
+                    break;
+                }
+                _ => {}
+            }
+            self.tokens.next_if_eq(&Token::LParen);
+            let mut patterns = Vec::new();
+            loop {
+                match self.tokens.next() {
+                    Some(Token::Word(pattern)) => patterns.push(pattern),
+                    None => return Err(ParseError::Incomplete),
+                    other => return Err(unexpected(other.as_ref())),
+                }
+                match self.tokens.next() {
+                    Some(Token::Pipe) => {}
+                    Some(Token::RParen) => break,
+                    None => return Err(ParseError::Incomplete),
+                    other => return Err(unexpected(other.as_ref())),
+                }
+            }
+            let body = self.parse_body(&["esac"], true)?;
+            arms.push(CaseArm { patterns, body });
+            // The last arm may leave out its `;;`
+            if self.tokens.next_if_eq(&Token::DoubleSemi).is_none() {
+                self.expect_word("esac")?;
+                break;
+            }
+        }
+        Ok(Compound::Case { word, arms })
+    }
+
+    fn parse_simple_command(&mut self) -> Result<SimpleCommand, ParseError> {
+        let mut words = Vec::new();
+        let mut redirects = Vec::new();
+        loop {
+            match self.tokens.peek() {
+                Some(Token::Word(_)) => {
+                    if let Some(Token::Word(word)) = self.tokens.next() {
+                        words.push(word);
+                    }
+                }
+                Some(Token::Redirect(_)) => {
+                    if let Some(Token::Redirect(op)) = self.tokens.next() {
+                        self.parse_redirect(op, &mut redirects)?;
+                    }
+                }
+                _ => break,
+            }
+        }
+        if words.is_empty() && redirects.is_empty() {
+            return Err(unexpected(self.tokens.peek()));
+        }
+        Ok(SimpleCommand { words, redirects })
+    }
+
+    fn parse_redirect(
+        &mut self,
+        op: RedirectOp,
+        redirects: &mut Vec<Redirect>,
+    ) -> Result<(), ParseError> {
+        let (fd, mode) = match op {
+            RedirectOp::Dup(fd, target) => {
+                redirects.push(Redirect::Dup { fd, target });
+                return Ok(());
+            }
+            RedirectOp::Heredoc(fd) | RedirectOp::HereString(fd) => {
+                let mut body = match self.tokens.next() {
+                    Some(Token::Word(body)) => body,
+                    other => return Err(unexpected(other.as_ref())),
+                };
+                // A here-string gets a newline added, like a one-line here-document
+                if op == RedirectOp::HereString(fd) {
+                    body.0.push(WordPart::Quoted("\n".to_string()));
+                }
+                redirects.push(Redirect::Here { fd, body });
+                return Ok(());
+            }
+            RedirectOp::Read(fd) => (fd, FileMode::Read),
+            RedirectOp::Write(fd) => (fd, FileMode::Write),
+            RedirectOp::Append(fd) => (fd, FileMode::Append),
+            RedirectOp::WriteBoth => (1, FileMode::Write),
+            RedirectOp::AppendBoth => (1, FileMode::Append),
+        };
+        let target = match self.tokens.next() {
+            Some(Token::Word(target)) => target,
+            other => return Err(unexpected(other.as_ref())),
+        };
+        redirects.push(Redirect::File { fd, target, mode });
+        // `&> f` is just `> f 2>&1`
+        if matches!(op, RedirectOp::WriteBoth | RedirectOp::AppendBoth) {
+            redirects.push(Redirect::Dup { fd: 2, target: 1 });
+        }
+        Ok(())
+    }
+}
+
+// Names build_command maps to a builtin (used by tab completion)
+pub const BUILTINS: &[&str] = &[
+    "ls", "cat", "cp", "pwd", "cd", "echo", "rm", "mkdir", "mv", "exit", "export", "unset", "set",
+    "clear", "source", ".", "local", "return", "alias", "unalias", "jobs", "fg", "bg", "wait",
+    "disown",
+];
+
+pub fn build_command(args: Vec<String>) -> CommandEnum {
+    if args.is_empty() {
+        return CommandEnum::Unknown("".to_string());
+    }
+    let cmd_name = &args[0];
+    let cmd_args = args[1..].to_vec();
+
+    let raw_args = cmd_args.clone();
+    let clean_args: Vec<String> = cmd_args
+        .iter()
+        .map(|ele| ele.replace("\n", "\\n"))
+        .collect();
+
+    match cmd_name.as_str() {
+        "ls" => CommandEnum::Ls(clean_args),
+        "cat" => CommandEnum::Cat(clean_args),
+        "cp" => CommandEnum::Cp(clean_args),
+        "pwd" => CommandEnum::Pwd,
+        "cd" => CommandEnum::Cd(clean_args, raw_args),
+        "echo" => CommandEnum::Echo(raw_args),
+        "rm" => CommandEnum::Rm(clean_args),
+        "mkdir" => CommandEnum::Mkdir(raw_args, clean_args),
+        "mv" => CommandEnum::Mv(clean_args),
+        "exit" => CommandEnum::Exit(raw_args),
+        "export" => CommandEnum::Export(raw_args),
+        "unset" => CommandEnum::Unset(raw_args),
+        "set" => CommandEnum::Set(raw_args),
+        "source" | "." => CommandEnum::Source(raw_args),
+        "local" => CommandEnum::Local(raw_args),
+        "return" => CommandEnum::Return(raw_args),
+        "alias" => CommandEnum::Alias(raw_args),
+        "unalias" => CommandEnum::Unalias(raw_args),
+        "jobs" => CommandEnum::Jobs(raw_args),
+        "fg" => CommandEnum::Fg(raw_args),
+        "bg" => CommandEnum::Bg(raw_args),
+        "wait" => CommandEnum::Wait(raw_args),
+        "disown" => CommandEnum::Disown(raw_args),
+        "clear" => CommandEnum::Clear,
+        _ => CommandEnum::External(cmd_name.clone(), raw_args),
     }
 }
 
@@ -142,34 +1420,90 @@ pub fn parse_input(input: &str) -> ParseResult {
 mod tests {
     use super::*;
 
+    // Quote-removed text of each word; operators show up as their symbol
+    fn texts(input: &str) -> Vec<String> {
+        parse_tokens(input)
+            .unwrap()
+            .iter()
+            .map(|token| match token {
+                Token::Word(word) => word.literal_text().expect("word needs expansion"),
+                Token::Pipe => "|".to_string(),
+                Token::And => "&&".to_string(),
+                Token::Or => "||".to_string(),
+                Token::Amp => "&".to_string(),
+                Token::Semi => ";".to_string(),
+                Token::DoubleSemi => ";;".to_string(),
+                Token::LParen => "(".to_string(),
+                Token::RParen => ")".to_string(),
+                Token::Newline => "\\n".to_string(),
+                Token::Redirect(_) => "<redirect>".to_string(),
+                Token::Arith(_) => "((".to_string(),
+            })
+            .collect()
+    }
+
+    fn parse(input: &str) -> CommandList {
+        match parse_input(input) {
+            ParseResult::Ok(list) => list,
+            other => panic!("Expected a command list, got {:?}", other),
+        }
+    }
+
+    // Most tests only care about a single command without pipes
+    fn single(input: &str) -> CommandEnum {
+        let list = parse(input);
+        assert_eq!(list.items.len(), 1);
+        let pipeline = &list.items[0].first;
+        assert_eq!(pipeline.commands.len(), 1);
+        let args = simple(&pipeline.commands[0])
+            .words
+            .iter()
+            .map(|w| w.literal_text().unwrap())
+            .collect();
+        build_command(args)
+    }
+
+    fn simple(command: &Command) -> &SimpleCommand {
+        match command {
+            Command::Simple(simple) => simple,
+            other => panic!("Expected a simple command, got {:?}", other),
+        }
+    }
+
+    fn redirects(input: &str) -> Vec<Redirect> {
+        simple(&parse(input).items[0].first.commands[0])
+            .redirects
+            .clone()
+    }
+
+    fn word(text: &str) -> Word {
+        Word(vec![WordPart::Literal(text.to_string())])
+    }
+
     // --- 1. Test Tokenization Logic ---
     #[test]
     fn test_tokenize_simple() {
         let input = "ls -la";
-        let expected = Ok(vec!["ls".to_string(), "-la".to_string()]);
-        assert_eq!(parse_tokens(input), expected);
+        let expected = vec!["ls", "-la"];
+        assert_eq!(texts(input), expected);
     }
 
     #[test]
     fn test_tokenize_quotes() {
         let input = "echo 'hello world' \"formatted string\"";
-        let expected = Ok(vec![
-            "echo".to_string(),
-            "hello world".to_string(),
-            "formatted string".to_string(),
-        ]);
-        assert_eq!(parse_tokens(input), expected);
+        let expected = vec!["echo", "hello world", "formatted string"];
+        assert_eq!(texts(input), expected);
     }
 
     #[test]
     fn test_tokenize_escaped_quotes() {
         // Input: echo "He said \"Hello\""
         let input = r#"echo "He said \"Hello\"""#;
-        let expected = Ok(vec![
-            "echo".to_string(),
-            "He said \"Hello\"".to_string(), // Parser keeps the internal quotes
-        ]);
-        assert_eq!(parse_tokens(input), expected);
+        let expected = vec![
+            "echo",
+            "He said \"Hello\"", // Parser keeps the internal quotes
+        ];
+        assert_eq!(texts(input), expected);
     }
 
     #[test]
@@ -178,12 +1512,149 @@ mod tests {
         assert_eq!(parse_tokens(input), Err("Incomplete".to_string()));
     }
 
+    #[test]
+    fn test_tokenize_empty_quotes_is_a_word() {
+        assert_eq!(texts("echo '' \"\""), vec!["echo", "", ""]);
+    }
+
+    #[test]
+    fn test_tokenize_comments() {
+        assert_eq!(
+            texts("echo a#b # rest | x\nls '#q' \\#e"),
+            vec!["echo", "a#b", "\\n", "ls", "#q", "#e"]
+        );
+        assert_eq!(texts("# just a comment"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_tokenize_operators() {
+        assert_eq!(
+            texts("ls| cat |wc;a&&b||c"),
+            vec!["ls", "|", "cat", "|", "wc", ";", "a", "&&", "b", "||", "c"]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_quoted_operators_are_literal() {
+        assert_eq!(
+            texts("echo 'a|b' \\| \"x;y\" '&&'"),
+            vec!["echo", "a|b", "|", "x;y", "&&"]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_variables() {
+        let param = |name: &str, quoted, default| WordPart::Param {
+            name: name.to_string(),
+            quoted,
+            default,
+        };
+        let tokens =
+            parse_tokens("$HOME/x ${A}b \"${B:-$C d}\" ${D:-'e f'} '$E' $ $12 ${10}").unwrap();
+        assert_eq!(
+            tokens[0],
+            Token::Word(Word(vec![
+                param("HOME", false, None),
+                WordPart::Literal("/x".to_string())
+            ]))
+        );
+        assert_eq!(
+            tokens[1],
+            Token::Word(Word(vec![
+                param("A", false, None),
+                WordPart::Literal("b".to_string())
+            ]))
+        );
+        let quoted_default = Word(vec![
+            param("C", true, None),
+            WordPart::Quoted(" d".to_string()),
+        ]);
+        assert_eq!(
+            tokens[2],
+            Token::Word(Word(vec![param("B", true, Some(quoted_default))]))
+        );
+        let default = Word(vec![WordPart::Quoted("e f".to_string())]);
+        assert_eq!(
+            tokens[3],
+            Token::Word(Word(vec![param("D", false, Some(default))]))
+        );
+        assert_eq!(
+            tokens[4],
+            Token::Word(Word(vec![WordPart::Quoted("$E".to_string())]))
+        );
+        assert_eq!(tokens[5], Token::Word(word("$")));
+        // One digit unless braced
+        assert_eq!(
+            tokens[6],
+            Token::Word(Word(vec![
+                param("1", false, None),
+                WordPart::Literal("2".to_string())
+            ]))
+        );
+        assert_eq!(tokens[7], Token::Word(Word(vec![param("10", false, None)])));
+    }
+
+    #[test]
+    fn test_tokenize_bad_or_unfinished_substitution() {
+        assert_eq!(parse_tokens("echo ${A"), Err("Incomplete".to_string()));
+        assert!(matches!(parse_input("echo ${1x}"), ParseResult::Err(_)));
+    }
+
+    #[test]
+    fn test_word_assignment() {
+        let tokens = parse_tokens("A=1 B= C=\"x y\" =no 1x=no").unwrap();
+        let assignments: Vec<_> = tokens
+            .iter()
+            .map(|t| match t {
+                Token::Word(w) => w
+                    .assignment()
+                    .map(|(name, value)| (name, value.literal_text())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            assignments,
+            vec![
+                Some(("A".to_string(), Some("1".to_string()))),
+                Some(("B".to_string(), Some("".to_string()))),
+                Some(("C".to_string(), Some("x y".to_string()))),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_status_param_respects_quotes() {
+        let tokens = parse_tokens("echo $? \"$?\" '$?' \\$?").unwrap();
+        let param = |quoted| {
+            Token::Word(Word(vec![WordPart::Param {
+                name: "?".to_string(),
+                quoted,
+                default: None,
+            }]))
+        };
+        assert_eq!(tokens[1], param(false));
+        assert_eq!(tokens[2], param(true));
+        assert_eq!(
+            tokens[3],
+            Token::Word(Word(vec![WordPart::Quoted("$?".to_string())]))
+        );
+        assert_eq!(
+            tokens[4],
+            Token::Word(Word(vec![
+                WordPart::Quoted("$".to_string()),
+                WordPart::Literal("?".to_string())
+            ]))
+        );
+    }
+
     // --- 2. Test Enum Mapping Logic ---
     #[test]
     fn test_parse_input_ls() {
         let input = "ls -la /tmp";
-        match parse_input(input) {
-            ParseResult::Ok(CommandEnum::Ls(args)) => {
+        match single(input) {
+            CommandEnum::Ls(args) => {
                 assert_eq!(args, vec!["-la", "/tmp"]);
             }
             _ => panic!("Expected CommandEnum::Ls"),
@@ -193,8 +1664,8 @@ mod tests {
     #[test]
     fn test_parse_input_cd() {
         let input = "cd /home/user";
-        match parse_input(input) {
-            ParseResult::Ok(CommandEnum::Cd(clean, raw)) => {
+        match single(input) {
+            CommandEnum::Cd(clean, raw) => {
                 assert_eq!(clean[0], "/home/user");
                 assert_eq!(raw[0], "/home/user");
             }
@@ -203,13 +1674,600 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_input_unknown() {
+    fn test_parse_input_external() {
         let input = "notacommand arg1";
-        match parse_input(input) {
-            ParseResult::Ok(CommandEnum::Unknown(cmd)) => {
+        match single(input) {
+            CommandEnum::External(cmd, args) => {
                 assert_eq!(cmd, "notacommand");
+                assert_eq!(args, vec!["arg1"]);
             }
-            _ => panic!("Expected CommandEnum::Unknown"),
+            _ => panic!("Expected CommandEnum::External"),
         }
     }
+
+    #[test]
+    fn test_parse_input_empty() {
+        assert_eq!(parse_input("   "), ParseResult::Ok(CommandList::default()));
+        assert_eq!(parse_input("\n\n"), ParseResult::Ok(CommandList::default()));
+    }
+
+    // --- 3. Test Pipelines ---
+    #[test]
+    fn test_parse_input_pipeline() {
+        let list = parse("ls -l | grep rs | wc -l");
+        let pipeline = &list.items[0].first;
+        assert_eq!(pipeline.commands.len(), 3);
+        assert_eq!(
+            simple(&pipeline.commands[0]).words,
+            vec![word("ls"), word("-l")]
+        );
+        assert_eq!(
+            simple(&pipeline.commands[2]).words,
+            vec![word("wc"), word("-l")]
+        );
+    }
+
+    #[test]
+    fn test_parse_input_trailing_pipe_is_incomplete() {
+        assert_eq!(parse_input("ls |"), ParseResult::Incomplete);
+        assert_eq!(parse_input("ls |\n"), ParseResult::Incomplete);
+    }
+
+    #[test]
+    fn test_parse_input_leading_pipe_is_error() {
+        assert!(matches!(parse_input("| ls"), ParseResult::Err(_)));
+        assert!(matches!(parse_input("ls | | wc"), ParseResult::Err(_)));
+    }
+
+    // --- 4. Test Redirections ---
+    #[test]
+    fn test_tokenize_redirect_operators() {
+        let tokens = parse_tokens("ls >out 2>> err 2>&1 <in &>all").unwrap();
+        let ops: Vec<RedirectOp> = tokens
+            .iter()
+            .filter_map(|t| match t {
+                Token::Redirect(op) => Some(*op),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                RedirectOp::Write(1),
+                RedirectOp::Append(2),
+                RedirectOp::Dup(2, 1),
+                RedirectOp::Read(0),
+                RedirectOp::WriteBoth,
+            ]
+        );
+        assert_eq!(
+            texts("ls >out 2>> err 2>&1 <in &>all"),
+            vec![
+                "ls",
+                "<redirect>",
+                "out",
+                "<redirect>",
+                "err",
+                "<redirect>",
+                "<redirect>",
+                "in",
+                "<redirect>",
+                "all"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_quoted_redirect_is_literal() {
+        assert_eq!(
+            texts("echo '>' a\\>b \"2\">c"),
+            vec!["echo", ">", "a>b", "2", "<redirect>", "c"]
+        );
+    }
+
+    #[test]
+    fn test_parse_input_redirects_are_removed_from_args() {
+        let list = parse("echo hi > out.txt");
+        let command = simple(&list.items[0].first.commands[0]);
+        assert_eq!(command.words, vec![word("echo"), word("hi")]);
+        assert_eq!(
+            command.redirects,
+            vec![Redirect::File {
+                fd: 1,
+                target: word("out.txt"),
+                mode: FileMode::Write
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_input_write_both_expands_to_dup() {
+        assert_eq!(
+            redirects("ls &>> log"),
+            vec![
+                Redirect::File {
+                    fd: 1,
+                    target: word("log"),
+                    mode: FileMode::Append
+                },
+                Redirect::Dup { fd: 2, target: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_input_redirect_without_target_is_error() {
+        assert!(matches!(parse_input("ls >"), ParseResult::Err(_)));
+        assert!(matches!(parse_input("ls > | wc"), ParseResult::Err(_)));
+    }
+
+    // --- 5. Test Command Lists ---
+    #[test]
+    fn test_parse_input_list_and_connectors() {
+        let list = parse("a; b && c || d\ne");
+        assert_eq!(list.items.len(), 3);
+        let connectors: Vec<Connector> = list.items[1].rest.iter().map(|(c, _)| *c).collect();
+        assert_eq!(connectors, vec![Connector::And, Connector::Or]);
+        assert_eq!(
+            simple(&list.items[2].first.commands[0]).words,
+            vec![word("e")]
+        );
+    }
+
+    #[test]
+    fn test_parse_input_trailing_connector_is_incomplete() {
+        assert_eq!(parse_input("ls &&"), ParseResult::Incomplete);
+        assert_eq!(parse_input("ls ||\n"), ParseResult::Incomplete);
+        assert!(matches!(parse_input("ls &&\nwc"), ParseResult::Ok(_)));
+    }
+
+    #[test]
+    fn test_parse_input_list_syntax_errors() {
+        assert!(matches!(parse_input("; ls"), ParseResult::Err(_)));
+        assert!(matches!(parse_input("ls ;; wc"), ParseResult::Err(_)));
+        assert!(matches!(parse_input("&& ls"), ParseResult::Err(_)));
+        assert!(matches!(parse_input("ls ;"), ParseResult::Ok(_)));
+    }
+
+    // --- 6. Test Compound Commands ---
+    fn compound(input: &str) -> Compound {
+        match &parse(input).items[0].first.commands[0] {
+            Command::Compound(compound, _) => compound.clone(),
+            other => panic!("Expected a compound command, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_if_elif_else() {
+        let Compound::If {
+            branches,
+            otherwise,
+        } = compound("if a; then b; elif c\nthen d; d2\nelse e; fi")
+        else {
+            panic!("Expected an if");
+        };
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[1].1.items.len(), 2);
+        assert!(otherwise.is_some());
+    }
+
+    #[test]
+    fn test_parse_loops() {
+        assert!(matches!(
+            compound("until a; do b; done"),
+            Compound::While { until: true, .. }
+        ));
+        let Compound::For { name, words, body } = compound("for x in 1 '2 3'\ndo echo $x; done")
+        else {
+            panic!("Expected a for");
+        };
+        assert_eq!(name, "x");
+        assert_eq!(words.map(|w| w.len()), Some(2));
+        assert_eq!(body.items.len(), 1);
+        assert!(matches!(
+            compound("for x do b; done"),
+            Compound::For { words: None, .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_case() {
+        let Compound::Case {
+            word: subject,
+            arms,
+        } = compound("case $f in\n *.c | *.h) cc;;\n (x) ;;\n *) other\nesac")
+        else {
+            panic!("Expected a case");
+        };
+        assert!(subject.literal_text().is_none());
+        assert_eq!(arms.len(), 3);
+        assert_eq!(arms[0].patterns, vec![word("*.c"), word("*.h")]);
+        assert!(arms[1].body.items.is_empty());
+    }
+
+    #[test]
+    fn test_parse_compound_in_pipeline_with_redirect() {
+        let list = parse("for x in a; do echo $x; done > out | wc -l");
+        let pipeline = &list.items[0].first;
+        assert_eq!(pipeline.commands.len(), 2);
+        let Command::Compound(_, redirects) = &pipeline.commands[0] else {
+            panic!("Expected a compound command");
+        };
+        assert_eq!(redirects.len(), 1);
+    }
+
+    #[test]
+    fn test_reserved_words_only_at_command_start() {
+        assert_eq!(
+            simple(&parse("echo if done").items[0].first.commands[0]).words,
+            vec![word("echo"), word("if"), word("done")]
+        );
+        assert!(matches!(
+            parse("'if' true").items[0].first.commands[0],
+            Command::Simple(_)
+        ));
+    }
+
+    #[test]
+    fn test_unclosed_block_is_incomplete() {
+        assert_eq!(parse_input("if true"), ParseResult::Incomplete);
+        assert_eq!(parse_input("if true; then\n echo"), ParseResult::Incomplete);
+        assert_eq!(parse_input("while true; do"), ParseResult::Incomplete);
+        assert_eq!(parse_input("for x in a b"), ParseResult::Incomplete);
+        assert_eq!(parse_input("case x in a)"), ParseResult::Incomplete);
+        assert!(matches!(
+            parse_input("if true\nthen\necho yes\nfi"),
+            ParseResult::Ok(_)
+        ));
+    }
+
+    #[test]
+    fn test_misplaced_reserved_words_are_errors() {
+        assert!(matches!(parse_input("fi"), ParseResult::Err(_)));
+        assert!(matches!(
+            parse_input("if true; then fi"),
+            ParseResult::Err(_)
+        ));
+        assert!(matches!(
+            parse_input("while; do x; done"),
+            ParseResult::Err(_)
+        ));
+        assert!(matches!(
+            parse_input("for 1x in a; do b; done"),
+            ParseResult::Err(_)
+        ));
+        assert!(matches!(
+            parse_input("if a; then b; fi c"),
+            ParseResult::Err(_)
+        ));
+        assert!(matches!(parse_input("echo ;; x"), ParseResult::Err(_)));
+    }
+
+    #[test]
+    fn test_parse_function_definitions() {
+        let function = |input: &str| match &parse(input).items[0].first.commands[0] {
+            Command::Function(function) => function.clone(),
+            other => panic!("Expected a function, got {:?}", other),
+        };
+        let f = function("greet() { echo hi; }");
+        assert_eq!(f.name, "greet");
+        assert!(matches!(f.body, Compound::Group(_)));
+
+        let f = function("function build\n{\n make\n} > log");
+        assert_eq!(f.name, "build");
+        assert_eq!(f.redirects.len(), 1);
+        assert!(matches!(
+            function("function t() if true; then :; fi").body,
+            Compound::If { .. }
+        ));
+
+        assert_eq!(parse_input("f() {"), ParseResult::Incomplete);
+        assert_eq!(parse_input("f()"), ParseResult::Incomplete);
+        assert!(matches!(parse_input("f() echo x"), ParseResult::Err(_)));
+        assert!(matches!(parse_input("a b() { :; }"), ParseResult::Err(_)));
+        assert!(matches!(parse_input("{ echo a }"), ParseResult::Incomplete));
+    }
+
+    // --- 7. Test Aliases ---
+    fn words_with_aliases(input: &str, aliases: &[(&str, &str)]) -> Vec<Vec<String>> {
+        let aliases = aliases
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let ParseResult::Ok(list) = parse_input_with_aliases(input, &aliases) else {
+            panic!("Expected a command list");
+        };
+        let first = &list.items[0].first;
+        first
+            .commands
+            .iter()
+            .map(|command| {
+                simple(command)
+                    .words
+                    .iter()
+                    .map(|w| w.literal_text().unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_alias_replaces_command_words_only() {
+        let aliases = [("ll", "ls -l"), ("g", "grep -n")];
+        assert_eq!(
+            words_with_aliases("ll /tmp | g ll", &aliases),
+            vec![vec!["ls", "-l", "/tmp"], vec!["grep", "-n", "ll"]]
+        );
+        // Quoting the name skips the alias
+        assert_eq!(
+            words_with_aliases("\\ll x", &aliases),
+            vec![vec!["ll", "x"]]
+        );
+        assert_eq!(words_with_aliases("'ll'", &aliases), vec![vec!["ll"]]);
+    }
+
+    #[test]
+    fn test_alias_recursion_stops() {
+        assert_eq!(
+            words_with_aliases("ls", &[("ls", "ls -F")]),
+            vec![vec!["ls", "-F"]]
+        );
+        assert_eq!(
+            words_with_aliases("a x", &[("a", "b 1"), ("b", "a 2")]),
+            vec![vec!["a", "2", "1", "x"]]
+        );
+    }
+
+    #[test]
+    fn test_alias_trailing_space_expands_next_word() {
+        let aliases = [("sudo", "sudo "), ("ll", "ls -l"), ("nice", "nice")];
+        assert_eq!(
+            words_with_aliases("sudo ll", &aliases),
+            vec![vec!["sudo", "ls", "-l"]]
+        );
+        assert_eq!(
+            words_with_aliases("nice ll", &aliases),
+            vec![vec!["nice", "ll"]]
+        );
+    }
+
+    #[test]
+    fn test_alias_can_hold_operators_and_keywords() {
+        let aliases = [("lsg", "ls | grep"), ("forever", "while true; do")];
+        assert_eq!(
+            words_with_aliases("lsg rs", &aliases),
+            vec![vec!["ls"], vec!["grep", "rs"]]
+        );
+        let aliases = aliases
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        assert_eq!(
+            parse_input_with_aliases("forever", &aliases),
+            ParseResult::Incomplete
+        );
+    }
+
+    // --- 8. Test Command Substitution ---
+    fn substitution_body(token: &Token) -> (CommandList, bool) {
+        match token {
+            Token::Word(Word(parts)) => match parts.as_slice() {
+                [WordPart::Command { list, quoted }] => (list.clone(), *quoted),
+                other => panic!("Expected a substitution, got {:?}", other),
+            },
+            other => panic!("Expected a word, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tokenize_command_substitution() {
+        let tokens = parse_tokens("echo $(ls -l | wc) \"$(echo \")\")\" `pwd`").unwrap();
+        assert_eq!(tokens.len(), 4);
+
+        let (list, quoted) = substitution_body(&tokens[1]);
+        assert!(!quoted);
+        assert_eq!(list.items[0].first.commands.len(), 2);
+
+        // The `)` inside quotes doesn't end the substitution
+        let (list, quoted) = substitution_body(&tokens[2]);
+        assert!(quoted);
+        assert_eq!(
+            simple(&list.items[0].first.commands[0]).words[1],
+            Word(vec![WordPart::Quoted(")".to_string())])
+        );
+
+        let (list, _) = substitution_body(&tokens[3]);
+        assert_eq!(
+            simple(&list.items[0].first.commands[0]).words,
+            vec![word("pwd")]
+        );
+    }
+
+    #[test]
+    fn test_nested_substitution_keeps_its_quotes() {
+        let tokens = parse_tokens("\"$(echo \"$(echo \"a b\")\")\"").unwrap();
+        let (outer, _) = substitution_body(&tokens[0]);
+        let words = &simple(&outer.items[0].first.commands[0]).words;
+        assert!(matches!(
+            words[1].0.as_slice(),
+            [WordPart::Command { quoted: true, .. }]
+        ));
+        assert!(parse_tokens("echo `echo \\`pwd\\``").is_ok());
+    }
+
+    #[test]
+    fn test_unterminated_substitution_is_incomplete() {
+        assert_eq!(parse_input("echo $(ls"), ParseResult::Incomplete);
+        assert_eq!(parse_input("echo \"$(echo \")"), ParseResult::Incomplete);
+        assert_eq!(parse_input("echo `ls"), ParseResult::Incomplete);
+        assert_eq!(parse_input("echo $(if true; then"), ParseResult::Incomplete);
+        assert!(matches!(parse_input("echo $(ls |)"), ParseResult::Err(_)));
+    }
+
+    // --- 9. Test Here-documents ---
+    fn here_body(input: &str) -> Word {
+        let list = parse(input);
+        match simple(&list.items[0].first.commands[0])
+            .redirects
+            .as_slice()
+        {
+            [Redirect::Here { fd: 0, body }] => body.clone(),
+            other => panic!("Expected one here-document, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_heredoc_body_is_read_after_the_line() {
+        let list = parse("cat <<EOF | wc -l\none\n  two\nEOF\necho done");
+        assert_eq!(list.items.len(), 2);
+        assert_eq!(list.items[0].first.commands.len(), 2);
+        assert_eq!(
+            here_body("cat <<EOF\none\n  two\nEOF").literal_text(),
+            Some("one\n  two\n".to_string())
+        );
+        // Two on one line take their bodies in order
+        let list = parse("cat <<A 2<<B\na\nA\nb\nB");
+        let bodies: Vec<_> = simple(&list.items[0].first.commands[0])
+            .redirects
+            .iter()
+            .map(|redirect| match redirect {
+                Redirect::Here { body, .. } => body.literal_text().unwrap(),
+                other => panic!("Expected a here-document, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(bodies, vec!["a\n", "b\n"]);
+    }
+
+    #[test]
+    fn test_heredoc_expansion_and_quoting() {
+        // Unquoted: `$` expands, `"` stays, `\$` escapes
+        let body = here_body("cat <<EOF\n\"$X\" \\$Y\nEOF");
+        assert_eq!(
+            body.0[1..],
+            [
+                WordPart::Param {
+                    name: "X".to_string(),
+                    quoted: true,
+                    default: None
+                },
+                WordPart::Quoted("\" $Y\n".to_string()),
+            ]
+        );
+        assert_eq!(body.0[0], WordPart::Quoted("\"".to_string()));
+        // Any quote on the delimiter keeps the body as is
+        for input in [
+            "cat <<'EOF'\n$X `ls`\nEOF",
+            "cat <<\"EOF\"\n$X `ls`\nEOF",
+            "cat <<\\EOF\n$X `ls`\nEOF",
+        ] {
+            assert_eq!(
+                here_body(input).literal_text(),
+                Some("$X `ls`\n".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_heredoc_dash_strips_tabs() {
+        assert_eq!(
+            here_body("cat <<-EOF\n\tone\n\t\ttwo\n  three\n\tEOF").literal_text(),
+            Some("one\ntwo\n  three\n".to_string())
+        );
+        // Without the dash an indented delimiter doesn't end the body
+        assert_eq!(parse_input("cat <<EOF\n\tEOF"), ParseResult::Incomplete);
+    }
+
+    #[test]
+    fn test_heredoc_is_incomplete_until_the_delimiter() {
+        assert_eq!(parse_input("cat <<EOF"), ParseResult::Incomplete);
+        assert_eq!(parse_input("cat <<EOF\n"), ParseResult::Incomplete);
+        assert_eq!(
+            parse_input("cat <<EOF\nline\nEOFX"),
+            ParseResult::Incomplete
+        );
+        assert!(matches!(
+            parse_input("cat <<EOF\nline\nEOF\n"),
+            ParseResult::Ok(_)
+        ));
+        assert!(matches!(parse_input("cat <<"), ParseResult::Err(_)));
+        assert!(matches!(parse_input("cat << | wc"), ParseResult::Err(_)));
+    }
+
+    #[test]
+    fn test_here_string_adds_a_newline() {
+        let list = parse("grep x <<< \"a $B\"");
+        let redirects = &simple(&list.items[0].first.commands[0]).redirects;
+        let [Redirect::Here { fd: 0, body }] = redirects.as_slice() else {
+            panic!("Expected a here-string, got {:?}", redirects);
+        };
+        assert_eq!(body.0.last(), Some(&WordPart::Quoted("\n".to_string())));
+        assert!(matches!(parse_input("cat <<<"), ParseResult::Err(_)));
+    }
+
+    // --- 10. Test Arithmetic ---
+    #[test]
+    fn test_tokenize_arithmetic_expansion() {
+        let tokens = parse_tokens("echo $((x + $y * (2 - 1))) \"$((1))\"").unwrap();
+        let Token::Word(Word(parts)) = &tokens[1] else {
+            panic!("Expected a word, got {:?}", tokens[1]);
+        };
+        let [
+            WordPart::Arith {
+                expr,
+                quoted: false,
+            },
+        ] = parts.as_slice()
+        else {
+            panic!("Expected an arithmetic expansion, got {:?}", parts);
+        };
+        // `$y` is expanded before the expression is evaluated
+        assert_eq!(expr.0[0], WordPart::Quoted("x + ".to_string()));
+        assert!(matches!(&expr.0[1], WordPart::Param { name, .. } if name == "y"));
+        assert_eq!(expr.0[2], WordPart::Quoted(" * (2 - 1)".to_string()));
+        assert!(matches!(
+            &tokens[2],
+            Token::Word(Word(parts)) if matches!(parts.as_slice(), [WordPart::Arith { quoted: true, .. }])
+        ));
+    }
+
+    #[test]
+    fn test_parse_arithmetic_command() {
+        assert_eq!(
+            compound("(( x > 3 ))"),
+            Compound::Arith(Word(vec![WordPart::Quoted(" x > 3 ".to_string())]))
+        );
+        let list = parse("if ((x)); then echo y; fi; while (( i < 3 )); do ((i++)); done");
+        assert_eq!(list.items.len(), 2);
+
+        assert_eq!(parse_input("echo $((1 +"), ParseResult::Incomplete);
+        assert_eq!(parse_input("(( (1)"), ParseResult::Incomplete);
+        assert!(matches!(
+            parse_input("echo $((1) + 2)"),
+            ParseResult::Err(_)
+        ));
+    }
+
+    // --- 11. Test Background Jobs ---
+    #[test]
+    fn test_parse_background_lists() {
+        assert_eq!(texts("a & b&&c &"), vec!["a", "&", "b", "&&", "c", "&"]);
+        assert_eq!(texts("x &> f"), vec!["x", "<redirect>", "f"]);
+        assert_eq!(texts("'&' \\&"), vec!["&", "&"]);
+
+        let list = parse("sleep 1 | wc & a && b &\nc");
+        let background: Vec<bool> = list.items.iter().map(|item| item.background).collect();
+        assert_eq!(background, vec![true, true, false]);
+        assert_eq!(list.items[1].rest.len(), 1);
+
+        let list = parse("while true; do sleep 1 & done");
+        let Command::Compound(Compound::While { body, .. }, _) = &list.items[0].first.commands[0]
+        else {
+            panic!("Expected a while loop, got {:?}", list);
+        };
+        assert!(body.items[0].background);
+
+        assert!(matches!(parse_input("& ls"), ParseResult::Err(_)));
+        assert!(matches!(parse_input("ls & ;"), ParseResult::Err(_)));
+        assert!(matches!(parse_input("ls && &"), ParseResult::Err(_)));
+    }
 }
@@ -0,0 +1,291 @@
+use crate::helper::git::GitInfo;
+use crate::helper::print_banner::{GREEN, RESET};
+use crate::helper::state_manager::ShellState;
+use crate::helper::unicode::str_width;
+use chrono::Local;
+
+// --- PS1 / PS2 ---
+// The prompt comes from PS1 (PS2 on continuation lines) with bash's escapes:
+//   \u user        \h host        \H full host name
+//   \w cwd (~ for HOME)           \W last part of the cwd
+//   \$ `#` for root, `$` otherwise
+//   \? status of the last command
+//   \g git branch and status, e.g. " (main ↑1 *)" (see git.rs)
+//   \t 24h time    \T 12h time    \A HH:MM     \@ 12h am/pm    \d date
+//   \n newline     \e escape      \a bell      \0nn octal      \\ backslash
+//   \[ ... \]  wraps text that takes no room on screen (colour codes)
+// Colour codes outside \[ \] are skipped too when measuring, so the cursor
+// lands right either way.
+
+const DEFAULT_PS2: &str = "> ";
+
+fn default_ps1() -> String {
+    format!("\\[{GREEN}\\]\\w$ \\[{RESET}\\]")
+}
+
+pub struct Prompt {
+    pub text: String,
+    // Visible width of each line of the prompt
+    line_widths: Vec<usize>,
+}
+
+impl Prompt {
+    // Column the input starts at
+    pub fn width(&self) -> usize {
+        self.line_widths.last().copied().unwrap_or(0)
+    }
+
+    // Rows taken by the lines before the last one (a multi-line PS1)
+    pub fn rows_above(&self, cols: usize) -> usize {
+        let cols = cols.max(1);
+        let above = &self.line_widths[..self.line_widths.len().saturating_sub(1)];
+        above.iter().map(|w| w.div_ceil(cols).max(1)).sum()
+    }
+}
+
+// Width once escape sequences (CSI `ESC [ ... letter`, OSC `ESC ] ... BEL`)
+// are taken out
+fn visible_width(s: &str) -> usize {
+    let mut plain = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            plain.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() || c == '~' {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    str_width(&plain)
+}
+
+fn user_name(shell: &ShellState) -> String {
+    if let Some(user) = shell.vars.get("USER") {
+        return user.to_string();
+    }
+    users::get_current_username()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn host_name() -> String {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+        return String::new();
+    }
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).into_owned()
+}
+
+fn prompt_sign() -> &'static str {
+    if unsafe { libc::geteuid() } == 0 {
+        "#"
+    } else {
+        "$"
+    }
+}
+
+// HOME (or anything under it) shows as `~`
+fn tilde_dir(dir: &str, home: Option<&str>) -> String {
+    match home {
+        Some(home) if !home.is_empty() && home != "/" => match dir.strip_prefix(home) {
+            Some("") => "~".to_string(),
+            Some(rest) if rest.starts_with('/') => format!("~{}", rest),
+            _ => dir.to_string(),
+        },
+        _ => dir.to_string(),
+    }
+}
+
+fn expand(template: &str, shell: &ShellState) -> Prompt {
+    let mut text = String::new();
+    // Printing part of the current line, for measuring
+    let mut visible = String::new();
+    let mut line_widths = Vec::new();
+    let mut hidden = false;
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let piece = if c != '\\' {
+            c.to_string()
+        } else {
+            let Some(escape) = chars.next() else {
+                text.push('\\');
+                visible.push('\\');
+                break;
+            };
+            let now = Local::now();
+            let home = shell.vars.get("HOME");
+            match escape {
+                'u' => user_name(shell),
+                'h' => host_name().split('.').next().unwrap_or("").to_string(),
+                'H' => host_name(),
+                'w' => tilde_dir(&shell.pwd.get_current_dir(), home),
+                'W' => {
+                    let dir = tilde_dir(&shell.pwd.get_current_dir(), home);
+                    match dir.rsplit_once('/') {
+                        Some((_, "")) | None => dir,
+                        Some((_, last)) => last.to_string(),
+                    }
+                }
+                '$' => prompt_sign().to_string(),
+                '?' => shell.last_status.to_string(),
+                'g' => shell
+                    .git
+                    .info_for(&shell.pwd.get_current_dir())
+                    .map(GitInfo::segment)
+                    .unwrap_or_default(),
+                't' => now.format("%H:%M:%S").to_string(),
+                'T' => now.format("%I:%M:%S").to_string(),
+                'A' => now.format("%H:%M").to_string(),
+                '@' => now.format("%I:%M %p").to_string(),
+                'd' => now.format("%a %b %d").to_string(),
+                'n' => "\n".to_string(),
+                'e' => "\x1b".to_string(),
+                'a' => "\x07".to_string(),
+                '\\' => "\\".to_string(),
+                '0' => {
+                    let mut code = 0u32;
+                    for _ in 0..2 {
+                        match chars.peek().and_then(|d| d.to_digit(8)) {
+                            Some(d) => {
+                                code = code * 8 + d;
+                                chars.next();
+                            }
+                            None => break,
+                        }
+                    }
+                    char::from_u32(code).unwrap_or('\0').to_string()
+                }
+                '[' => {
+                    hidden = true;
+                    continue;
+                }
+                ']' => {
+                    hidden = false;
+                    continue;
+                }
+                other => format!("\\{}", other),
+            }
+        };
+
+        for c in piece.chars() {
+            text.push(c);
+            if c == '\n' {
+                line_widths.push(visible_width(&visible));
+                visible.clear();
+            } else if !hidden {
+                visible.push(c);
+            }
+        }
+    }
+    line_widths.push(visible_width(&visible));
+    Prompt { text, line_widths }
+}
+
+// Reading .git is only worth it when PS1 shows it
+pub fn refresh_git(shell: &mut ShellState) {
+    if shell.vars.get("PS1").is_some_and(|ps1| ps1.contains("\\g")) {
+        let dir = shell.pwd.get_current_dir();
+        shell.git.refresh(&dir);
+    }
+}
+
+// PS2 on continuation lines, PS1 otherwise
+pub fn build_prompt(shell: &ShellState) -> Prompt {
+    let template = if shell.is_continuation {
+        shell.vars.get("PS2").unwrap_or(DEFAULT_PS2).to_string()
+    } else {
+        shell
+            .vars
+            .get("PS1")
+            .map(str::to_string)
+            .unwrap_or_else(default_ps1)
+    };
+    expand(&template, shell)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::pwd::PwdState;
+
+    fn shell_in(dir: &str) -> ShellState {
+        let mut shell = ShellState::new();
+        shell.pwd = PwdState::new(dir.to_string(), dir.to_string());
+        shell.vars.set("HOME", "/home/me".to_string());
+        shell.vars.set("USER", "me".to_string());
+        shell
+    }
+
+    fn expand_str(template: &str, shell: &ShellState) -> String {
+        expand(template, shell).text
+    }
+
+    #[test]
+    fn test_cwd_and_user_escapes() {
+        let shell = shell_in("/home/me/src/0-shell");
+        assert_eq!(expand_str("\\u:\\w", &shell), "me:~/src/0-shell");
+        assert_eq!(expand_str("\\W", &shell), "0-shell");
+        assert_eq!(expand_str("\\w", &shell_in("/home/me")), "~");
+        assert_eq!(expand_str("\\W", &shell_in("/home/me")), "~");
+        assert_eq!(expand_str("\\w", &shell_in("/home/meta")), "/home/meta");
+        assert_eq!(expand_str("\\W", &shell_in("/")), "/");
+    }
+
+    #[test]
+    fn test_status_and_literal_escapes() {
+        let mut shell = shell_in("/tmp");
+        shell.last_status = 127;
+        assert_eq!(expand_str("[\\?] \\\\ \\q", &shell), "[127] \\ \\q");
+        assert_eq!(expand_str("\\033[1m", &shell), "\x1b[1m");
+        assert_eq!(expand_str("\\A", &shell).len(), 5);
+    }
+
+    #[test]
+    fn test_width_skips_colours() {
+        let shell = shell_in("/tmp");
+        // Marked with \[ \]
+        let prompt = expand("\\[\\e[32m\\]\\w\\[\\e[0m\\]$ ", &shell);
+        assert_eq!(prompt.text, "\x1b[32m/tmp\x1b[0m$ ");
+        assert_eq!(prompt.width(), 6);
+        // Unmarked codes are measured as nothing as well
+        assert_eq!(expand("\\e[1;31m>\\e[0m ", &shell).width(), 2);
+        // The default keeps the old look: green cwd then `$ `
+        assert_eq!(expand(&default_ps1(), &shell).width(), 6);
+    }
+
+    #[test]
+    fn test_multiline_prompt() {
+        let shell = shell_in("/tmp");
+        let prompt = expand("\\w is a long first line\\n> ", &shell);
+        assert_eq!(prompt.width(), 2);
+        assert_eq!(prompt.rows_above(80), 1);
+        assert_eq!(prompt.rows_above(10), 3);
+        assert_eq!(expand("$ ", &shell).rows_above(80), 0);
+    }
+
+    #[test]
+    fn test_ps2_on_continuation() {
+        let mut shell = shell_in("/tmp");
+        shell.is_continuation = true;
+        assert_eq!(build_prompt(&shell).text, "> ");
+        shell.vars.set("PS2", "... ".to_string());
+        assert_eq!(build_prompt(&shell).width(), 4);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::helper::executor::execute;
+use crate::helper::parser::{ParseResult, parse_input_with_aliases};
+use crate::helper::state_manager::ShellState;
+use std::{fs, io, path::Path};
+
+// --- Running Scripts ---
+// The rc file and `source` feed their lines through the same
+// parse_input/execute path as typed input. Lines are collected until they
+// parse, so quotes and `\` continuations may span lines like at the prompt.
+// A syntax error skips what was collected and carries on with the next line.
+// Returns the status of the last command run (0 if none was).
+pub fn run_script(text: &str, origin: &str, shell: &mut ShellState) -> i32 {
+    run_lines(text.lines().map(str::to_string), origin, shell)
+}
+
+fn run_lines(lines: impl Iterator<Item = String>, origin: &str, shell: &mut ShellState) -> i32 {
+    let mut pending = String::new();
+    let mut first_line = 0;
+    let mut status = 0;
+
+    for (n, line) in lines.enumerate() {
+        if pending.is_empty() {
+            first_line = n + 1;
+        } else {
+            pending.push('\n');
+        }
+        pending.push_str(&line);
+
+        match parse_input_with_aliases(&pending, &shell.aliases) {
+            ParseResult::Ok(list) => {
+                if !list.items.is_empty() {
+                    status = execute(&list, shell);
+                }
+            }
+            ParseResult::Incomplete => continue,
+            ParseResult::Err(e) => {
+                eprintln!("{}: line {}: {}", origin, first_line, e);
+                status = 2;
+                shell.last_status = status;
+            }
+        }
+        pending.clear();
+        if shell.unwinding() {
+            return status;
+        }
+    }
+
+    if !pending.trim().is_empty() {
+        eprintln!("{}: line {}: unexpected end of file", origin, first_line);
+        status = 2;
+        shell.last_status = status;
+    }
+    status
+}
+
+// ~/.0shellrc, if there is one (skipped with --norc)
+pub fn run_rc_file(shell: &mut ShellState) {
+    let Some(home) = shell.vars.get("HOME") else {
+        return;
+    };
+    let path = Path::new(home).join(".0shellrc");
+    if let Ok(text) = fs::read_to_string(&path) {
+        run_script(&text, &path.display().to_string(), shell);
+    }
+}
+
+// --- Non-interactive Mode ---
+// `0-shell -c 'cmd'`, `0-shell script.sh` and `cmd | 0-shell` run without
+// the banner, raw mode or line editor and exit with the last status.
+#[derive(Debug, PartialEq)]
+pub enum Invocation {
+    Interactive { norc: bool },
+    Command(String),
+    // The script and the arguments after it (`$1`...)
+    File(String, Vec<String>),
+    Stdin,
+}
+
+pub fn parse_args(args: &[String], stdin_is_tty: bool) -> Result<Invocation, String> {
+    let mut norc = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--norc" => norc = true,
+            "-c" => {
+                return match args.next() {
+                    Some(text) => Ok(Invocation::Command(text.clone())),
+                    None => Err("-c: option requires an argument".to_string()),
+                };
+            }
+            "--" => break,
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("{}: invalid option", flag));
+            }
+            path => return Ok(Invocation::File(path.to_string(), args.cloned().collect())),
+        }
+    }
+    // Anything after `--` is a script name too
+    if let Some(path) = args.next() {
+        return Ok(Invocation::File(path.clone(), args.cloned().collect()));
+    }
+    Ok(if stdin_is_tty {
+        Invocation::Interactive { norc }
+    } else {
+        Invocation::Stdin
+    })
+}
+
+// Lines read a byte at a time straight from fd 0, so a command that reads
+// stdin gets the rest of it: `printf 'cat\nhi\n' | 0-shell` prints hi
+struct StdinLines;
+
+impl Iterator for StdinLines {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let mut line = Vec::new();
+        let mut byte = 0u8;
+        loop {
+            match unsafe { libc::read(0, &mut byte as *mut u8 as *mut libc::c_void, 1) } {
+                1 if byte == b'\n' => break,
+                1 => line.push(byte),
+                0 if line.is_empty() => return None,
+                0 => break,
+                _ if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => {}
+                _ => return None,
+            }
+        }
+        Some(String::from_utf8_lossy(&line).into_owned())
+    }
+}
+
+// Returns the code the process should exit with
+pub fn run_non_interactive(invocation: Invocation, shell: &mut ShellState) -> i32 {
+    let status = match invocation {
+        Invocation::Interactive { .. } => return 0,
+        Invocation::Command(text) => run_script(&text, "-c", shell),
+        Invocation::File(path, args) => {
+            let full = Path::new(&shell.pwd.get_current_dir()).join(&path);
+            match fs::read_to_string(full) {
+                Ok(text) => {
+                    shell.positional = args;
+                    shell.script_name = Some(path.clone());
+                    run_script(&text, &path, shell)
+                }
+                Err(e) => {
+                    eprintln!("0-shell: {}: {}", path, e);
+                    return 127;
+                }
+            }
+        }
+        Invocation::Stdin => run_lines(StdinLines, "stdin", shell),
+    };
+    shell.exit_code.unwrap_or(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::pwd::PwdState;
+
+    fn test_shell() -> ShellState {
+        let mut shell = ShellState::new();
+        shell.pwd = PwdState::new("/tmp".to_string(), "/".to_string());
+        shell
+    }
+
+    #[test]
+    fn test_lines_share_state_and_status() {
+        let mut shell = test_shell();
+        let status = run_script("A=1\n\nB=\"$A 2\"\nfalse", "test", &mut shell);
+        assert_eq!(shell.vars.get("B"), Some("1 2"));
+        assert_eq!(status, 1);
+        assert_eq!(run_script("# only a comment\n", "test", &mut shell), 0);
+    }
+
+    #[test]
+    fn test_command_spanning_lines() {
+        let mut shell = test_shell();
+        run_script("X='one\ntwo'\nY=after", "test", &mut shell);
+        assert_eq!(shell.vars.get("X"), Some("one\ntwo"));
+        assert_eq!(shell.vars.get("Y"), Some("after"));
+    }
+
+    #[test]
+    fn test_syntax_error_skips_to_next_line() {
+        let mut shell = test_shell();
+        let status = run_script("A=1\n| oops\nB=2", "test", &mut shell);
+        assert_eq!(shell.vars.get("B"), Some("2"));
+        assert_eq!(status, 0);
+
+        assert_eq!(run_script("X='never closed", "test", &mut shell), 2);
+        assert_eq!(shell.vars.get("X"), None);
+    }
+
+    #[test]
+    fn test_exit_stops_the_script() {
+        let mut shell = test_shell();
+        let status = run_script("exit 3\nA=1", "test", &mut shell);
+        assert_eq!((status, shell.exit_code), (3, Some(3)));
+        assert_eq!(shell.vars.get("A"), None);
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            parse_args(&args(&[]), true),
+            Ok(Invocation::Interactive { norc: false })
+        );
+        assert_eq!(
+            parse_args(&args(&["--norc"]), true),
+            Ok(Invocation::Interactive { norc: true })
+        );
+        assert_eq!(parse_args(&args(&[]), false), Ok(Invocation::Stdin));
+        assert_eq!(
+            parse_args(&args(&["-c", "ls -l"]), true),
+            Ok(Invocation::Command("ls -l".to_string()))
+        );
+        assert_eq!(
+            parse_args(&args(&["--norc", "build.sh", "x", "-c"]), true),
+            Ok(Invocation::File("build.sh".to_string(), args(&["x", "-c"])))
+        );
+        assert_eq!(
+            parse_args(&args(&["--", "-odd-name", "y"]), true),
+            Ok(Invocation::File("-odd-name".to_string(), args(&["y"])))
+        );
+        assert!(parse_args(&args(&["-c"]), true).is_err());
+        assert!(parse_args(&args(&["-x"]), true).is_err());
+    }
+
+    #[test]
+    fn test_non_interactive_exit_codes() {
+        let mut shell = test_shell();
+        let run = |text: &str, shell: &mut ShellState| {
+            run_non_interactive(Invocation::Command(text.to_string()), shell)
+        };
+        assert_eq!(run("true; false", &mut shell), 1);
+        assert_eq!(run("exit 4; true", &mut shell), 4);
+
+        let dir = std::env::temp_dir().join(format!("0shell-script-args-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("args.sh"), "N=$#; ONE=$1; ALL=\"$@\"; ME=$0\n").unwrap();
+        let mut shell = test_shell();
+        shell.pwd = PwdState::new(dir.display().to_string(), "/".to_string());
+        let script = Invocation::File("args.sh".to_string(), args(&["a", "b c"]));
+        assert_eq!(run_non_interactive(script, &mut shell), 0);
+        assert_eq!(shell.vars.get("N"), Some("2"));
+        assert_eq!(shell.vars.get("ONE"), Some("a"));
+        assert_eq!(shell.vars.get("ALL"), Some("a b c"));
+        assert_eq!(shell.vars.get("ME"), Some("args.sh"));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut shell = test_shell();
+        let missing = Invocation::File("no-such-script-0shell.sh".to_string(), Vec::new());
+        assert_eq!(run_non_interactive(missing, &mut shell), 127);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::helper::line_editor;
+use crate::helper::state_manager::ShellState;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// --- Reverse-i-search (Ctrl-R) ---
+// While `shell.search` is Some, keys go to on_search_key first.
+// Typing narrows the query, Ctrl-R steps to older matches, Enter and the
+// arrow keys keep the match as the new line, Esc/Ctrl-G/Ctrl-C give up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistorySearch {
+    pub query: String,
+    // Index into history of the entry shown
+    pub found: Option<usize>,
+    // The last Ctrl-R or keystroke had no (older) match
+    pub failing: bool,
+    // Line to put back if the search is cancelled (only the one being
+    // typed: the lines before it on a continuation prompt are kept)
+    original: String,
+}
+
+// Newest entry at or before `from` that contains `query`
+fn find_back(history: &[String], query: &str, from: usize) -> Option<usize> {
+    if query.is_empty() || history.is_empty() {
+        return None;
+    }
+    let from = from.min(history.len() - 1);
+    (0..=from).rev().find(|&i| history[i].contains(query))
+}
+
+pub fn start_search(shell: &mut ShellState) {
+    shell.search = Some(HistorySearch {
+        query: String::new(),
+        found: None,
+        failing: false,
+        original: shell.view_buffer.clone(),
+    });
+}
+
+fn set_line(shell: &mut ShellState, line: String) {
+    let chars: Vec<char> = line.chars().collect();
+    let end = chars.len();
+    line_editor::set_line(shell, chars, end);
+}
+
+fn show_entry(shell: &mut ShellState, index: Option<usize>) {
+    if let Some(i) = index {
+        set_line(shell, shell.history[i].clone());
+        shell.hist_idx = i;
+    }
+}
+
+// Returns true when the key was used up by the search. On false the search
+// is over and main should still handle the key (Enter runs the line).
+pub fn on_search_key(shell: &mut ShellState, key: KeyEvent) -> bool {
+    let Some(mut search) = shell.search.take() else {
+        return false;
+    };
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let newest = shell.history.len().saturating_sub(1);
+
+    match key.code {
+        KeyCode::Char('r') if ctrl => {
+            let older = match search.found {
+                Some(0) => None,
+                Some(i) => find_back(&shell.history, &search.query, i - 1),
+                None => find_back(&shell.history, &search.query, newest),
+            };
+            search.failing = older.is_none() && !search.query.is_empty();
+            search.found = older.or(search.found);
+        }
+        KeyCode::Char('g') | KeyCode::Char('c') if ctrl => {
+            set_line(shell, search.original);
+            return true;
+        }
+        KeyCode::Esc => {
+            set_line(shell, search.original);
+            return true;
+        }
+        KeyCode::Char(c) if !ctrl => {
+            search.query.push(c);
+            // The current match stays if it still fits the longer query
+            let from = search.found.unwrap_or(newest);
+            let found = find_back(&shell.history, &search.query, from);
+            search.failing = found.is_none();
+            search.found = found.or(search.found);
+        }
+        KeyCode::Backspace => {
+            search.query.pop();
+            search.found = find_back(&shell.history, &search.query, newest);
+            search.failing = false;
+        }
+        KeyCode::Enter | KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down => {
+            show_entry(shell, search.found);
+            return false;
+        }
+        _ => {}
+    }
+
+    show_entry(shell, search.found);
+    shell.search = Some(search);
+    true
+}
+
+// (prompt, char offset of the cursor inside the line shown after it)
+pub fn search_prompt(search: &HistorySearch, line: &str) -> (String, usize) {
+    let label = if search.failing {
+        "(failing reverse-i-search)"
+    } else {
+        "(reverse-i-search)"
+    };
+    let prompt = format!("{}`{}': ", label, search.query);
+    let offset = line
+        .find(&search.query)
+        .filter(|_| search.found.is_some())
+        .map(|byte| line[..byte].chars().count())
+        .unwrap_or(0);
+    (prompt, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell_with_history(entries: &[&str]) -> ShellState {
+        let mut shell = ShellState::new();
+        shell.history = entries.iter().map(|e| e.to_string()).collect();
+        shell.hist_idx = shell.history.len();
+        shell
+    }
+
+    fn press(shell: &mut ShellState, code: KeyCode) -> bool {
+        on_search_key(shell, KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn ctrl(shell: &mut ShellState, c: char) -> bool {
+        on_search_key(
+            shell,
+            KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL),
+        )
+    }
+
+    fn type_query(shell: &mut ShellState, text: &str) {
+        for c in text.chars() {
+            assert!(press(shell, KeyCode::Char(c)));
+        }
+    }
+
+    #[test]
+    fn test_typing_finds_newest_match() {
+        let mut shell = shell_with_history(&["git status", "ls", "git commit", "pwd"]);
+        start_search(&mut shell);
+        type_query(&mut shell, "git");
+        assert_eq!(shell.view_buffer, "git commit");
+
+        type_query(&mut shell, " s");
+        assert_eq!(shell.view_buffer, "git status");
+    }
+
+    #[test]
+    fn test_ctrl_r_cycles_older_and_reports_failure() {
+        let mut shell = shell_with_history(&["echo 1", "ls", "echo 2", "echo 3"]);
+        start_search(&mut shell);
+        type_query(&mut shell, "echo");
+        assert_eq!(shell.view_buffer, "echo 3");
+
+        ctrl(&mut shell, 'r');
+        assert_eq!(shell.view_buffer, "echo 2");
+        ctrl(&mut shell, 'r');
+        assert_eq!(shell.view_buffer, "echo 1");
+
+        // Nothing older: stay on the oldest match and say so
+        ctrl(&mut shell, 'r');
+        assert_eq!(shell.view_buffer, "echo 1");
+        assert!(shell.search.as_ref().unwrap().failing);
+    }
+
+    #[test]
+    fn test_enter_accepts_and_escape_restores() {
+        let mut shell = shell_with_history(&["make test", "ls"]);
+        start_search(&mut shell);
+        type_query(&mut shell, "make");
+        assert!(!press(&mut shell, KeyCode::Enter));
+        assert!(shell.search.is_none());
+        assert_eq!(shell.buffer, "make test");
+
+        shell.buffer = "half typed".to_string();
+        shell.view_buffer = shell.buffer.clone();
+        start_search(&mut shell);
+        type_query(&mut shell, "ls");
+        assert!(press(&mut shell, KeyCode::Esc));
+        assert!(shell.search.is_none());
+        assert_eq!(shell.buffer, "half typed");
+    }
+
+    #[test]
+    fn test_search_prompt() {
+        let mut shell = shell_with_history(&["cargo build"]);
+        start_search(&mut shell);
+        type_query(&mut shell, "build");
+        let search = shell.search.as_ref().unwrap();
+        assert_eq!(
+            search_prompt(search, &shell.view_buffer),
+            ("(reverse-i-search)`build': ".to_string(), 6)
+        );
+    }
+
+    #[test]
+    fn test_continuation_lines_are_kept() {
+        let mut shell = shell_with_history(&["echo hi", "ls"]);
+        shell.buffer = "for i in 1 2\ndo ".to_string();
+        shell.view_buffer = "do ".to_string();
+        shell.is_continuation = true;
+
+        start_search(&mut shell);
+        type_query(&mut shell, "echo");
+        assert_eq!(shell.buffer, "for i in 1 2\necho hi");
+        assert!(press(&mut shell, KeyCode::Esc));
+        assert_eq!(shell.buffer, "for i in 1 2\ndo ");
+        assert_eq!(shell.view_buffer, "do ");
+
+        start_search(&mut shell);
+        type_query(&mut shell, "ls");
+        assert!(!press(&mut shell, KeyCode::Enter));
+        assert_eq!(shell.buffer, "for i in 1 2\nls");
+        assert_eq!(shell.view_buffer, "ls");
+    }
+}
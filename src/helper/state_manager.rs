@@ -1,6 +1,21 @@
 use crate::command::pwd::PwdState;
+use crate::helper::completion::CompletionMenu;
+use crate::helper::git::GitCache;
+use crate::helper::history::{self, HistoryConfig};
+use crate::helper::jobs::JobTable;
+use crate::helper::line_editor::KillRing;
+use crate::helper::parser::Function;
+use crate::helper::search::HistorySearch;
+use crate::helper::ui::get_byte_index;
+use crate::helper::variables::Variables;
+use crate::helper::vi_mode::ViState;
 use crossterm::terminal::disable_raw_mode;
-use std::{env, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    path::PathBuf,
+    rc::Rc,
+};
 
 // --- Helper Macro ---
 #[macro_export]
@@ -20,6 +35,16 @@ impl Drop for RawModeGuard {
     }
 }
 
+// --- Shell Options ---
+// Toggled with `set -o name` / `set +o name`
+#[derive(Debug, Clone, Default)]
+pub struct ShellOptions {
+    // A glob that matches nothing is an error instead of staying literal
+    pub failglob: bool,
+    // Vi keybindings instead of emacs ones (`set -o vi`)
+    pub vi: bool,
+}
+
 // --- State Management ---
 pub struct ShellState {
     pub history: Vec<String>,
@@ -29,6 +54,37 @@ pub struct ShellState {
     pub is_continuation: bool,
     pub pwd: PwdState,
     pub cursor_idx: usize,
+    pub last_status: i32,
+    // Set by the `exit` builtin; main stops the REPL when it sees it
+    pub exit_code: Option<i32>,
+    pub vars: Variables,
+    pub options: ShellOptions,
+    // Candidates listed under the prompt; any key but Tab drops them
+    pub completion: Option<CompletionMenu>,
+    // Where commit_to_history appends; None keeps history in memory only
+    pub history_file: Option<PathBuf>,
+    // Some while Ctrl-R is active
+    pub search: Option<HistorySearch>,
+    pub kill_ring: KillRing,
+    pub vi: ViState,
+    // Branch/status for `\g` in PS1, kept for the cwd between commands
+    pub git: GitCache,
+    // Defined with `name() { ...; }`, looked up before builtins
+    pub functions: HashMap<String, Rc<Function>>,
+    // $1..$n of the running function (or script)
+    pub positional: Vec<String>,
+    // $0 when running a script file; the shell's own name otherwise
+    pub script_name: Option<String>,
+    // Functions and sourced files being run; `return` needs one
+    pub call_depth: usize,
+    // Set by `return` until the function or sourced file is left
+    pub returning: bool,
+    // `alias name=value`, swapped in by the parser
+    pub aliases: BTreeMap<String, String>,
+    // Status of the last `$(...)`, which `x=$(cmd)` reports as its own
+    pub substitution_status: Option<i32>,
+    // Background and stopped jobs, and the terminal when job control is on
+    pub jobs: JobTable,
 }
 
 // FIX 1: Add Default implementation
@@ -54,19 +110,106 @@ impl ShellState {
             is_continuation: false,
             pwd: PwdState::new(path_str.clone(), path_str),
             cursor_idx: 0,
+            last_status: 0,
+            exit_code: None,
+            vars: Variables::from_env(),
+            options: ShellOptions::default(),
+            completion: None,
+            history_file: None,
+            search: None,
+            kill_ring: KillRing::default(),
+            vi: ViState::default(),
+            git: GitCache::default(),
+            functions: HashMap::new(),
+            positional: Vec::new(),
+            script_name: None,
+            call_depth: 0,
+            returning: false,
+            aliases: BTreeMap::new(),
+            substitution_status: None,
+            jobs: JobTable::default(),
+        }
+    }
+
+    // What a `$(...)` runs in: a copy of everything a command can change,
+    // so nothing it does reaches this shell
+    pub fn subshell(&self) -> ShellState {
+        ShellState {
+            pwd: self.pwd.clone(),
+            last_status: self.last_status,
+            vars: self.vars.clone(),
+            options: self.options.clone(),
+            functions: self.functions.clone(),
+            positional: self.positional.clone(),
+            script_name: self.script_name.clone(),
+            call_depth: self.call_depth,
+            aliases: self.aliases.clone(),
+            ..ShellState::new()
         }
     }
 
+    // `exit` or `return` is on its way out: nothing more runs until it's handled
+    pub fn unwinding(&self) -> bool {
+        self.exit_code.is_some() || self.returning
+    }
+
     pub fn reset_buffers(&mut self) {
         self.buffer.clear();
         self.view_buffer.clear();
         self.cursor_idx = 0;
+        self.completion = None;
+    }
+
+    // Replaces chars start..end of the current line (in both buffers) and
+    // leaves the cursor right after the new text
+    pub fn replace_view_range(&mut self, start: usize, end: usize, text: &str) {
+        let view_start = get_byte_index(&self.view_buffer, start);
+        let view_end = get_byte_index(&self.view_buffer, end);
+        let buffer_base_len = self.buffer.len() - self.view_buffer.len();
+
+        self.buffer.replace_range(
+            buffer_base_len + view_start..buffer_base_len + view_end,
+            text,
+        );
+        self.view_buffer.replace_range(view_start..view_end, text);
+        self.cursor_idx = start + text.chars().count();
+    }
+
+    // Reads the history file named by HISTFILE (or ~/.0shell_history)
+    // and keeps appending to it from now on
+    pub fn load_history(&mut self) {
+        let Some(path) = history::history_path(&self.vars) else {
+            return;
+        };
+        match history::load(&path, &HistoryConfig::from_vars(&self.vars)) {
+            Ok(entries) => self.history = entries,
+            Err(e) => eprintln!("\r\n[Warning] History read error: {}", e),
+        }
+        self.hist_idx = self.history.len();
+        self.history_file = Some(path);
     }
 
     pub fn commit_to_history(&mut self) {
-        // FIX 2: Collapsed "if" statements
-        if !self.buffer.trim().is_empty() && self.history.last() != Some(&self.buffer) {
-            self.history.push(self.buffer.clone());
+        let config = HistoryConfig::from_vars(&self.vars);
+        let entry = self.buffer.clone();
+        let ignored = entry.trim().is_empty()
+            || self.history.last() == Some(&entry)
+            || (config.ignore_space && entry.starts_with(' '));
+
+        if !ignored {
+            if config.erase_dups {
+                self.history.retain(|old| *old != entry);
+            }
+            self.history.push(entry.clone());
+            let excess = self.history.len().saturating_sub(config.max_size);
+            self.history.drain(..excess);
+
+            if let Some(path) = &self.history_file {
+                try_log!(
+                    history::append(path, &entry, &config),
+                    "History write error"
+                );
+            }
         }
         self.hist_idx = self.history.len();
     }
@@ -87,6 +230,24 @@ mod tests {
             // Mocking PwdState for the test
             pwd: PwdState::new("/".to_string(), "/".to_string()),
             cursor_idx: 0,
+            last_status: 0,
+            exit_code: None,
+            vars: Variables::default(),
+            options: ShellOptions::default(),
+            completion: None,
+            history_file: None,
+            search: None,
+            kill_ring: KillRing::default(),
+            vi: ViState::default(),
+            git: GitCache::default(),
+            functions: HashMap::new(),
+            positional: Vec::new(),
+            script_name: None,
+            call_depth: 0,
+            returning: false,
+            aliases: BTreeMap::new(),
+            substitution_status: None,
+            jobs: JobTable::default(),
         }
     }
 
@@ -145,4 +306,39 @@ mod tests {
         // History should still only have 1 item
         assert_eq!(state.history.len(), 1);
     }
+
+    #[test]
+    fn test_commit_to_history_policies() {
+        let mut state = create_test_state();
+        state
+            .vars
+            .set("HISTCONTROL", "ignorespace:erasedups".to_string());
+        state.vars.set("HISTSIZE", "3".to_string());
+
+        for line in ["ls", "pwd", " secret", "ls", "echo a", "echo b"] {
+            state.buffer = line.to_string();
+            state.commit_to_history();
+        }
+
+        assert_eq!(state.history, vec!["ls", "echo a", "echo b"]);
+        assert_eq!(state.hist_idx, 3);
+    }
+
+    #[test]
+    fn test_history_survives_restart() {
+        let path = std::env::temp_dir().join(format!("0shell-restart-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut first = create_test_state();
+        first.vars.set("HISTFILE", path.display().to_string());
+        first.load_history();
+        first.buffer = "echo from the first session".to_string();
+        first.commit_to_history();
+
+        let mut second = create_test_state();
+        second.vars.set("HISTFILE", path.display().to_string());
+        second.load_history();
+        assert_eq!(second.history, vec!["echo from the first session"]);
+        let _ = std::fs::remove_file(path);
+    }
 }
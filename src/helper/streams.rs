@@ -0,0 +1,12 @@
+use std::io::{Read, Write};
+
+// --- Builtin I/O ---
+// Builtins never touch stdout() directly so the executor can point them
+// at the terminal, a pipe buffer or a file.
+pub struct Streams<'a> {
+    // None means "the terminal": commands like cat fall back to raw keystrokes
+    pub stdin: Option<&'a mut dyn Read>,
+    pub stdout: &'a mut dyn Write,
+    pub stderr: &'a mut dyn Write,
+    pub stdout_is_tty: bool,
+}
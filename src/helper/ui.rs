@@ -1,12 +1,17 @@
-use crate::helper::print_banner::{GREEN, RESET};
+use crate::helper::completion::format_menu;
+use crate::helper::prompt::Prompt;
+use crate::helper::search::search_prompt;
 use crate::helper::state_manager::ShellState;
+use crate::helper::unicode::{grapheme_width, str_width};
+use crate::helper::vi_mode::ViMode;
 use crate::try_log;
 use crossterm::{
-    cursor::MoveTo,
+    cursor::{MoveTo, SetCursorStyle},
     execute,
     terminal::{Clear, ClearType, size},
 };
 use std::io::{Write, stdout};
+use unicode_segmentation::UnicodeSegmentation;
 
 // 🛠️ HELPER: Convert Character Index -> Byte Index
 // (Now public so main can use it if needed, or kept private if only used here)
@@ -14,9 +19,131 @@ pub fn get_byte_index(s: &str, char_idx: usize) -> usize {
     s.chars().take(char_idx).map(|c| c.len_utf8()).sum()
 }
 
+// A pty nobody sized (e.g. under `script`) reports 0x0
+fn terminal_size() -> (u16, u16) {
+    match size() {
+        Ok((cols, rows)) if cols > 0 && rows > 0 => (cols, rows),
+        _ => (80, 24),
+    }
+}
+
+// --- Wrapping ---
+// Where the input lands once long lines wrap at the terminal edge. Rows and
+// the cursor's (row, col) are relative to the prompt's row. A line that ends
+// exactly at the edge gets one more row: print_wrapped moves onto it, so the
+// cursor always has a cell to sit in and the row count never guesses.
+#[derive(Debug, PartialEq)]
+pub struct Layout {
+    pub rows: usize,
+    pub cursor: (usize, usize),
+}
+
+// (row, col) of the first `upto` chars of a line that starts at column
+// `start`. A wide char that does not fit at the edge goes to the next row,
+// like the terminal puts it, and the cursor in front of it follows.
+fn position(start: usize, line: &str, upto: usize, cols: usize) -> (usize, usize) {
+    let (mut row, mut col) = (start / cols, start % cols);
+    let mut seen = 0;
+    for g in line.graphemes(true) {
+        let width = grapheme_width(g);
+        if col + width > cols {
+            row += 1;
+            col = 0;
+        }
+        if seen >= upto {
+            break;
+        }
+        col += width;
+        if col >= cols {
+            row += 1;
+            col = 0;
+        }
+        seen += g.chars().count();
+    }
+    (row, col)
+}
+
+pub fn layout(prompt_len: usize, text: &str, cursor: usize, cols: usize) -> Layout {
+    let cols = cols.max(1);
+    let mut rows = 0;
+    let mut cursor_at = (0, 0);
+    let mut seen = 0;
+    for (i, line) in text.split('\n').enumerate() {
+        let start = if i == 0 { prompt_len } else { 0 };
+        let len = line.chars().count();
+        if (seen..=seen + len).contains(&cursor) {
+            let (row, col) = position(start, line, cursor - seen, cols);
+            cursor_at = (rows + row, col);
+        }
+        rows += position(start, line, len, cols).0 + 1;
+        seen += len + 1;
+    }
+    Layout {
+        rows,
+        cursor: cursor_at,
+    }
+}
+
+// Prints the text after an already printed prompt, matching `layout`
+fn print_wrapped(prompt_len: usize, text: &str, cols: usize) {
+    let cols = cols.max(1);
+    for (i, line) in text.split('\n').enumerate() {
+        let start = if i == 0 {
+            prompt_len
+        } else {
+            print!("\r\n");
+            0
+        };
+        print!("{}", line);
+        // The terminal holds the cursor on the last column until the next
+        // char; stepping down by hand makes the extra row real
+        let (_, end_col) = position(start, line, usize::MAX, cols);
+        if end_col == 0 && (start > 0 || !line.is_empty()) {
+            print!("\n\r");
+        }
+    }
+}
+
+// Text (plus any lines under it), then scroll correction and the cursor.
+// `rows_above` are prompt rows printed before the one the text starts on.
+fn draw(
+    rows_above: usize,
+    prompt_len: usize,
+    text: &str,
+    cursor: usize,
+    below: &[String],
+    start_y: &mut u16,
+) {
+    let (term_cols, term_rows) = terminal_size();
+    print_wrapped(prompt_len, text, term_cols as usize);
+    for line in below {
+        print!("\r\n{}", line);
+    }
+    try_log!(stdout().flush(), "Flush err");
+
+    // Output past the bottom scrolls the screen, taking our first row with it
+    let layout = layout(prompt_len, text, cursor, term_cols as usize);
+    let used_rows = (rows_above + layout.rows + below.len()) as u16;
+    let expected_end_row = *start_y + used_rows;
+    if expected_end_row > term_rows {
+        *start_y = start_y.saturating_sub(expected_end_row - term_rows);
+    }
+
+    let (row, col) = layout.cursor;
+    try_log!(
+        execute!(
+            stdout(),
+            MoveTo(col as u16, *start_y + (rows_above + row) as u16)
+        ),
+        "Cursor move err"
+    );
+}
+
 // --- THE RENDER ENGINE ---
-pub fn render_system(shell: &ShellState, prompt_len: usize, start_y: &mut u16, current_dir: &str) {
+pub fn render_system(shell: &ShellState, prompt: &Prompt, start_y: &mut u16) {
     // 1. Reset visual state
+    // Everything from the prompt down is repainted, so a shorter line
+    // leaves nothing of the old one behind
     try_log!(
         execute!(
             stdout(),
@@ -27,48 +154,125 @@ pub fn render_system(shell: &ShellState, prompt_len: usize, start_y: &mut u16, c
     );
 
     // 2. Print Prompt & Buffer
-    if !shell.is_continuation {
-        print!("{GREEN}{}$ {RESET}", current_dir);
-    } else {
-        print!("> ");
+    // Ctrl-R swaps the prompt for the search one, cursor on the match
+    if let Some(search) = &shell.search {
+        let (prompt, offset) = search_prompt(search, &shell.view_buffer);
+        print!("{}", prompt);
+        draw(
+            0,
+            str_width(&prompt),
+            &shell.view_buffer,
+            offset,
+            &[],
+            start_y,
+        );
+        return;
     }
+    // Vi `/` search reads its pattern on the prompt line
+    if let Some(input) = &shell.vi.search_input {
+        print!("/");
+        try_log!(
+            execute!(stdout(), SetCursorStyle::SteadyBar),
+            "Cursor style err"
+        );
+        draw(0, 1, input, input.chars().count(), &[], start_y);
+        return;
+    }
+    // In vi mode the prompt starts with the mode and the cursor shape follows it
+    let mut prompt_len = prompt.width();
+    if shell.options.vi {
+        let (label, style) = match shell.vi.mode {
+            ViMode::Insert => ("(ins) ", SetCursorStyle::SteadyBar),
+            ViMode::Normal => ("(cmd) ", SetCursorStyle::SteadyBlock),
+        };
+        print!("{}", label);
+        // It only pushes the input right when the prompt is a single line
+        if !prompt.text.contains('\n') {
+            prompt_len += label.len();
+        }
+        try_log!(execute!(stdout(), style), "Cursor style err");
+    }
+    print!("{}", prompt.text.replace('\n', "\r\n"));
 
-    let display_text = shell.view_buffer.replace("\n", "\r\n");
-    print!("{}", display_text);
-    try_log!(stdout().flush(), "Flush err");
+    // Tab completion candidates go below the input
+    let (term_cols, _) = terminal_size();
+    let menu = match &shell.completion {
+        Some(menu) => format_menu(menu, term_cols as usize),
+        None => Vec::new(),
+    };
+    draw(
+        prompt.rows_above(term_cols as usize),
+        prompt_len,
+        &shell.view_buffer,
+        shell.cursor_idx,
+        &menu,
+        start_y,
+    );
+}
 
-    // 3. DETECT SCROLLING
-    let (_, term_rows) = size().unwrap_or((80, 24));
-    let used_rows = shell.view_buffer.chars().filter(|&c| c == '\n').count() as u16 + 1;
-    let expected_end_row = *start_y + used_rows;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if expected_end_row > term_rows {
-        let scroll_amount = expected_end_row - term_rows;
-        *start_y = start_y.saturating_sub(scroll_amount);
-    }
-
-    // 4. Calculate Visual Cursor Position
-    let byte_idx = get_byte_index(&shell.view_buffer, shell.cursor_idx);
-    let active_part = &shell.view_buffer[..byte_idx];
-
-    let cursor_rows = active_part.chars().filter(|&c| c == '\n').count() as u16;
-    let last_line_len = active_part
-        .split('\n')
-        .next_back()
-        .unwrap_or("")
-        .chars()
-        .count();
-
-    let target_x = if cursor_rows == 0 {
-        (prompt_len + last_line_len) as u16
-    } else {
-        last_line_len as u16
-    };
+    #[test]
+    fn test_short_line_stays_on_one_row() {
+        assert_eq!(
+            layout(4, "ls -l", 2, 80),
+            Layout {
+                rows: 1,
+                cursor: (0, 6)
+            }
+        );
+    }
 
-    let target_y = *start_y + cursor_rows;
+    #[test]
+    fn test_long_line_wraps_at_width() {
+        // 4 + 14 = 18 columns on a 10 wide terminal
+        let text = "echo long words";
+        assert_eq!(layout(4, text, 15, 10).rows, 2);
+        assert_eq!(layout(4, text, 15, 10).cursor, (1, 9));
+        assert_eq!(layout(4, text, 5, 10).cursor, (0, 9));
+        assert_eq!(layout(4, text, 6, 10).cursor, (1, 0));
+    }
 
-    try_log!(
-        execute!(stdout(), MoveTo(target_x, target_y)),
-        "Cursor move err"
-    );
+    #[test]
+    fn test_exact_fill_moves_cursor_to_next_row() {
+        assert_eq!(
+            layout(2, "abcdefgh", 8, 10),
+            Layout {
+                rows: 2,
+                cursor: (1, 0)
+            }
+        );
+        // A prompt wider than the terminal wraps too
+        assert_eq!(layout(25, "", 0, 10).cursor, (2, 5));
+    }
+
+    #[test]
+    fn test_multiline_rows_add_up() {
+        let text = "for x in a b\ndo echo $x\ndone";
+        assert_eq!(layout(2, text, 0, 10).rows, 2 + 2 + 1);
+        // Start of "done"
+        assert_eq!(layout(2, text, 24, 10).cursor, (4, 0));
+        // End of "do echo $x" is one past the edge
+        assert_eq!(layout(2, text, 23, 10).cursor, (3, 0));
+    }
+
+    #[test]
+    fn test_wide_chars_take_two_columns() {
+        // 2 + 3 * 2 columns
+        assert_eq!(layout(2, "日本語", 3, 80).cursor, (0, 8));
+        assert_eq!(layout(2, "日本語", 1, 80).cursor, (0, 4));
+        // Combining marks take no column
+        assert_eq!(layout(0, "e\u{301}x", 2, 80).cursor, (0, 1));
+    }
+
+    #[test]
+    fn test_wide_char_wraps_early_at_the_edge() {
+        // "abc" fills columns 0-2, the wide char does not fit in column 3
+        let layout = layout(0, "abc日d", 5, 4);
+        assert_eq!(layout.rows, 2);
+        assert_eq!(layout.cursor, (1, 3));
+        assert_eq!(super::layout(0, "abc日d", 3, 4).cursor, (1, 0));
+    }
 }
@@ -0,0 +1,74 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// --- Graphemes & Display Width ---
+// Buffers are indexed in chars, but what the user sees (and deletes) is a
+// grapheme cluster: `e` + a combining accent, a flag, a family emoji.
+// Cursor moves go cluster by cluster so they never land inside one, and the
+// renderer measures terminal columns here instead of counting chars.
+
+// Char index of the cluster start left of `idx`
+pub fn prev_boundary(s: &str, idx: usize) -> usize {
+    let mut start = 0;
+    let mut pos = 0;
+    for g in s.graphemes(true) {
+        if pos >= idx {
+            break;
+        }
+        start = pos;
+        pos += g.chars().count();
+    }
+    start
+}
+
+// Char index of the cluster end right of `idx`
+pub fn next_boundary(s: &str, idx: usize) -> usize {
+    let mut pos = 0;
+    for g in s.graphemes(true) {
+        pos += g.chars().count();
+        if pos > idx {
+            return pos;
+        }
+    }
+    pos
+}
+
+// Columns one cluster takes: 2 for CJK and emoji, 0 for a lone combining mark
+pub fn grapheme_width(g: &str) -> usize {
+    g.width().min(2)
+}
+
+pub fn str_width(s: &str) -> usize {
+    s.graphemes(true).map(grapheme_width).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boundaries_skip_whole_clusters() {
+        // "e" + combining acute, then "x"
+        let s = "e\u{301}x";
+        assert_eq!(next_boundary(s, 0), 2);
+        assert_eq!(next_boundary(s, 2), 3);
+        assert_eq!(next_boundary(s, 3), 3);
+        assert_eq!(prev_boundary(s, 3), 2);
+        assert_eq!(prev_boundary(s, 2), 0);
+        assert_eq!(prev_boundary(s, 0), 0);
+
+        let family = "a👨\u{200d}👩\u{200d}👧b";
+        assert_eq!(next_boundary(family, 1), 6);
+        assert_eq!(prev_boundary(family, 6), 1);
+    }
+
+    #[test]
+    fn test_widths() {
+        assert_eq!(str_width("ls"), 2);
+        assert_eq!(str_width("日本"), 4);
+        assert_eq!(str_width("e\u{301}"), 1);
+        assert_eq!(str_width("🦀"), 2);
+        assert_eq!(str_width("👨\u{200d}👩\u{200d}👧"), 2);
+        assert_eq!(str_width("🇫🇷"), 2);
+    }
+}
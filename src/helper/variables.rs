@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::env;
+
+// --- Variable Store ---
+// Every shell variable lives here. Exported ones are handed to external
+// commands as their environment; the rest only exist inside the shell.
+#[derive(Debug, Clone)]
+struct Variable {
+    // None for `export NAME` on a name that was never assigned
+    value: Option<String>,
+    exported: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    vars: HashMap<String, Variable>,
+    // One per running function: what its `local` names held before the call
+    scopes: Vec<HashMap<String, Option<Variable>>>,
+}
+
+impl Variables {
+    // Starts from the environment the shell was launched with
+    pub fn from_env() -> Self {
+        let vars = env::vars()
+            .map(|(name, value)| {
+                let var = Variable {
+                    value: Some(value),
+                    exported: true,
+                };
+                (name, var)
+            })
+            .collect();
+        Self {
+            vars,
+            scopes: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.get(name)?.value.as_deref()
+    }
+
+    // Assigning keeps the exported flag of an existing variable
+    pub fn set(&mut self, name: &str, value: String) {
+        self.vars
+            .entry(name.to_string())
+            .and_modify(|var| var.value = Some(value.clone()))
+            .or_insert(Variable {
+                value: Some(value),
+                exported: false,
+            });
+    }
+
+    pub fn export(&mut self, name: &str) {
+        self.vars
+            .entry(name.to_string())
+            .and_modify(|var| var.exported = true)
+            .or_insert(Variable {
+                value: None,
+                exported: true,
+            });
+    }
+
+    pub fn unset(&mut self, name: &str) {
+        self.vars.remove(name);
+    }
+
+    // --- Function Scopes ---
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    // Puts back whatever the finished call's locals hid
+    pub fn pop_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        for (name, saved) in scope {
+            match saved {
+                Some(var) => self.vars.insert(name, var),
+                None => self.vars.remove(&name),
+            };
+        }
+    }
+
+    // `local NAME`: starts out unset and is restored when the function
+    // returns. False outside of a function.
+    pub fn make_local(&mut self, name: &str) -> bool {
+        let Some(scope) = self.scopes.last_mut() else {
+            return false;
+        };
+        if !scope.contains_key(name) {
+            scope.insert(name.to_string(), self.vars.remove(name));
+        }
+        true
+    }
+
+    // Environment for child processes, sorted so `export` output is stable
+    pub fn exported(&self) -> Vec<(String, String)> {
+        let mut env: Vec<(String, String)> = self
+            .vars
+            .iter()
+            .filter(|(_, var)| var.exported)
+            .filter_map(|(name, var)| Some((name.clone(), var.value.clone()?)))
+            .collect();
+        env.sort();
+        env
+    }
+
+    // Exported names, including the ones without a value yet
+    pub fn exported_names(&self) -> Vec<(String, Option<String>)> {
+        let mut names: Vec<(String, Option<String>)> = self
+            .vars
+            .iter()
+            .filter(|(_, var)| var.exported)
+            .map(|(name, var)| (name.clone(), var.value.clone()))
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+// Letters, digits and `_`, not starting with a digit
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c == '_' || c.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+    chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_keeps_export_flag() {
+        let mut vars = Variables::default();
+        vars.set("A", "1".to_string());
+        assert!(vars.exported().is_empty());
+
+        vars.export("A");
+        vars.set("A", "2".to_string());
+        assert_eq!(vars.exported(), vec![("A".to_string(), "2".to_string())]);
+
+        vars.unset("A");
+        assert_eq!(vars.get("A"), None);
+    }
+
+    #[test]
+    fn test_export_without_value_stays_out_of_env() {
+        let mut vars = Variables::default();
+        vars.export("LATER");
+        assert!(vars.exported().is_empty());
+        vars.set("LATER", "now".to_string());
+        assert_eq!(vars.get("LATER"), Some("now"));
+        assert_eq!(vars.exported().len(), 1);
+    }
+
+    #[test]
+    fn test_is_valid_name() {
+        assert!(is_valid_name("HOME"));
+        assert!(is_valid_name("_x1"));
+        assert!(!is_valid_name("1x"));
+        assert!(!is_valid_name("a-b"));
+        assert!(!is_valid_name(""));
+    }
+
+    #[test]
+    fn test_locals_are_restored_per_scope() {
+        let mut vars = Variables::default();
+        vars.set("X", "global".to_string());
+        assert!(!vars.make_local("X"));
+
+        vars.push_scope();
+        assert!(vars.make_local("X"));
+        assert_eq!(vars.get("X"), None);
+        vars.set("X", "outer".to_string());
+        vars.make_local("NEW");
+        vars.set("NEW", "1".to_string());
+
+        vars.push_scope();
+        vars.make_local("X");
+        vars.set("X", "inner".to_string());
+        vars.pop_scope();
+        assert_eq!(vars.get("X"), Some("outer"));
+
+        vars.pop_scope();
+        assert_eq!(vars.get("X"), Some("global"));
+        assert_eq!(vars.get("NEW"), None);
+    }
+}
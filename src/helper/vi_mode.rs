@@ -0,0 +1,748 @@
+use crate::helper::line_editor::{self, history_next, history_prev, insert_str, set_line};
+use crate::helper::state_manager::ShellState;
+use crate::helper::unicode::{next_boundary, prev_boundary};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// --- Vi Mode (`set -o vi`) ---
+// Insert mode is the normal line editor. Esc switches to normal mode, where
+// keys pile up in `pending` until they form a whole command like `3dw`.
+// A change remembers the keys that made it, so `.` just plays them again.
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ViMode {
+    #[default]
+    Insert,
+    Normal,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ViState {
+    pub mode: ViMode,
+    pending: Vec<char>,
+    pending_keys: Vec<KeyEvent>,
+    // Keys of the change in progress (an insert runs until Esc)
+    recording: Option<Vec<KeyEvent>>,
+    last_change: Vec<KeyEvent>,
+    replaying: bool,
+    // (line, cursor) before each change
+    undo: Vec<(String, usize)>,
+    // Some while typing after `/`
+    pub search_input: Option<String>,
+    last_search: Option<String>,
+}
+
+const UNDO_LIMIT: usize = 100;
+
+impl ViState {
+    // Every new prompt starts in insert mode with nothing to undo
+    pub fn start_line(&mut self) {
+        *self = ViState {
+            last_change: std::mem::take(&mut self.last_change),
+            last_search: self.last_search.take(),
+            ..ViState::default()
+        };
+    }
+}
+
+// --- Commands ---
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Motion {
+    Left,
+    Right,
+    WordForward,
+    WordBackward,
+    WordEnd,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    // f, t, F, T
+    Find {
+        target: char,
+        before: bool,
+        backward: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    Motion(Motion),
+    // dd, cc, yy
+    Line,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Move(Motion),
+    Operate(char, Target),
+    // i, a, I, A
+    Insert(char),
+    Paste { after: bool },
+    Undo,
+    Repeat,
+    Search,
+    SearchNext,
+    Older,
+    Newer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Command {
+    count: usize,
+    action: Action,
+}
+
+impl Command {
+    fn is_change(&self) -> bool {
+        match self.action {
+            Action::Operate(op, _) => op != 'y',
+            Action::Insert(_) | Action::Paste { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Parse {
+    Incomplete,
+    Invalid,
+    Done(Command),
+}
+
+// More than any line needs; a bigger count would only spin on one key
+const MAX_COUNT: usize = 1000;
+
+// A count never starts with 0 (that is the "start of line" motion)
+fn read_count(keys: &[char], i: &mut usize) -> Option<usize> {
+    if !matches!(keys.get(*i), Some('1'..='9')) {
+        return None;
+    }
+    let mut count: usize = 0;
+    while let Some(d) = keys.get(*i).and_then(|c| c.to_digit(10)) {
+        count = count.saturating_mul(10).saturating_add(d as usize);
+        *i += 1;
+    }
+    Some(count.min(MAX_COUNT))
+}
+
+fn parse_motion(keys: &[char], i: usize) -> Result<Motion, Parse> {
+    let Some(&c) = keys.get(i) else {
+        return Err(Parse::Incomplete);
+    };
+    Ok(match c {
+        'h' => Motion::Left,
+        'l' | ' ' => Motion::Right,
+        'w' => Motion::WordForward,
+        'b' => Motion::WordBackward,
+        'e' => Motion::WordEnd,
+        '0' => Motion::LineStart,
+        '^' => Motion::FirstNonBlank,
+        '$' => Motion::LineEnd,
+        'f' | 't' | 'F' | 'T' => match keys.get(i + 1) {
+            Some(&target) => Motion::Find {
+                target,
+                before: c == 't' || c == 'T',
+                backward: c == 'F' || c == 'T',
+            },
+            None => return Err(Parse::Incomplete),
+        },
+        _ => return Err(Parse::Invalid),
+    })
+}
+
+fn parse(keys: &[char]) -> Parse {
+    let mut i = 0;
+    let count = read_count(keys, &mut i);
+    let Some(&c) = keys.get(i) else {
+        return Parse::Incomplete;
+    };
+    i += 1;
+
+    let action = match c {
+        'd' | 'c' | 'y' => {
+            let inner = read_count(keys, &mut i);
+            let target = match keys.get(i) {
+                None => return Parse::Incomplete,
+                Some(&same) if same == c => Target::Line,
+                Some(_) => match parse_motion(keys, i) {
+                    Ok(motion) => Target::Motion(motion),
+                    Err(result) => return result,
+                },
+            };
+            let count = (count.unwrap_or(1) * inner.unwrap_or(1)).min(MAX_COUNT);
+            return Parse::Done(Command {
+                count,
+                action: Action::Operate(c, target),
+            });
+        }
+        'D' => Action::Operate('d', Target::Motion(Motion::LineEnd)),
+        'C' => Action::Operate('c', Target::Motion(Motion::LineEnd)),
+        'x' => Action::Operate('d', Target::Motion(Motion::Right)),
+        'X' => Action::Operate('d', Target::Motion(Motion::Left)),
+        'i' | 'a' | 'I' | 'A' => Action::Insert(c),
+        'p' => Action::Paste { after: true },
+        'P' => Action::Paste { after: false },
+        'u' => Action::Undo,
+        '.' => Action::Repeat,
+        '/' => Action::Search,
+        'n' => Action::SearchNext,
+        'k' => Action::Older,
+        'j' => Action::Newer,
+        _ => match parse_motion(keys, i - 1) {
+            Ok(motion) => Action::Move(motion),
+            Err(result) => return result,
+        },
+    };
+    Parse::Done(Command {
+        count: count.unwrap_or(1),
+        action,
+    })
+}
+
+// --- Motions ---
+// 0 = blank, 1 = word characters, 2 = other punctuation (vim's "word")
+fn class(c: char) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+fn next_word_start(line: &[char], mut i: usize) -> usize {
+    if i >= line.len() {
+        return line.len();
+    }
+    let start_class = class(line[i]);
+    if start_class != 0 {
+        while i < line.len() && class(line[i]) == start_class {
+            i += 1;
+        }
+    }
+    while i < line.len() && class(line[i]) == 0 {
+        i += 1;
+    }
+    i
+}
+
+fn prev_word_start(line: &[char], mut i: usize) -> usize {
+    while i > 0 && class(line[i - 1]) == 0 {
+        i -= 1;
+    }
+    if i == 0 {
+        return 0;
+    }
+    let word_class = class(line[i - 1]);
+    while i > 0 && class(line[i - 1]) == word_class {
+        i -= 1;
+    }
+    i
+}
+
+fn word_end(line: &[char], mut i: usize) -> usize {
+    i += 1;
+    while i < line.len() && class(line[i]) == 0 {
+        i += 1;
+    }
+    if i >= line.len() {
+        return line.len().saturating_sub(1);
+    }
+    let word_class = class(line[i]);
+    while i + 1 < line.len() && class(line[i + 1]) == word_class {
+        i += 1;
+    }
+    i
+}
+
+// Where a motion lands and whether that last char is part of an operator's range
+fn motion_target(
+    line: &[char],
+    cursor: usize,
+    motion: Motion,
+    count: usize,
+) -> Option<(usize, bool)> {
+    let len = line.len();
+    let text: String = line.iter().collect();
+    let repeat = |step: &dyn Fn(usize) -> usize| (0..count).fold(cursor, |i, _| step(i));
+    Some(match motion {
+        // h and l step over whole grapheme clusters
+        Motion::Left => (repeat(&|i| prev_boundary(&text, i)), false),
+        Motion::Right => (repeat(&|i| next_boundary(&text, i)), false),
+        Motion::WordForward => (repeat(&|i| next_word_start(line, i)), false),
+        Motion::WordBackward => (repeat(&|i| prev_word_start(line, i)), false),
+        Motion::WordEnd => (repeat(&|i| word_end(line, i)), true),
+        Motion::LineStart => (0, false),
+        Motion::FirstNonBlank => (
+            line.iter().position(|c| !c.is_whitespace()).unwrap_or(len),
+            false,
+        ),
+        Motion::LineEnd => (len, false),
+        Motion::Find {
+            target,
+            before,
+            backward,
+        } => {
+            let mut found = cursor;
+            for _ in 0..count {
+                found = if backward {
+                    (0..found).rev().find(|&i| line[i] == target)?
+                } else {
+                    (found + 1..len).find(|&i| line[i] == target)?
+                };
+            }
+            match (before, backward) {
+                (false, false) => (found, true),
+                (true, false) => (found - 1, true),
+                (false, true) => (found, false),
+                (true, true) => (found + 1, false),
+            }
+        }
+    })
+}
+
+// --- Execution ---
+fn clamp_normal(shell: &mut ShellState) {
+    // Onto the start of the cluster under the cursor (the last one at the end)
+    let end = next_boundary(&shell.view_buffer, shell.cursor_idx);
+    shell.cursor_idx = prev_boundary(&shell.view_buffer, end);
+}
+
+fn enter_insert(shell: &mut ShellState) {
+    shell.vi.mode = ViMode::Insert;
+}
+
+fn operate(shell: &mut ShellState, op: char, target: Target, count: usize) {
+    let mut line: Vec<char> = shell.view_buffer.chars().collect();
+    let cursor = shell.cursor_idx.min(line.len());
+
+    let (start, end) = match target {
+        Target::Line => (0, line.len()),
+        Target::Motion(motion) => {
+            // `cw` on a word changes to its end and keeps the space, like vim.
+            // Unlike `e` it stops at the end of the word it starts in.
+            if motion == Motion::WordForward
+                && op == 'c'
+                && line.get(cursor).is_some_and(|c| class(*c) != 0)
+            {
+                let mut end = cursor;
+                while end + 1 < line.len() && class(line[end + 1]) == class(line[cursor]) {
+                    end += 1;
+                }
+                let end = (1..count).fold(end, |i, _| word_end(&line, i));
+                (cursor, end + 1)
+            } else {
+                let Some((target, inclusive)) = motion_target(&line, cursor, motion, count) else {
+                    return;
+                };
+                if target >= cursor {
+                    (cursor, (target + inclusive as usize).min(line.len()))
+                } else {
+                    (target, cursor)
+                }
+            }
+        }
+    };
+
+    let text: String = line[start..end].iter().collect();
+    shell.kill_ring.store(text);
+    match op {
+        'y' => shell.cursor_idx = start,
+        _ => {
+            line.drain(start..end);
+            set_line(shell, line, start);
+            if op == 'c' {
+                enter_insert(shell);
+            }
+        }
+    }
+}
+
+fn paste(shell: &mut ShellState, after: bool, count: usize) {
+    let Some(text) = shell.kill_ring.yank_text().map(|t| t.repeat(count)) else {
+        return;
+    };
+    if after && !shell.view_buffer.is_empty() {
+        shell.cursor_idx = next_boundary(&shell.view_buffer, shell.cursor_idx);
+    }
+    insert_str(shell, &text);
+    // The cursor rests on the last pasted char
+    shell.cursor_idx = prev_boundary(&shell.view_buffer, shell.cursor_idx);
+}
+
+fn search_history(shell: &mut ShellState, pattern: &str) {
+    if let Some(i) = (0..shell.hist_idx)
+        .rev()
+        .find(|&i| shell.history[i].contains(pattern))
+    {
+        shell.hist_idx = i;
+        shell.buffer = shell.history[i].clone();
+        shell.view_buffer = shell.history[i].clone();
+        shell.cursor_idx = 0;
+    }
+}
+
+fn execute(shell: &mut ShellState, command: Command, keys: Vec<KeyEvent>) {
+    if command.is_change() {
+        shell
+            .vi
+            .undo
+            .push((shell.view_buffer.clone(), shell.cursor_idx));
+        if shell.vi.undo.len() > UNDO_LIMIT {
+            shell.vi.undo.remove(0);
+        }
+        if !shell.vi.replaying {
+            shell.vi.recording = Some(keys);
+        }
+    }
+    let Command { count, action } = command;
+
+    match action {
+        Action::Move(motion) => {
+            let line: Vec<char> = shell.view_buffer.chars().collect();
+            if let Some((target, _)) = motion_target(&line, shell.cursor_idx, motion, count) {
+                shell.cursor_idx = target;
+            }
+        }
+        Action::Operate(op, target) => operate(shell, op, target, count),
+        Action::Insert(kind) => {
+            let len = shell.view_buffer.chars().count();
+            shell.cursor_idx = match kind {
+                'a' => next_boundary(&shell.view_buffer, shell.cursor_idx),
+                'I' => 0,
+                'A' => len,
+                _ => shell.cursor_idx,
+            };
+            enter_insert(shell);
+        }
+        Action::Paste { after } => paste(shell, after, count),
+        Action::Undo => {
+            if let Some((line, cursor)) = shell.vi.undo.pop() {
+                set_line(shell, line.chars().collect(), cursor);
+            }
+        }
+        Action::Repeat => {
+            let keys = shell.vi.last_change.clone();
+            shell.vi.replaying = true;
+            for key in keys {
+                handle_key(shell, key);
+            }
+            shell.vi.replaying = false;
+        }
+        Action::Search => shell.vi.search_input = Some(String::new()),
+        Action::SearchNext => {
+            if let Some(pattern) = shell.vi.last_search.clone() {
+                search_history(shell, &pattern);
+            }
+        }
+        Action::Older => (0..count).for_each(|_| history_prev(shell)),
+        Action::Newer => (0..count).for_each(|_| history_next(shell)),
+    }
+
+    // A change that stays in normal mode is complete right away
+    if shell.vi.mode == ViMode::Normal {
+        if let Some(keys) = shell.vi.recording.take() {
+            shell.vi.last_change = keys;
+        }
+        clamp_normal(shell);
+    }
+}
+
+// Normal mode only sees plain characters; arrows map to their vi keys
+fn normal_char(key: KeyEvent) -> Option<char> {
+    if key
+        .modifiers
+        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    {
+        return None;
+    }
+    match key.code {
+        KeyCode::Char(c) => Some(c),
+        KeyCode::Left | KeyCode::Backspace => Some('h'),
+        KeyCode::Right => Some('l'),
+        KeyCode::Up => Some('k'),
+        KeyCode::Down => Some('j'),
+        KeyCode::Home => Some('0'),
+        KeyCode::End => Some('$'),
+        KeyCode::Delete => Some('x'),
+        _ => None,
+    }
+}
+
+fn search_key(shell: &mut ShellState, key: KeyEvent) -> bool {
+    let Some(mut input) = shell.vi.search_input.take() else {
+        return false;
+    };
+    match key.code {
+        KeyCode::Enter => {
+            // A bare `/` searches for the previous pattern again
+            if !input.is_empty() {
+                shell.vi.last_search = Some(input);
+            }
+            if let Some(pattern) = shell.vi.last_search.clone() {
+                search_history(shell, &pattern);
+            }
+            return true;
+        }
+        KeyCode::Esc => return true,
+        KeyCode::Backspace if input.is_empty() => return true,
+        KeyCode::Backspace => {
+            input.pop();
+        }
+        KeyCode::Char(c) => input.push(c),
+        _ => {}
+    }
+    shell.vi.search_input = Some(input);
+    true
+}
+
+fn record(shell: &mut ShellState, key: KeyEvent) {
+    if !shell.vi.replaying
+        && let Some(keys) = &mut shell.vi.recording
+    {
+        keys.push(key);
+    }
+}
+
+// Same contract as line_editor::handle_key: false leaves the key to main
+pub fn handle_key(shell: &mut ShellState, key: KeyEvent) -> bool {
+    if shell.vi.search_input.is_some() {
+        return search_key(shell, key);
+    }
+
+    if shell.vi.mode == ViMode::Insert {
+        if key.code == KeyCode::Esc {
+            record(shell, key);
+            if let Some(keys) = shell.vi.recording.take()
+                && !shell.vi.replaying
+            {
+                shell.vi.last_change = keys;
+            }
+            shell.vi.mode = ViMode::Normal;
+            shell.cursor_idx = prev_boundary(&shell.view_buffer, shell.cursor_idx);
+            return true;
+        }
+        let used = line_editor::handle_key(shell, key);
+        if used {
+            record(shell, key);
+        }
+        return used;
+    }
+
+    if key.code == KeyCode::Esc {
+        shell.vi.pending.clear();
+        shell.vi.pending_keys.clear();
+        return true;
+    }
+    let Some(c) = normal_char(key) else {
+        return false;
+    };
+    shell.vi.pending.push(c);
+    shell.vi.pending_keys.push(key);
+    match parse(&shell.vi.pending) {
+        Parse::Incomplete => {}
+        Parse::Invalid => {
+            shell.vi.pending.clear();
+            shell.vi.pending_keys.clear();
+        }
+        Parse::Done(command) => {
+            shell.vi.pending.clear();
+            let keys = std::mem::take(&mut shell.vi.pending_keys);
+            execute(shell, command, keys);
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vi_shell(line: &str, cursor: usize) -> ShellState {
+        let mut shell = ShellState::new();
+        shell.options.vi = true;
+        shell.buffer = line.to_string();
+        shell.view_buffer = line.to_string();
+        shell.cursor_idx = cursor;
+        shell.vi.mode = ViMode::Normal;
+        shell
+    }
+
+    // Feeds keys like vim notation: `<Esc>` is the escape key
+    fn keys(shell: &mut ShellState, input: &str) {
+        let mut rest = input;
+        while !rest.is_empty() {
+            let code = if let Some(after) = rest.strip_prefix("<Esc>") {
+                rest = after;
+                KeyCode::Esc
+            } else {
+                let c = rest.chars().next().unwrap();
+                rest = &rest[c.len_utf8()..];
+                KeyCode::Char(c)
+            };
+            handle_key(shell, KeyEvent::new(code, KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn test_parse_counts_and_operators() {
+        let done = |count, action| Parse::Done(Command { count, action });
+        assert_eq!(
+            parse(&['3', 'w']),
+            done(3, Action::Move(Motion::WordForward))
+        );
+        assert_eq!(
+            parse(&['2', 'd', '3', 'w']),
+            done(6, Action::Operate('d', Target::Motion(Motion::WordForward)))
+        );
+        assert_eq!(
+            parse(&['d', 'd']),
+            done(1, Action::Operate('d', Target::Line))
+        );
+        assert_eq!(parse(&['d']), Parse::Incomplete);
+        assert_eq!(parse(&['d', 't']), Parse::Incomplete);
+        assert_eq!(parse(&['0']), done(1, Action::Move(Motion::LineStart)));
+        assert_eq!(parse(&['d', 'z']), Parse::Invalid);
+
+        // Huge counts are capped instead of overflowing
+        let mut huge = vec!['9'; 40];
+        huge.push('w');
+        assert_eq!(
+            parse(&huge),
+            done(MAX_COUNT, Action::Move(Motion::WordForward))
+        );
+        assert_eq!(
+            parse(&['9', '9', '9', '9', 'd', '9', '9', '9', '9', 'w']),
+            done(
+                MAX_COUNT,
+                Action::Operate('d', Target::Motion(Motion::WordForward))
+            )
+        );
+    }
+
+    #[test]
+    fn test_word_motions() {
+        let mut shell = vi_shell("ls -la /tmp/dir", 0);
+        keys(&mut shell, "w");
+        assert_eq!(shell.cursor_idx, 3);
+        keys(&mut shell, "2w");
+        assert_eq!(shell.cursor_idx, 7);
+        keys(&mut shell, "e");
+        assert_eq!(shell.cursor_idx, 10);
+        keys(&mut shell, "b");
+        assert_eq!(shell.cursor_idx, 8);
+        keys(&mut shell, "$");
+        assert_eq!(shell.cursor_idx, 14);
+        keys(&mut shell, "0");
+        assert_eq!(shell.cursor_idx, 0);
+    }
+
+    #[test]
+    fn test_find_motions() {
+        let mut shell = vi_shell("echo a,b,c", 0);
+        keys(&mut shell, "f,");
+        assert_eq!(shell.cursor_idx, 6);
+        keys(&mut shell, "0t,");
+        assert_eq!(shell.cursor_idx, 5);
+        keys(&mut shell, "$F,");
+        assert_eq!(shell.cursor_idx, 8);
+    }
+
+    #[test]
+    fn test_operators() {
+        let mut shell = vi_shell("echo one two three", 5);
+        keys(&mut shell, "dw");
+        assert_eq!(shell.view_buffer, "echo two three");
+
+        keys(&mut shell, "cwTWO<Esc>");
+        assert_eq!(shell.view_buffer, "echo TWO three");
+        assert_eq!(shell.vi.mode, ViMode::Normal);
+
+        keys(&mut shell, "0ywP");
+        assert_eq!(shell.view_buffer, "echo echo TWO three");
+
+        keys(&mut shell, "$dF ");
+        assert_eq!(shell.view_buffer, "echo echo TWOe");
+
+        keys(&mut shell, "0d$");
+        assert_eq!(shell.view_buffer, "");
+    }
+
+    #[test]
+    fn test_insert_commands_and_buffer_sync() {
+        let mut shell = vi_shell("b", 0);
+        keys(&mut shell, "ia<Esc>");
+        keys(&mut shell, "Ac<Esc>");
+        keys(&mut shell, "I> <Esc>");
+        assert_eq!(shell.view_buffer, "> abc");
+        assert_eq!(shell.buffer, "> abc");
+        // Esc steps back onto the last inserted char
+        assert_eq!(shell.cursor_idx, 1);
+    }
+
+    #[test]
+    fn test_dot_repeats_last_change() {
+        let mut shell = vi_shell("a b c d", 0);
+        keys(&mut shell, "x");
+        keys(&mut shell, "w.");
+        assert_eq!(shell.view_buffer, "  c d");
+
+        let mut shell = vi_shell("x y", 0);
+        keys(&mut shell, "cwfoo<Esc>w.");
+        assert_eq!(shell.view_buffer, "foo foo");
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut shell = vi_shell("keep this", 0);
+        keys(&mut shell, "dwx");
+        assert_eq!(shell.view_buffer, "his");
+        keys(&mut shell, "u");
+        assert_eq!(shell.view_buffer, "this");
+        keys(&mut shell, "u");
+        assert_eq!(shell.view_buffer, "keep this");
+        assert_eq!(shell.cursor_idx, 0);
+    }
+
+    #[test]
+    fn test_slash_searches_history() {
+        let mut shell = vi_shell("", 0);
+        shell.history = vec!["make build".into(), "ls".into(), "make test".into()];
+        shell.hist_idx = 3;
+
+        keys(&mut shell, "/make");
+        handle_key(
+            &mut shell,
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+        );
+        assert_eq!(shell.buffer, "make test");
+        keys(&mut shell, "n");
+        assert_eq!(shell.buffer, "make build");
+    }
+
+    #[test]
+    fn test_enter_and_ctrl_keys_go_to_main() {
+        let mut shell = vi_shell("ls", 0);
+        assert!(!handle_key(
+            &mut shell,
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)
+        ));
+        assert!(!handle_key(
+            &mut shell,
+            KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL)
+        ));
+    }
+
+    #[test]
+    fn test_h_l_and_x_use_whole_clusters() {
+        let mut shell = vi_shell("ne\u{301}e", 0);
+        keys(&mut shell, "l");
+        assert_eq!(shell.cursor_idx, 1);
+        keys(&mut shell, "l");
+        assert_eq!(shell.cursor_idx, 3);
+        keys(&mut shell, "hx");
+        assert_eq!(shell.view_buffer, "ne");
+        keys(&mut shell, "$");
+        assert_eq!(shell.cursor_idx, 1);
+    }
+}
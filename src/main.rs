@@ -2,39 +2,73 @@ pub mod command;
 pub mod helper;
 
 use crossterm::{
-    cursor::{self, MoveToColumn},
+    cursor::{self, MoveTo, MoveToColumn, SetCursorStyle},
     event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode},
+    terminal::{Clear, ClearType, disable_raw_mode, enable_raw_mode},
 };
-use std::io::{self, Write, stdout};
+use std::io::{self, IsTerminal, Write, stdout};
 
+use helper::completion::on_tab;
 use helper::executor::execute;
-use helper::parser::{CommandEnum, ParseResult, parse_input};
+use helper::external::{clear_interrupt, install_signal_handlers};
+use helper::line_editor;
+use helper::parser::{ParseResult, parse_input_with_aliases};
 use helper::print_banner::print_banner;
+use helper::prompt::{build_prompt, refresh_git};
+use helper::script::{Invocation, parse_args, run_non_interactive, run_rc_file};
+use helper::search::{on_search_key, start_search};
 use helper::state_manager::{RawModeGuard, ShellState};
-use helper::ui::{get_byte_index, render_system};
+use helper::ui::render_system;
+use helper::vi_mode;
 fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut shell = ShellState::new();
+
+    // Scripts, -c and piped input never touch the terminal
+    let norc = match parse_args(&args, io::stdin().is_terminal()) {
+        Ok(Invocation::Interactive { norc }) => norc,
+        Ok(invocation) => std::process::exit(run_non_interactive(invocation, &mut shell)),
+        Err(e) => {
+            eprintln!("0-shell: {}", e);
+            std::process::exit(2);
+        }
+    };
+
     let _guard = RawModeGuard;
+    install_signal_handlers();
+    shell.jobs.enable();
     print_banner();
-    enable_raw_mode()?;
+    shell.load_history();
 
-    let mut shell = ShellState::new();
+    // The rc file runs like typed commands, so not in raw mode
+    if !norc {
+        run_rc_file(&mut shell);
+        if let Some(code) = shell.exit_code {
+            std::process::exit(code);
+        }
+    }
+    enable_raw_mode()?;
 
     loop {
         // --- 1. SETUP START OF LINE ---
-        let current_dir = shell.pwd.get_current_dir();
-        let prompt_len = if shell.is_continuation {
-            2
-        } else {
-            current_dir.len() + 2
-        };
+        // Background jobs that ended or stopped since the last prompt
+        if !shell.is_continuation {
+            for line in shell.jobs.notify() {
+                print!("{}\r\n", line);
+            }
+        }
+
+        // Built once per line, so \t shows when the prompt appeared
+        refresh_git(&mut shell);
+        let prompt = build_prompt(&shell);
 
         try_log!(execute!(stdout(), MoveToColumn(0)), "Cursor reset error");
 
         let (_, mut start_y) = cursor::position().unwrap_or((0, 0));
+        shell.vi.start_line();
 
-        render_system(&shell, prompt_len, &mut start_y, &current_dir);
+        render_system(&shell, &prompt, &mut start_y);
 
         // --- 2. INPUT LOOP ---
         loop {
@@ -50,69 +84,67 @@ fn main() -> io::Result<()> {
                 if key.kind != KeyEventKind::Press {
                     continue;
                 }
-                match key.code {
-                    KeyCode::Char(c) => {
-                        if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'd' {
-                            print!("^D\r\n");
-
-                            return Ok(());
-                        }
-                        if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'c' {
-                            shell.commit_to_history();
-                            shell.reset_buffers();
-                            shell.is_continuation = false;
-                            print!("^C\r\n");
-                            break;
-                        }
-
-                        let char_count = shell.view_buffer.chars().count();
+                if key.code != KeyCode::Tab {
+                    shell.completion = None;
+                }
+                // Ctrl-R mode gets the key first; Enter/arrows fall through
+                if shell.search.is_some() && on_search_key(&mut shell, key) {
+                    render_system(&shell, &prompt, &mut start_y);
+                    continue;
+                }
 
-                        if shell.cursor_idx >= char_count {
-                            shell.buffer.push(c);
-                            shell.view_buffer.push(c);
-                        } else {
-                            let view_byte_idx =
-                                get_byte_index(&shell.view_buffer, shell.cursor_idx);
+                // Vi mode handles its own keys; Enter and Ctrl keys fall through
+                if shell.options.vi && vi_mode::handle_key(&mut shell, key) {
+                    render_system(&shell, &prompt, &mut start_y);
+                    continue;
+                }
 
-                            // Calculate where to insert in the main buffer
-                            // (If continuation, buffer includes previous lines)
-                            let buffer_base_len = shell.buffer.len() - shell.view_buffer.len();
-                            let buffer_byte_idx = buffer_base_len + view_byte_idx;
+                let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                match key.code {
+                    KeyCode::Char('d') if ctrl => {
+                        print!("^D\r\n");
 
-                            shell.buffer.insert(buffer_byte_idx, c);
-                            shell.view_buffer.insert(view_byte_idx, c);
-                        }
-                        shell.cursor_idx += 1;
+                        return Ok(());
                     }
-
-                    KeyCode::Backspace => {
-                        if !shell.view_buffer.is_empty() && shell.cursor_idx > 0 {
-                            let view_byte_idx =
-                                get_byte_index(&shell.view_buffer, shell.cursor_idx - 1);
-
-                            let buffer_base_len = shell.buffer.len() - shell.view_buffer.len();
-                            let buffer_byte_idx = buffer_base_len + view_byte_idx;
-
-                            shell.buffer.remove(buffer_byte_idx);
-                            shell.view_buffer.remove(view_byte_idx);
-                            shell.cursor_idx -= 1;
-                        }
+                    KeyCode::Char('c') if ctrl => {
+                        shell.commit_to_history();
+                        shell.reset_buffers();
+                        shell.is_continuation = false;
+                        print!("^C\r\n");
+                        break;
+                    }
+                    KeyCode::Char('r') if ctrl => start_search(&mut shell),
+                    // Clear the screen but keep what is being typed
+                    KeyCode::Char('l') if ctrl => {
+                        try_log!(
+                            execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0)),
+                            "Clear screen error"
+                        );
+                        start_y = 0;
                     }
 
                     KeyCode::Enter => {
                         print!("\r\n");
                         try_log!(stdout().flush(), "Flush error");
 
-                        match parse_input(&shell.buffer) {
-                            ParseResult::Ok(cmd) => {
+                        match parse_input_with_aliases(&shell.buffer, &shell.aliases) {
+                            ParseResult::Ok(list) => {
                                 shell.commit_to_history();
-                                if let CommandEnum::Exit = cmd {
-                                    disable_raw_mode()?;
-                                    return Ok(());
+                                // Commands should not inherit the vi cursor shape
+                                if shell.options.vi {
+                                    try_log!(
+                                        execute!(stdout(), SetCursorStyle::DefaultUserShape),
+                                        "Cursor style error"
+                                    );
                                 }
                                 disable_raw_mode()?;
 
-                                execute(cmd, &mut shell.pwd);
+                                clear_interrupt();
+                                execute(&list, &mut shell);
+                                shell.git.mark_stale();
+                                if let Some(code) = shell.exit_code {
+                                    std::process::exit(code);
+                                }
                                 enable_raw_mode()?;
 
                                 shell.reset_buffers();
@@ -135,41 +167,19 @@ fn main() -> io::Result<()> {
                         }
                     }
 
-                    KeyCode::Up => {
-                        if shell.hist_idx > 0 {
-                            shell.hist_idx -= 1;
-                            shell.buffer = shell.history[shell.hist_idx].clone();
-                            shell.view_buffer = shell.history[shell.hist_idx].clone();
-                            shell.cursor_idx = shell.view_buffer.chars().count();
-                        }
-                    }
-                    KeyCode::Down => {
-                        if shell.hist_idx < shell.history.len() {
-                            shell.hist_idx += 1;
-                            if shell.hist_idx < shell.history.len() {
-                                shell.buffer = shell.history[shell.hist_idx].clone();
-                                shell.view_buffer = shell.history[shell.hist_idx].clone();
-                                shell.cursor_idx = shell.view_buffer.chars().count();
-                            } else {
-                                shell.reset_buffers();
-                            }
-                        }
-                    }
+                    KeyCode::Tab => on_tab(&mut shell),
 
-                    KeyCode::Left => {
-                        if shell.cursor_idx > 0 {
-                            shell.cursor_idx -= 1;
-                        }
-                    }
-                    KeyCode::Right => {
-                        if shell.cursor_idx < shell.view_buffer.chars().count() {
-                            shell.cursor_idx += 1;
-                        }
+                    // Everything else is line editing
+                    _ if !shell.options.vi => {
+                        line_editor::handle_key(&mut shell, key);
                     }
                     _ => {}
                 }
 
-                render_system(&shell, prompt_len, &mut start_y, &current_dir);
+                render_system(&shell, &prompt, &mut start_y);
+            } else if let Event::Resize(..) = event {
+                // Wrapping depends on the width, so lay everything out again
+                render_system(&shell, &prompt, &mut start_y);
             }
         }
     }
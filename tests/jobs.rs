@@ -0,0 +1,77 @@
+use std::process::{Command, Stdio};
+
+// --- Background Jobs ---
+// `cmd &` forks the shell, which is only safe in a single-threaded process,
+// so these run the real binary with `-c` instead of the test harness.
+
+// (stdout, stderr, status) of `0-shell -c script`
+fn shell(script: &str) -> (String, String, i32) {
+    let output = Command::new(env!("CARGO_BIN_EXE_shell"))
+        .args(["-c", script])
+        .current_dir(std::env::temp_dir())
+        .stdin(Stdio::null())
+        .output()
+        .expect("could not run the shell");
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+        output.status.code().unwrap_or(-1),
+    )
+}
+
+#[test]
+fn test_wait_gets_the_job_status() {
+    // The line goes on at once; `wait` gets the job's status
+    let (out, _, status) = shell("sh -c 'exit 3' & X=after; wait $!; echo $? $X");
+    assert_eq!(out, "3 after\n");
+    assert_eq!(status, 0);
+
+    let (out, _, _) = shell("sleep 0 & P=$!; test -n \"$P\" && wait $P && echo ok");
+    assert_eq!(out, "ok\n");
+
+    // Gone from the table once waited for
+    let (out, err, _) = shell("true & wait %1; wait %1; echo $?");
+    assert_eq!(out, "127\n");
+    assert!(err.contains("wait: %1: no such job"));
+}
+
+#[test]
+fn test_background_list_runs_in_a_fork() {
+    // A whole and-or list is one job, and its changes stay in the fork
+    let (out, _, _) = shell(
+        "{ Y=inside; echo bg; } && false || exit 5 & wait %1; echo $? ${Y:-unset}; echo still here",
+    );
+    assert_eq!(out, "bg\n5 unset\nstill here\n");
+
+    // Without job control a job reads nothing from our stdin
+    let (out, _, _) = shell("cat & wait; echo done");
+    assert_eq!(out, "done\n");
+}
+
+#[test]
+fn test_jobs_listing_and_disown() {
+    let (out, _, _) = shell("sleep 1 & sleep 1 & jobs; jobs -p %1 | grep -c .");
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "[1]-  Running                 sleep 1 &",
+            "[2]+  Running                 sleep 1 &",
+            "1",
+        ]
+    );
+
+    // A disowned job is no longer listed or waited for
+    let (out, _, _) = shell("sleep 1 & sleep 0 & disown %1; jobs; wait; echo $?; jobs");
+    assert_eq!(out, "[2]+  Running                 sleep 0 &\n0\n");
+}
+
+#[test]
+fn test_fg_and_bg_need_job_control() {
+    let (_, err, status) = shell("sleep 0 & fg");
+    assert_eq!(status, 1);
+    assert!(err.contains("fg: no job control"));
+    let (_, err, status) = shell("bg %1");
+    assert_eq!(status, 1);
+    assert!(err.contains("bg: no job control"));
+}